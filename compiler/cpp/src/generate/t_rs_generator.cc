@@ -18,6 +18,7 @@
  */
 
 #include <map>
+#include <set>
 #include <fstream>
 #include <sstream>
 #include <string>
@@ -30,6 +31,7 @@
 
 using std::map;
 using std::ofstream;
+using std::set;
 using std::string;
 using std::vector;
 
@@ -43,8 +45,18 @@ class t_rs_generator : public t_oop_generator {
                  const string& option_string)
     : t_oop_generator(program)
   {
-    (void) parsed_options;
     (void) option_string;
+    std::map<std::string, std::string>::const_iterator iter;
+
+    iter = parsed_options.find("unknown_fields");
+    gen_unknown_fields_ = (iter != parsed_options.end());
+
+    iter = parsed_options.find("newtypes");
+    gen_newtypes_ = (iter != parsed_options.end());
+
+    iter = parsed_options.find("strict_enums");
+    gen_strict_enums_ = (iter != parsed_options.end());
+
     // FIXME: change back to gen-rs when we finalize mod structure for generated code
     out_dir_base_ = "src";
   }
@@ -66,14 +78,20 @@ class t_rs_generator : public t_oop_generator {
   string rs_imports();
 
   string render_rs_type(t_type* type);
+  string render_field_type(t_field* tfield);
+  string render_derives(t_type* type);
+  bool is_comparable(t_type* type, bool& has_double, bool& has_hashed, set<t_struct*>& seen);
+  bool is_newtype(t_typedef* ttypedef);
+  bool keeps_unknown_fields(t_struct* tstruct);
+  bool defines_type(const string& name);
   string render_suffix(t_type* type);
   string render_type_init(t_type* type);
 
-  void generate_service_generics(t_service* tservice);
-  void generate_service_fields(t_service* tservice);
-  void generate_service_methods(char field, t_service* tservice);
+  void generate_service_methods(t_service* tservice, bool parent);
   void generate_service_method_arglist(const vector<t_field*>& fields);
-  void generate_service_uses(t_service* tservice);
+  string render_idempotent_methods(t_service* tservice);
+  vector<string> module_segments(t_program* program);
+  string render_module_path(t_program* program);
 
   /**
    *Transforms a string with words separated by underscores to a pascal case equivalent
@@ -113,6 +131,21 @@ class t_rs_generator : public t_oop_generator {
 
  private:
   ofstream f_mod_;
+
+  /**
+   * True if structs should keep the fields they don't know
+   */
+  bool gen_unknown_fields_;
+
+  /**
+   * True if typedefs should be distinct types rather than aliases
+   */
+  bool gen_newtypes_;
+
+  /**
+   * True if enums should reject the values they don't know
+   */
+  bool gen_strict_enums_;
 };
 
 /*
@@ -138,20 +171,10 @@ private:
 int t_temp_var::index_ = 0;
 
 
-/*
- * This is necessary because we want to generate use clauses for all services,
- */
 void t_rs_generator::generate_program() {
   // Initialize the generator
   init_generator();
 
-  // Generate service uses
-  vector<t_service*> services = program_->get_services();
-  vector<t_service*>::iterator sv_iter;
-  for (sv_iter = services.begin(); sv_iter != services.end(); ++sv_iter) {
-    generate_service_uses(*sv_iter);
-  }
-
   // Generate enums
   vector<t_enum*> enums = program_->get_enums();
   vector<t_enum*>::iterator en_iter;
@@ -179,6 +202,8 @@ void t_rs_generator::generate_program() {
   // generate_consts(consts);
 
   // Generate services
+  vector<t_service*> services = program_->get_services();
+  vector<t_service*>::iterator sv_iter;
   for (sv_iter = services.begin(); sv_iter != services.end(); ++sv_iter) {
     generate_service(*sv_iter);
   }
@@ -188,12 +213,50 @@ void t_rs_generator::generate_program() {
 }
 
 void t_rs_generator::init_generator() {
-  // Make output directory
+  // Types from included files, and from the files those include, are
+  // referred to by their module path, so two files must not end up in the
+  // same module.
+  const string module_path = render_module_path(NULL);
+  vector<t_program*> includes;
+  set<t_program*> seen;
+  seen.insert(program_);
+  vector<t_program*> pending(1, program_);
+  while (!pending.empty()) {
+    const vector<t_program*>& direct = pending.back()->get_includes();
+    pending.pop_back();
+    for (vector<t_program*>::const_iterator it = direct.begin(); it != direct.end(); ++it) {
+      if (seen.insert(*it).second) {
+        includes.push_back(*it);
+        pending.push_back(*it);
+      }
+    }
+  }
+
+  for (size_t i = 0; i < includes.size(); ++i) {
+    const string include_path = render_module_path(includes[i]);
+    for (size_t j = 0; j < i; ++j) {
+      if (render_module_path(includes[j]) == include_path) {
+        throw "included files " + includes[j]->get_path() + " and " + includes[i]->get_path()
+          + " are both generated to " + include_path.substr(0, include_path.size() - 2);
+      }
+    }
+    if (include_path == module_path) {
+      throw "included file " + includes[i]->get_path() + " is generated to "
+        + include_path.substr(0, include_path.size() - 2) + " like " + program_->get_path();
+    }
+  }
+
+  // Make output directory, one level per segment of the module path. The
+  // modules above the generated one are left for the crate to declare.
   // FIXME: enable when finalizing the code structure
   //MKDIR(get_out_dir().c_str());
-  string pname = underscore(program_name_);
-  string moddirname = get_out_dir() + pname + "/";
-  MKDIR(moddirname.c_str());
+  string moddirname = get_out_dir();
+  vector<string> segments = module_segments(program_);
+  vector<string>::const_iterator seg_iter;
+  for (seg_iter = segments.begin(); seg_iter != segments.end(); ++seg_iter) {
+    moddirname += *seg_iter + "/";
+    MKDIR(moddirname.c_str());
+  }
 
   // Make output file
   string f_mod_name = moddirname + "mod.rs";
@@ -224,11 +287,102 @@ string t_rs_generator::rs_imports() {
 }
 
 // Generates a type alias, translating a thrift `typedef` to a rust `type`.
+// Generates a typedef, as an alias or as a newtype wrapping the aliased type.
 void t_rs_generator::generate_typedef(t_typedef* ttypedef) {
   string tname = pascalcase(ttypedef->get_symbolic());
   string tdef = render_rs_type(ttypedef->get_type());
-  indent(f_mod_) << "pub type " << tname << " = " << tdef << ";\n";
+  if (!is_newtype(ttypedef)) {
+    indent(f_mod_) << "pub type " << tname << " = " << tdef << ";\n";
+    f_mod_ << "\n";
+    return;
+  }
+
+  indent(f_mod_) << "newtype! {\n";
+  indent_up();
+
+  indent(f_mod_) << "name = " << tname << ",\n";
+  indent(f_mod_) << "inner = " << tdef;
+
+  // Only emitted when needed, the macro defaults to no extra derives.
+  string derives = render_derives(ttypedef->get_type());
+  if (!derives.empty()) {
+    f_mod_ << ",\n";
+    indent(f_mod_) << "derive = [" << derives << "]";
+  }
   f_mod_ << "\n";
+
+  indent_down();
+  indent(f_mod_) << "}\n\n"; // Close newtype invocation.
+}
+
+// Whether the typedef is generated as a newtype, per its `rs.newtype`
+// annotation or the `newtypes` option.
+bool t_rs_generator::is_newtype(t_typedef* ttypedef) {
+  map<string, string>::const_iterator it = ttypedef->annotations_.find("rs.newtype");
+  return (it == ttypedef->annotations_.end()) ? gen_newtypes_ : it->second == "true";
+}
+
+// The traits a struct or newtype of the type derives besides Debug, Clone,
+// Default and PartialEq, which are those all its fields are known to
+// implement.
+string t_rs_generator::render_derives(t_type* type) {
+  bool has_double = false;
+  bool has_hashed = false;
+  set<t_struct*> seen;
+  if (!is_comparable(type, has_double, has_hashed, seen) || (has_double && has_hashed)) {
+    return "";
+  }
+  if (has_double) {
+    return "PartialOrd, ";
+  }
+  return has_hashed ? "Eq, " : "Eq, Hash, PartialOrd, Ord, ";
+}
+
+// Whether values of the type can be compared beyond PartialEq, noting in
+// `has_double` when they can't be totally, and in `has_hashed` when they hold
+// sets or maps, which can't be ordered or hashed. Structs keeping unknown
+// fields or with fields of a custom type can't be compared at all.
+bool t_rs_generator::is_comparable(t_type* type, bool& has_double, bool& has_hashed, set<t_struct*>& seen) {
+  while (type->is_typedef()) {
+    type = ((t_typedef*)type)->get_type();
+  }
+
+  if (type->is_base_type()) {
+    if (((t_base_type*)type)->get_base() == t_base_type::TYPE_DOUBLE) {
+      has_double = true;
+    }
+    return true;
+  } else if (type->is_enum()) {
+    return true;
+  } else if (type->is_list()) {
+    return is_comparable(((t_list*)type)->get_elem_type(), has_double, has_hashed, seen);
+  } else if (type->is_set()) {
+    has_hashed = true;
+    return is_comparable(((t_set*)type)->get_elem_type(), has_double, has_hashed, seen);
+  } else if (type->is_map()) {
+    has_hashed = true;
+    return is_comparable(((t_map*)type)->get_key_type(), has_double, has_hashed, seen)
+        && is_comparable(((t_map*)type)->get_val_type(), has_double, has_hashed, seen);
+  } else if (type->is_struct() || type->is_xception()) {
+    t_struct* tstruct = (t_struct*)type;
+    // Recursive structs are comparable if the rest of their fields are.
+    if (!seen.insert(tstruct).second) {
+      return true;
+    }
+    if (keeps_unknown_fields(tstruct)) {
+      return false;
+    }
+    vector<t_field*>::const_iterator m_iter;
+    const vector<t_field*>& members = tstruct->get_members();
+    for (m_iter = members.begin(); m_iter != members.end(); ++m_iter) {
+      if ((*m_iter)->annotations_.count("rs.type")
+          || !is_comparable((*m_iter)->get_type(), has_double, has_hashed, seen)) {
+        return false;
+      }
+    }
+    return true;
+  }
+  return false;
 }
 
 // Generates an enum, translating a thrift enum into a rust enum.
@@ -242,23 +396,56 @@ void t_rs_generator::generate_enum(t_enum* tenum) {
   indent(f_mod_) << "values = [\n";
   indent_up();
 
-  // Generate the enum variant declarations.
+  // Generate the enum variant declarations, with the name from the Thrift
+  // file when the variant's differs.
   vector<t_enum_value*> constants = tenum->get_constants();
   vector<t_enum_value*>::iterator i, end = constants.end();
   for (i = constants.begin(); i != end; ++i) {
     string name = capitalize((*i)->get_name());
     int value = (*i)->get_value();
-    indent(f_mod_) << name << " = " << value << ",\n";
+    indent(f_mod_) << name << " = " << value;
+    if (name != (*i)->get_name()) {
+      f_mod_ << " => \"" << (*i)->get_name() << "\"";
+    }
+    f_mod_ << ",\n";
   }
 
   indent_down();
   indent(f_mod_) << "],\n";
-  indent(f_mod_) << "default = " << capitalize(constants.at(0)->get_name()) << "\n";
+  indent(f_mod_) << "default = " << capitalize(constants.at(0)->get_name());
+
+  // Unless strict, the enum holds the values it doesn't know in a variant
+  // named so as not to clash with the others.
+  map<string, string>::const_iterator it = tenum->annotations_.find("rs.strict");
+  bool strict = (it == tenum->annotations_.end()) ? gen_strict_enums_ : it->second == "true";
+  if (!strict) {
+    string unknown = "Unknown";
+    bool clashes = true;
+    while (clashes) {
+      clashes = false;
+      for (i = constants.begin(); i != end; ++i) {
+        if (capitalize((*i)->get_name()) == unknown) {
+          unknown += "_";
+          clashes = true;
+        }
+      }
+    }
+    f_mod_ << ",\n";
+    indent(f_mod_) << "unknown = " << unknown;
+  }
+  f_mod_ << "\n";
 
   indent_down();
   indent(f_mod_) << "}\n\n"; // Close enom invocation.
 }
 
+// Whether the struct keeps the fields it doesn't know, per its
+// `rs.unknown_fields` annotation or the `unknown_fields` option.
+bool t_rs_generator::keeps_unknown_fields(t_struct* tstruct) {
+  map<string, string>::const_iterator it = tstruct->annotations_.find("rs.unknown_fields");
+  return (it == tstruct->annotations_.end()) ? gen_unknown_fields_ : it->second == "true";
+}
+
 // Generate a struct, translating a thrift struct into a rust struct.
 void t_rs_generator::generate_struct(t_struct* tstruct) {
   string sname = pascalcase(tstruct->get_name());
@@ -276,21 +463,106 @@ void t_rs_generator::generate_struct(t_struct* tstruct) {
   for (m_iter = members.begin(); m_iter != members.end(); ++m_iter) {
     t_field* tfield = *m_iter;
     indent(f_mod_) << to_field_name(tfield->get_name())
-      << ": " << render_rs_type(tfield->get_type())
+      << ": " << render_field_type(tfield)
       << " => " << tfield->get_key() << ",\n";
   }
 
   indent_down();
-  indent(f_mod_) << "}\n";
+  indent(f_mod_) << "}";
+
+  string derives = render_derives(tstruct);
+  if (keeps_unknown_fields(tstruct)) {
+    // The member holding them is named so as not to clash with the fields.
+    string unknown = "unknown_fields";
+    bool clashes = true;
+    while (clashes) {
+      clashes = false;
+      for (m_iter = members.begin(); m_iter != members.end(); ++m_iter) {
+        if (to_field_name((*m_iter)->get_name()) == unknown) {
+          unknown += "_";
+          clashes = true;
+        }
+      }
+    }
+    f_mod_ << ",\n";
+    indent(f_mod_) << "unknown_fields = " << unknown;
+  } else if (!derives.empty()) {
+    f_mod_ << ",\n";
+    indent(f_mod_) << "derive = [" << derives << "]";
+  }
+
+  // The builder, checking the required fields are set, and the accessors of
+  // each field. Structs without fields do without.
+  if (!members.empty()) {
+    for (m_iter = members.begin(); m_iter != members.end(); ++m_iter) {
+      if (to_field_name((*m_iter)->get_name()) == "build") {
+        throw "field build of " + tstruct->get_name() + " clashes with the build method of its builder";
+      }
+    }
+    // The builder is named so as not to clash with the types of the program.
+    string builder = sname + "Builder";
+    while (defines_type(builder)) {
+      builder += "_";
+    }
+
+    f_mod_ << ",\n";
+    indent(f_mod_) << "builder = " << builder << ",\n";
+    indent(f_mod_) << "accessors = [\n";
+    indent_up();
+
+    string required;
+    for (m_iter = members.begin(); m_iter != members.end(); ++m_iter) {
+      string name = underscore((*m_iter)->get_name());
+      indent(f_mod_) << "set_" << name << " take_" << name << " has_" << name << ",\n";
+      if ((*m_iter)->get_req() == t_field::T_REQUIRED) {
+        required += to_field_name((*m_iter)->get_name()) + ", ";
+      }
+    }
+
+    indent_down();
+    indent(f_mod_) << "],\n";
+    indent(f_mod_) << "required = [" << required << "]";
+  }
+  f_mod_ << "\n";
 
   indent_down();
   indent(f_mod_) << "}\n\n"; // Close strukt invocation.
 }
 
+// Whether the program defines a type, or a service, generated as `name`.
+bool t_rs_generator::defines_type(const string& name) {
+  const vector<t_typedef*>& typedefs = program_->get_typedefs();
+  for (vector<t_typedef*>::const_iterator i = typedefs.begin(); i != typedefs.end(); ++i) {
+    if (pascalcase((*i)->get_symbolic()) == name) {
+      return true;
+    }
+  }
+  const vector<t_enum*>& enums = program_->get_enums();
+  for (vector<t_enum*>::const_iterator i = enums.begin(); i != enums.end(); ++i) {
+    if (pascalcase((*i)->get_name()) == name) {
+      return true;
+    }
+  }
+  const vector<t_struct*>& objects = program_->get_objects();
+  for (vector<t_struct*>::const_iterator i = objects.begin(); i != objects.end(); ++i) {
+    if (pascalcase((*i)->get_name()) == name) {
+      return true;
+    }
+  }
+  const vector<t_service*>& services = program_->get_services();
+  for (vector<t_service*>::const_iterator i = services.begin(); i != services.end(); ++i) {
+    if (pascalcase((*i)->get_name()) == name) {
+      return true;
+    }
+  }
+  return false;
+}
+
 // Generate a service, translating from a thrift service to a rust trait.
 void t_rs_generator::generate_service(t_service* tservice) {
     const string sname = pascalcase(tservice->get_name());
     const string trait_name = sname;
+    const string context_trait_name = sname + "WithContext";
     const string processor_name = sname + "Processor";
     const string client_name = sname + "Client";
 
@@ -299,47 +571,58 @@ void t_rs_generator::generate_service(t_service* tservice) {
 
     // Trait, processor and client type names.
     indent(f_mod_) << "trait_name = " << trait_name << ",\n";
+    indent(f_mod_) << "context_trait_name = " << context_trait_name << ",\n";
     indent(f_mod_) << "processor_name = " << processor_name << ",\n";
     indent(f_mod_) << "client_name = " << client_name << ",\n";
 
+    // The service trait extends the trait of the parent, so that a single
+    // handler serves the whole chain.
+    indent(f_mod_) << "parents = [";
+    t_service* extends = tservice->get_extends();
+    if (extends) {
+      const string parent_path = render_module_path(extends->get_program()) + pascalcase(extends->get_name());
+      f_mod_ << parent_path << " => " << parent_path << "WithContext, ";
+    }
+    f_mod_ << "],\n";
+
     // The methods originating in this service to go in the service trait.
     indent(f_mod_) << "service_methods = [\n";
     indent_up();
 
-    generate_service_methods('a', tservice);
+    generate_service_methods(tservice, false);
 
     indent_down();
     indent(f_mod_) << "],\n";
 
-    // The methods from parent services that need to go in the processor.
+    // The methods from all the ancestors, which the processor and the client
+    // handle too.
     indent(f_mod_) << "parent_methods = [\n";
     indent_up();
 
-    char field;
-    t_service* parent;
-    for (parent = tservice->get_extends(), field = 'b';
-         parent && field <= 'z';
-         parent = parent->get_extends(), field++) {
-        generate_service_methods(field, parent);
+    for (t_service* parent = extends; parent; parent = parent->get_extends()) {
+        generate_service_methods(parent, true);
     }
 
     indent_down();
-    indent(f_mod_) << "],\n";
-
-    indent(f_mod_) << "bounds = [";
-    generate_service_generics(tservice);
-    f_mod_ << "],\n";
+    indent(f_mod_) << "]";
 
-    indent(f_mod_) << "fields = [";
-    generate_service_fields(tservice);
-    f_mod_ << "]\n";
+    // Only emitted when needed, the macro defaults to no idempotent methods.
+    string idempotent = render_idempotent_methods(tservice);
+    if (!idempotent.empty()) {
+      f_mod_ << ",\n";
+      indent(f_mod_) << "idempotent = [" << idempotent << "]";
+    }
+    f_mod_ << "\n";
 
     indent_down();
     indent(f_mod_) << "}\n\n";
 }
 
-void t_rs_generator::generate_service_methods(char field, t_service* tservice) {
+// The methods of the service; those of parents name their argument and
+// result structs by path, since they may be defined in another module.
+void t_rs_generator::generate_service_methods(t_service* tservice, bool parent) {
     const string sname = pascalcase(tservice->get_name());
+    const string path = render_module_path(tservice->get_program());
 
     vector<t_function*> functions = tservice->get_functions();
     vector<t_function*>::const_iterator f_iter;
@@ -348,8 +631,12 @@ void t_rs_generator::generate_service_methods(char field, t_service* tservice) {
         const string argname = sname + pascalcase(tfunction->get_name()) + "Args";
         const string resname = sname + pascalcase(tfunction->get_name()) + "Result";
 
-        indent(f_mod_) << argname << " -> " << resname << " = "
-          << field << "." << tfunction->get_name() << "(\n";
+        if (parent) {
+          indent(f_mod_) << "(" << path << argname << ", " << path << resname << ") = ";
+        } else {
+          indent(f_mod_) << argname << " -> " << resname << " = ";
+        }
+        f_mod_ << tfunction->get_name() << "(\n";
 
         indent_up();
         generate_service_method_arglist(tfunction->get_arglist()->get_members());
@@ -365,30 +652,6 @@ void t_rs_generator::generate_service_methods(char field, t_service* tservice) {
     }
 }
 
-void t_rs_generator::generate_service_generics(t_service* tservice) {
-  t_service* parent = tservice;
-  char generic = 'A';
-
-  while (parent && generic <= 'Z') {
-    f_mod_ << generic << ": " << parent->get_name() << ", ";
-    parent = parent->get_extends();
-    generic++;
-  }
-}
-
-void t_rs_generator::generate_service_fields(t_service* tservice) {
-  t_service* parent = tservice;
-  char generic = 'A';
-  char field = 'a';
-
-  while (parent && generic <= 'Z' && field <= 'z') {
-    f_mod_ << field << ": " << generic << ", ";
-    parent = parent->get_extends();
-    generic++;
-    field++;
-  }
-}
-
 void t_rs_generator::generate_service_method_arglist(const vector<t_field*>& fields) {
     vector<t_field*>::const_iterator field_iter;
     for (field_iter = fields.begin(); field_iter != fields.end(); ++field_iter) {
@@ -399,18 +662,94 @@ void t_rs_generator::generate_service_method_arglist(const vector<t_field*>& fie
     }
 }
 
-void t_rs_generator::generate_service_uses(t_service* tservice) {
-  t_service* service = tservice->get_extends();
-  while (service) {
-    indent(f_mod_) << "use " << service->get_program()->get_name() << "::*;\n";
-    service = service->get_extends();
+// Lists the methods of the service and its parents annotated with
+// `rs.idempotent = "true"`, which clients are allowed to retry.
+string t_rs_generator::render_idempotent_methods(t_service* tservice) {
+  string methods;
+  for (t_service* service = tservice; service; service = service->get_extends()) {
+    vector<t_function*> functions = service->get_functions();
+    vector<t_function*>::const_iterator f_iter;
+    for (f_iter = functions.begin(); f_iter != functions.end(); ++f_iter) {
+      map<string, string>::const_iterator it = (*f_iter)->annotations_.find("rs.idempotent");
+      if (it != (*f_iter)->annotations_.end() && it->second == "true") {
+        methods += (*f_iter)->get_name() + ", ";
+      }
+    }
   }
-  indent(f_mod_) << "\n";
+  return methods;
+}
+
+// The modules the code for `program` goes in: the segments of its
+// `namespace rs a.b`, or the name of the file when it declares none. The
+// `namespace *` of other languages is ignored, as it names packages rather
+// than modules of the crate. A first segment `thrift` becomes `thrift_`, not
+// to shadow the thrift crate.
+vector<string> t_rs_generator::module_segments(t_program* program) {
+  vector<string> segments;
+  map<string, string>::const_iterator ns = program->get_namespaces().find("rs");
+  if (ns == program->get_namespaces().end() || ns->second.empty()) {
+    segments.push_back(normalize_id(underscore(program->get_name())));
+  } else {
+    string::size_type start = 0;
+    string::size_type end;
+    do {
+      end = ns->second.find('.', start);
+      segments.push_back(normalize_id(ns->second.substr(start, end - start)));
+      start = end + 1;
+    } while (end != string::npos);
+  }
+
+  if (segments[0] == "thrift") {
+    segments[0] += "_";
+  }
+  return segments;
+}
+
+// The absolute path to the module generated for `program`, ending in `::`.
+// Empty for the program being generated, whose items are in scope; passing
+// NULL gives its path anyway.
+string t_rs_generator::render_module_path(t_program* program) {
+  if (program == program_) {
+    return "";
+  }
+
+  string path = "crate::";
+  vector<string> segments = module_segments(program ? program : program_);
+  vector<string>::const_iterator seg_iter;
+  for (seg_iter = segments.begin(); seg_iter != segments.end(); ++seg_iter) {
+    path += *seg_iter + "::";
+  }
+  return path;
+}
+
+// Renders the type of a struct field: the one given by `rs.type` if any,
+// followed by the converter given by `rs.convert` when the type can't be
+// encoded itself.
+string t_rs_generator::render_field_type(t_field* tfield) {
+  map<string, string>::const_iterator rs_type = tfield->annotations_.find("rs.type");
+  map<string, string>::const_iterator rs_convert = tfield->annotations_.find("rs.convert");
+  if (rs_type == tfield->annotations_.end()) {
+    if (rs_convert != tfield->annotations_.end()) {
+      throw "field " + tfield->get_name() + " has rs.convert but no rs.type";
+    }
+    return render_rs_type(tfield->get_type());
+  }
+
+  if (rs_convert == tfield->annotations_.end()) {
+    return rs_type->second;
+  }
+  return rs_type->second + " as " + rs_convert->second;
 }
 
 // Renders a rust type representing the passed in type.
 string t_rs_generator::render_rs_type(t_type* type) {
-  type = get_true_type(type);
+  // Typedefs are aliases, except for newtypes.
+  while (type->is_typedef()) {
+    if (is_newtype((t_typedef*)type)) {
+      return render_module_path(type->get_program()) + pascalcase(((t_typedef*)type)->get_symbolic());
+    }
+    type = ((t_typedef*)type)->get_type();
+  }
 
   if (type->is_base_type()) {
     t_base_type::t_base tbase = ((t_base_type*)type)->get_base();
@@ -434,10 +773,10 @@ string t_rs_generator::render_rs_type(t_type* type) {
     }
 
   } else if (type->is_enum()) {
-    return capitalize(((t_enum*)type)->get_name());
+    return render_module_path(type->get_program()) + capitalize(((t_enum*)type)->get_name());
 
   } else if (type->is_struct() || type->is_xception()) {
-    return capitalize(((t_struct*)type)->get_name());
+    return render_module_path(type->get_program()) + capitalize(((t_struct*)type)->get_name());
 
   } else if (type->is_map()) {
     t_type* ktype = ((t_map*)type)->get_key_type();
@@ -458,5 +797,13 @@ string t_rs_generator::render_rs_type(t_type* type) {
   return ""; // silence the compiler warning
 }
 
-THRIFT_REGISTER_GENERATOR(rs, "Rust", "")
+THRIFT_REGISTER_GENERATOR(
+    rs,
+    "Rust",
+    "    unknown_fields:  Keep the fields structs don't know in unknown_fields, and write them\n"
+    "                     back when encoding. Override per struct with rs.unknown_fields.\n"
+    "    newtypes:        Generate typedefs as newtypes rather than aliases. Override per\n"
+    "                     typedef with rs.newtype.\n"
+    "    strict_enums:    Fail to decode enum values not in the Thrift file, rather than keeping\n"
+    "                     them in an Unknown variant. Override per enum with rs.strict.\n")
 
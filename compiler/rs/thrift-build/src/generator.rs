@@ -0,0 +1,624 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+//! Renders a program as the C++ compiler's `rs` generator does, in
+//! `compiler/cpp/src/generate/t_rs_generator.cc`. Both must be kept in step.
+
+use std::collections::HashSet;
+
+use thrift_idl::ast::{self, Definition, Enum, Field, Requiredness, Service, Struct, Type, Typedef};
+
+use program::{Program, Programs};
+use Error;
+
+const THRIFT_VERSION: &str = "1.0.0-dev";
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "alignof", "as", "be", "box", "break", "const", "continue", "crate", "do", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "offsetof", "override", "priv", "pub", "pure", "ref", "return",
+    "sizeof", "static", "self", "struct", "super", "true", "trait", "type", "typeof", "unsafe",
+    "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The options of the C++ generator, as in `--gen rs:unknown_fields`.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub unknown_fields: bool,
+    pub newtypes: bool,
+    pub strict_enums: bool,
+}
+
+pub struct Generator<'a> {
+    programs: &'a Programs,
+    program: usize,
+    options: &'a Options,
+    out: String,
+    indent: usize,
+}
+
+// What keeps comparable types from deriving every trait: doubles have no total
+// order, and sets and maps can be neither hashed nor ordered.
+#[derive(Default)]
+struct Limits {
+    has_double: bool,
+    has_hashed: bool,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(programs: &'a Programs, program: usize, options: &'a Options) -> Generator<'a> {
+        Generator { programs, program, options, out: String::new(), indent: 0 }
+    }
+
+    /// The contents of the module for the program.
+    pub fn generate(mut self) -> Result<String, Error> {
+        let programs = self.programs;
+        let definitions = &programs.get(self.program).document.definitions;
+
+        self.out.push_str(&autogen_comment());
+        self.out.push('\n');
+        self.out.push_str("#![allow(unused_mut, dead_code, non_snake_case)]\n");
+        self.out.push_str("#[allow(unused_imports)]\n");
+        self.out.push_str("use std::collections::{HashMap, HashSet};\n");
+        self.out.push('\n');
+
+        for definition in definitions {
+            if let Definition::Enum(ref tenum) = *definition {
+                self.enumeration(tenum)?;
+            }
+        }
+        for definition in definitions {
+            match *definition {
+                Definition::Typedef(ref typedef) => self.typedef(typedef)?,
+                Definition::Senum(ref senum) => {
+                    self.line(&format!("pub type {} = String;", pascalcase(&senum.name)));
+                    self.out.push('\n');
+                }
+                _ => {}
+            }
+        }
+        for definition in definitions {
+            match *definition {
+                Definition::Struct(ref tstruct) |
+                Definition::Union(ref tstruct) |
+                Definition::Exception(ref tstruct) => self.structure(tstruct)?,
+                _ => {}
+            }
+        }
+        for definition in definitions {
+            if let Definition::Service(ref service) = *definition {
+                self.service(service)?;
+            }
+        }
+
+        Ok(self.out)
+    }
+
+    // An alias, or a newtype wrapping the aliased type.
+    fn typedef(&mut self, typedef: &Typedef) -> Result<(), Error> {
+        let name = pascalcase(&typedef.name);
+        let ty = self.rs_type(self.program, &typedef.ty)?;
+        if !self.is_newtype(typedef) {
+            self.line(&format!("pub type {} = {};", name, ty));
+            self.out.push('\n');
+            return Ok(());
+        }
+
+        self.line("newtype! {");
+        self.indent += 1;
+        self.line(&format!("name = {},", name));
+        self.start_line(&format!("inner = {}", ty));
+
+        // Only emitted when needed, the macro defaults to no extra derives.
+        let mut limits = Limits::default();
+        let comparable = self.is_comparable(self.program, &typedef.ty, &mut limits, &mut HashSet::new())?;
+        let derives = Generator::derives(comparable, &limits);
+        if !derives.is_empty() {
+            self.out.push_str(",\n");
+            self.start_line(&format!("derive = [{}]", derives));
+        }
+        self.out.push('\n');
+
+        self.indent -= 1;
+        self.line("}");
+        self.out.push('\n');
+        Ok(())
+    }
+
+    // Whether the typedef is generated as a newtype, per its `rs.newtype`
+    // annotation or the `newtypes` option.
+    fn is_newtype(&self, typedef: &Typedef) -> bool {
+        match ast::annotation(&typedef.annotations, "rs.newtype") {
+            Some(value) => value == "true",
+            None => self.options.newtypes,
+        }
+    }
+
+    // The traits a struct or newtype derives besides Debug, Clone, Default
+    // and PartialEq, which are those all its fields are known to implement.
+    fn derives(comparable: bool, limits: &Limits) -> &'static str {
+        match (comparable, limits.has_double, limits.has_hashed) {
+            (false, _, _) | (true, true, true) => "",
+            (true, true, false) => "PartialOrd, ",
+            (true, false, true) => "Eq, ",
+            (true, false, false) => "Eq, Hash, PartialOrd, Ord, ",
+        }
+    }
+
+    // Whether values of the type can be compared beyond PartialEq, noting in
+    // `limits` what keeps them from deriving every trait. Structs keeping
+    // unknown fields or with fields of a custom type can't be compared at all.
+    fn is_comparable(&self, program: usize, ty: &Type, limits: &mut Limits,
+                     seen: &mut HashSet<(usize, String)>) -> Result<bool, Error> {
+        let comparable = match *ty {
+            Type::Double => {
+                limits.has_double = true;
+                true
+            }
+            Type::Bool | Type::Byte | Type::I16 | Type::I32 | Type::I64 | Type::String | Type::Binary => true,
+            Type::List(ref elem) => self.is_comparable(program, elem, limits, seen)?,
+            Type::Set(ref elem) => {
+                limits.has_hashed = true;
+                self.is_comparable(program, elem, limits, seen)?
+            }
+            Type::Map(ref key, ref value) => {
+                limits.has_hashed = true;
+                self.is_comparable(program, key, limits, seen)? && self.is_comparable(program, value, limits, seen)?
+            }
+            Type::Named(ref name) => {
+                let (defining, definition) = self.programs.lookup(program, name)?;
+                match *definition {
+                    Definition::Typedef(ref typedef) => self.is_comparable(defining, &typedef.ty, limits, seen)?,
+                    Definition::Senum(_) | Definition::Enum(_) => true,
+                    Definition::Struct(ref tstruct) |
+                    Definition::Union(ref tstruct) |
+                    Definition::Exception(ref tstruct) => self.is_struct_comparable(defining, tstruct, limits, seen)?,
+                    _ => false,
+                }
+            }
+        };
+        Ok(comparable)
+    }
+
+    fn is_struct_comparable(&self, program: usize, tstruct: &Struct, limits: &mut Limits,
+                            seen: &mut HashSet<(usize, String)>) -> Result<bool, Error> {
+        // Recursive structs are comparable if the rest of their fields are.
+        if !seen.insert((program, tstruct.name.clone())) {
+            return Ok(true);
+        }
+        if self.keeps_unknown_fields(tstruct) {
+            return Ok(false);
+        }
+        for field in &tstruct.fields {
+            if ast::annotation(&field.annotations, "rs.type").is_some() ||
+               !self.is_comparable(program, &field.ty, limits, seen)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Whether the struct keeps the fields it doesn't know, per its
+    // `rs.unknown_fields` annotation or the `unknown_fields` option.
+    fn keeps_unknown_fields(&self, tstruct: &Struct) -> bool {
+        match ast::annotation(&tstruct.annotations, "rs.unknown_fields") {
+            Some(value) => value == "true",
+            None => self.options.unknown_fields,
+        }
+    }
+
+    fn enumeration(&mut self, tenum: &Enum) -> Result<(), Error> {
+        if tenum.values.is_empty() {
+            return Err(self.error(format!("enum {} has no values", tenum.name)));
+        }
+
+        self.line("enom! {");
+        self.indent += 1;
+        self.line(&format!("name = {},", pascalcase(&tenum.name)));
+        self.line("values = [");
+        self.indent += 1;
+
+        // Values without an explicit one follow the previous value.
+        let mut value = -1;
+        for enum_value in &tenum.values {
+            value = match enum_value.value {
+                Some(x) => x,
+                None => value + 1,
+            };
+            if value < i64::from(i32::MIN) || value > i64::from(i32::MAX) {
+                return Err(self.error(format!("value of {}.{} is out of range", tenum.name, enum_value.name)));
+            }
+            // The name from the Thrift file, when the variant's differs.
+            let name = capitalize(&enum_value.name);
+            if name == enum_value.name {
+                self.line(&format!("{} = {},", name, value));
+            } else {
+                self.line(&format!("{} = {} => \"{}\",", name, value, enum_value.name));
+            }
+        }
+
+        self.indent -= 1;
+        self.line("],");
+        self.start_line(&format!("default = {}", capitalize(&tenum.values[0].name)));
+
+        // Unless strict, the enum holds the values it doesn't know in a
+        // variant named so as not to clash with the others.
+        let strict = match ast::annotation(&tenum.annotations, "rs.strict") {
+            Some(value) => value == "true",
+            None => self.options.strict_enums,
+        };
+        if !strict {
+            let mut unknown = String::from("Unknown");
+            while tenum.values.iter().any(|value| capitalize(&value.name) == unknown) {
+                unknown.push('_');
+            }
+            self.out.push_str(",\n");
+            self.start_line(&format!("unknown = {}", unknown));
+        }
+        self.out.push('\n');
+        self.indent -= 1;
+        self.line("}");
+        self.out.push('\n');
+        Ok(())
+    }
+
+    fn structure(&mut self, tstruct: &Struct) -> Result<(), Error> {
+        let sname = pascalcase(&tstruct.name);
+        self.line("strukt! {");
+        self.indent += 1;
+        self.line(&format!("name = {},", sname));
+        self.line("fields = {");
+        self.indent += 1;
+        self.fields(self.program, &tstruct.fields, true)?;
+        self.indent -= 1;
+
+        let mut limits = Limits::default();
+        let comparable = self.is_struct_comparable(self.program, tstruct, &mut limits, &mut HashSet::new())?;
+        let derives = Generator::derives(comparable, &limits);
+        self.start_line("}");
+        if self.keeps_unknown_fields(tstruct) {
+            // The member holding them is named so as not to clash with the
+            // fields.
+            let mut unknown = String::from("unknown_fields");
+            while tstruct.fields.iter().any(|field| to_field_name(&field.name) == unknown) {
+                unknown.push('_');
+            }
+            self.out.push_str(",\n");
+            self.start_line(&format!("unknown_fields = {}", unknown));
+        } else if !derives.is_empty() {
+            self.out.push_str(",\n");
+            self.start_line(&format!("derive = [{}]", derives));
+        }
+
+        // The builder, checking the required fields are set, and the
+        // accessors of each field. Structs without fields do without.
+        if !tstruct.fields.is_empty() {
+            if tstruct.fields.iter().any(|field| to_field_name(&field.name) == "build") {
+                return Err(self.error(format!("field build of {} clashes with the build method of its builder",
+                                              tstruct.name)));
+            }
+            // The builder is named so as not to clash with the types of the
+            // program.
+            let mut builder = format!("{}Builder", sname);
+            while self.defines_type(&builder) {
+                builder.push('_');
+            }
+
+            self.out.push_str(",\n");
+            self.line(&format!("builder = {},", builder));
+            self.line("accessors = [");
+            self.indent += 1;
+            let mut required = String::new();
+            for field in &tstruct.fields {
+                let name = underscore(&field.name);
+                self.line(&format!("set_{0} take_{0} has_{0},", name));
+                if field.requiredness == Requiredness::Required {
+                    required.push_str(&format!("{}, ", to_field_name(&field.name)));
+                }
+            }
+            self.indent -= 1;
+            self.line("],");
+            self.start_line(&format!("required = [{}]", required));
+        }
+        self.out.push('\n');
+
+        self.indent -= 1;
+        self.line("}");
+        self.out.push('\n');
+        Ok(())
+    }
+
+    // Whether the program defines a type, or a service, generated as `name`.
+    fn defines_type(&self, name: &str) -> bool {
+        self.programs.get(self.program).document.definitions.iter().any(|definition| match *definition {
+            Definition::Const(_) => false,
+            _ => pascalcase(definition.name()) == name,
+        })
+    }
+
+    fn service(&mut self, service: &'a Service) -> Result<(), Error> {
+        let sname = pascalcase(&service.name);
+        let parents = self.parents(self.program, service)?;
+
+        self.line("service! {");
+        self.indent += 1;
+        self.line(&format!("trait_name = {},", sname));
+        self.line(&format!("context_trait_name = {}WithContext,", sname));
+        self.line(&format!("processor_name = {}Processor,", sname));
+        self.line(&format!("client_name = {}Client,", sname));
+
+        // The service trait extends the trait of the parent, so that a single
+        // handler serves the whole chain.
+        match parents.get(1) {
+            Some(&(program, parent)) => {
+                let path = self.module_path(program) + &pascalcase(&parent.name);
+                self.line(&format!("parents = [{0} => {0}WithContext, ],", path));
+            }
+            None => self.line("parents = [],"),
+        }
+
+        self.line("service_methods = [");
+        self.indent += 1;
+        self.service_methods(self.program, service, false)?;
+        self.indent -= 1;
+        self.line("],");
+
+        self.line("parent_methods = [");
+        self.indent += 1;
+        for &(program, parent) in parents.iter().skip(1) {
+            self.service_methods(program, parent, true)?;
+        }
+        self.indent -= 1;
+        self.start_line("]");
+
+        // Only emitted when needed, the macro defaults to no idempotent methods.
+        let idempotent: String = parents.iter()
+            .flat_map(|&(_, parent)| parent.functions.iter())
+            .filter(|function| ast::annotation(&function.annotations, "rs.idempotent") == Some("true"))
+            .map(|function| format!("{}, ", function.name))
+            .collect();
+        if !idempotent.is_empty() {
+            self.out.push_str(",\n");
+            self.start_line(&format!("idempotent = [{}]", idempotent));
+        }
+        self.out.push('\n');
+
+        self.indent -= 1;
+        self.line("}");
+        self.out.push('\n');
+        Ok(())
+    }
+
+    // The methods of the service; those of parents name their argument and
+    // result structs by path, since they may be defined in another module.
+    fn service_methods(&mut self, program: usize, service: &Service, parent: bool) -> Result<(), Error> {
+        let sname = pascalcase(&service.name);
+        let path = self.module_path(program);
+        for function in &service.functions {
+            let prefix = sname.clone() + &pascalcase(&function.name);
+            if parent {
+                self.line(&format!("({0}{1}Args, {0}{1}Result) = {2}(", path, prefix, function.name));
+            } else {
+                self.line(&format!("{0}Args -> {0}Result = {1}(", prefix, function.name));
+            }
+            self.indent += 1;
+            self.fields(program, &function.params, false)?;
+            self.indent -= 1;
+
+            let return_type = match function.return_type {
+                Some(ref ty) => self.rs_type(program, ty)?,
+                None => String::from("()"),
+            };
+            self.line(&format!(") -> {} => [", return_type));
+            self.indent += 1;
+            self.fields(program, &function.throws, false)?;
+            self.indent -= 1;
+            self.line("],");
+        }
+        Ok(())
+    }
+
+    // Struct fields may have their type given by `rs.type`, followed by the
+    // converter given by `rs.convert` when the type can't be encoded itself.
+    fn fields(&mut self, program: usize, fields: &[Field], struct_fields: bool) -> Result<(), Error> {
+        // Like the C++ compiler, fields without a positive id are numbered
+        // downwards from -1.
+        let mut auto_id = -1;
+        for field in fields {
+            let id = match field.id {
+                Some(id) if id > 0 => id,
+                _ => {
+                    auto_id -= 1;
+                    auto_id + 1
+                }
+            };
+            let rs_type = ast::annotation(&field.annotations, "rs.type").filter(|_| struct_fields);
+            let rs_convert = ast::annotation(&field.annotations, "rs.convert").filter(|_| struct_fields);
+            let ty = match (rs_type, rs_convert) {
+                (None, None) => self.rs_type(program, &field.ty)?,
+                (None, Some(_)) => {
+                    return Err(self.error(format!("field {} has rs.convert but no rs.type", field.name)));
+                }
+                (Some(ty), None) => String::from(ty),
+                (Some(ty), Some(convert)) => format!("{} as {}", ty, convert),
+            };
+            self.line(&format!("{}: {} => {},", to_field_name(&field.name), ty, id));
+        }
+        Ok(())
+    }
+
+    // The service and its ancestors, with the programs defining them.
+    fn parents(&self, program: usize, service: &'a Service) -> Result<Vec<(usize, &'a Service)>, Error> {
+        let mut parents = vec![(program, service)];
+        loop {
+            let (program, service) = parents[parents.len() - 1];
+            let name = match service.extends {
+                Some(ref name) => name,
+                None => break,
+            };
+            let (defining, definition) = self.programs.lookup(program, name)?;
+            let parent = match *definition {
+                Definition::Service(ref parent) => parent,
+                _ => return Err(self.error(format!("`{}` is not a service", name))),
+            };
+            // Unlike the C++ compiler, names may refer to later definitions.
+            if parents.iter().any(|&(program, service)| program == defining && service.name == parent.name) {
+                return Err(self.error(format!("service `{}` extends itself", parent.name)));
+            }
+            parents.push((defining, parent));
+        }
+        Ok(parents)
+    }
+
+    fn rs_type(&self, program: usize, ty: &Type) -> Result<String, Error> {
+        let rendered = match *ty {
+            Type::Bool => String::from("bool"),
+            Type::Byte => String::from("i8"),
+            Type::I16 => String::from("i16"),
+            Type::I32 => String::from("i32"),
+            Type::I64 => String::from("i64"),
+            Type::Double => String::from("f64"),
+            Type::String => String::from("String"),
+            Type::Binary => String::from("Vec<u8>"),
+            Type::List(ref elem) => format!("Vec<{}>", self.rs_type(program, elem)?),
+            Type::Set(ref elem) => format!("HashSet<{}>", self.rs_type(program, elem)?),
+            Type::Map(ref key, ref value) => {
+                format!("HashMap<{}, {}>", self.rs_type(program, key)?, self.rs_type(program, value)?)
+            }
+            Type::Named(ref name) => {
+                let (defining, definition) = self.programs.lookup(program, name)?;
+                match *definition {
+                    Definition::Typedef(ref typedef) if self.is_newtype(typedef) => {
+                        self.module_path(defining) + &pascalcase(&typedef.name)
+                    }
+                    Definition::Typedef(ref typedef) => self.rs_type(defining, &typedef.ty)?,
+                    Definition::Senum(_) => String::from("String"),
+                    Definition::Enum(ref tenum) => self.module_path(defining) + &capitalize(&tenum.name),
+                    Definition::Struct(ref tstruct) |
+                    Definition::Union(ref tstruct) |
+                    Definition::Exception(ref tstruct) => self.module_path(defining) + &capitalize(&tstruct.name),
+                    _ => return Err(self.error(format!("`{}` is not a type", name))),
+                }
+            }
+        };
+        Ok(rendered)
+    }
+
+    // The absolute path to the module generated for `program`, ending in
+    // `::`. Empty for the program being generated, whose items are in scope.
+    fn module_path(&self, program: usize) -> String {
+        if program == self.program {
+            return String::new();
+        }
+        let mut path = String::from("crate::");
+        for segment in module_segments(self.programs.get(program)) {
+            path.push_str(&segment);
+            path.push_str("::");
+        }
+        path
+    }
+
+    fn line(&mut self, text: &str) {
+        self.start_line(text);
+        self.out.push('\n');
+    }
+
+    fn start_line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::Resolve(self.programs.get(self.program).path.clone(), message)
+    }
+}
+
+/// The modules the code for `program` goes in: the segments of its
+/// `namespace rs a.b`, or the name of the file when it declares none. The
+/// `namespace *` of other languages is ignored, as it names packages rather
+/// than modules of the crate. A first segment `thrift` becomes `thrift_`, not
+/// to shadow the thrift crate.
+pub fn module_segments(program: &Program) -> Vec<String> {
+    let namespace = program.document.namespaces.iter()
+                           .find(|namespace| namespace.scope == "rs" && !namespace.name.is_empty());
+    let mut segments: Vec<String> = match namespace {
+        Some(namespace) => namespace.name.split('.').map(normalize_id).collect(),
+        None => vec![normalize_id(&underscore(&program.name))],
+    };
+    if segments[0] == "thrift" {
+        segments[0].push('_');
+    }
+    segments
+}
+
+pub fn autogen_comment() -> String {
+    format!("///////////////////////////////////////////////////////////////\n\
+             // Autogenerated by Thrift Compiler ({})\n\
+             //\n\
+             // DO NOT EDIT UNLESS YOU ARE SURE YOU KNOW WHAT YOU ARE DOING\n\
+             ///////////////////////////////////////////////////////////////\n",
+            THRIFT_VERSION)
+}
+
+fn capitalize(id: &str) -> String {
+    let mut chars = id.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+// a_multi_word -> AMultiWord
+fn pascalcase(id: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+    for c in id.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    capitalize(&out)
+}
+
+// aMultiWord -> a_multi_word
+fn underscore(id: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in id.chars().enumerate() {
+        if i > 0 && c.is_ascii_uppercase() {
+            out.push('_');
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+fn normalize_id(id: &str) -> String {
+    if KEYWORDS.contains(&id) { String::from(id) + "_" } else { String::from(id) }
+}
+
+fn to_field_name(id: &str) -> String {
+    normalize_id(&underscore(id))
+}
@@ -0,0 +1,265 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+//! Generates Rust code for Thrift files from a build script, without the
+//! C++ compiler.
+//!
+//! ```no_run
+//! // build.rs
+//! extern crate thrift_build;
+//!
+//! fn main() {
+//!     thrift_build::compile("tutorial.thrift")
+//!         .out_dir(std::env::var("OUT_DIR").unwrap())
+//!         .run()
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! The code for `tutorial.thrift` is written to `$OUT_DIR/tutorial/mod.rs`,
+//! as `thrift --gen rs --out $OUT_DIR tutorial.thrift` would, and uses the
+//! macros of the `thrift` crate. Files with a `namespace rs a.b` go in
+//! `$OUT_DIR/a/b/mod.rs` instead. The files they include are generated too,
+//! and referred to by absolute paths like `crate::shared::SharedStruct`, so
+//! the modules belong at the root of the crate:
+//!
+//! ```ignore
+//! // main.rs
+//! #[macro_use]
+//! extern crate thrift;
+//!
+//! include!(concat!(env!("OUT_DIR"), "/thrift_modules.rs"));
+//! ```
+
+extern crate thrift_idl;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+mod generator;
+mod program;
+
+use generator::{Generator, Options};
+use program::Programs;
+
+/// Starts configuring the generation of the code for `file`.
+pub fn compile<P: AsRef<Path>>(file: P) -> Compiler {
+    Compiler {
+        files: vec![file.as_ref().to_path_buf()],
+        include_dirs: Vec::new(),
+        out_dir: None,
+        options: Options::default(),
+        cargo: true,
+    }
+}
+
+/// Generates the code for Thrift files.
+#[must_use = "nothing is generated until `run` is called"]
+#[derive(Clone, Debug)]
+pub struct Compiler {
+    files: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+    options: Options,
+    cargo: bool,
+}
+
+impl Compiler {
+    /// Generates the code for another file as well.
+    pub fn file<P: AsRef<Path>>(mut self, file: P) -> Compiler {
+        self.files.push(file.as_ref().to_path_buf());
+        self
+    }
+
+    /// Looks for included files in `dir` when they are not next to the file
+    /// including them, like `thrift -I`.
+    pub fn include_dir<P: AsRef<Path>>(mut self, dir: P) -> Compiler {
+        self.include_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Where to write the generated modules. Defaults to `$OUT_DIR`.
+    pub fn out_dir<P: AsRef<Path>>(mut self, dir: P) -> Compiler {
+        self.out_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Keeps the fields structs don't know and writes them back, like
+    /// `--gen rs:unknown_fields`.
+    pub fn unknown_fields(mut self, enabled: bool) -> Compiler {
+        self.options.unknown_fields = enabled;
+        self
+    }
+
+    /// Generates typedefs as newtypes rather than aliases, like
+    /// `--gen rs:newtypes`.
+    pub fn newtypes(mut self, enabled: bool) -> Compiler {
+        self.options.newtypes = enabled;
+        self
+    }
+
+    /// Fails to decode enum values the Thrift files don't list, rather than
+    /// keeping them in an `Unknown` variant, like `--gen rs:strict_enums`.
+    pub fn strict_enums(mut self, enabled: bool) -> Compiler {
+        self.options.strict_enums = enabled;
+        self
+    }
+
+    /// Whether to print `cargo:rerun-if-changed` for each Thrift file read,
+    /// included ones too. On by default.
+    pub fn cargo_metadata(mut self, enabled: bool) -> Compiler {
+        self.cargo = enabled;
+        self
+    }
+
+    /// Generates the code for the files and those they include, returning
+    /// the paths of their modules in the order they were read.
+    ///
+    /// Also writes the `mod.rs` of the modules above those, and
+    /// `thrift_modules.rs` declaring the top-level ones. Modules whose
+    /// contents did not change are left untouched, so that cargo doesn't
+    /// rebuild the crate needlessly.
+    pub fn run(self) -> Result<Vec<PathBuf>, Error> {
+        let out_dir = match self.out_dir {
+            Some(ref dir) => dir.clone(),
+            None => match env::var_os("OUT_DIR") {
+                Some(dir) => PathBuf::from(dir),
+                None => return Err(Error::NoOutDir),
+            },
+        };
+        // thrift_modules.rs is included from elsewhere, so it must point at
+        // the modules with absolute paths.
+        let out_dir = if out_dir.is_absolute() {
+            out_dir
+        } else {
+            env::current_dir().map_err(|err| Error::Io(out_dir.clone(), err))?.join(out_dir)
+        };
+
+        let mut programs = Programs::default();
+        for file in &self.files {
+            programs.load(file, &self.include_dirs)?;
+        }
+        programs.check_typedefs()?;
+
+        if self.cargo {
+            for path in programs.paths() {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+
+        // The program generated in each module, and the modules declared by
+        // each module, starting from the root of the crate.
+        let mut generated = BTreeMap::new();
+        let mut declared = BTreeMap::new();
+        declared.insert(Vec::new(), BTreeSet::new());
+        for index in 0..programs.count() {
+            let segments = generator::module_segments(programs.get(index));
+            if let Some(&other) = generated.get(&segments) {
+                let message = format!("generated to the module `crate::{}`, like {}",
+                                      segments.join("::"), programs.get(other).path.display());
+                return Err(Error::Resolve(programs.get(index).path.clone(), message));
+            }
+            for depth in 1..=segments.len() {
+                declared.entry(segments[..depth].to_vec()).or_insert_with(BTreeSet::new);
+                declared.get_mut(&segments[..depth - 1]).unwrap().insert(segments[depth - 1].clone());
+            }
+            generated.insert(segments, index);
+        }
+
+        let mut written = vec![PathBuf::new(); programs.count()];
+        for (module, children) in &declared {
+            if module.is_empty() {
+                let mut code = generator::autogen_comment();
+                for child in children {
+                    let path = out_dir.join(child).join("mod.rs");
+                    code.push_str(&format!("\n#[path = {:?}]\npub mod {};\n", path.display().to_string(), child));
+                }
+                write_if_changed(&out_dir.join("thrift_modules.rs"), &code)?;
+                continue;
+            }
+
+            let path = module.iter().fold(out_dir.clone(), |dir, segment| dir.join(segment)).join("mod.rs");
+            let mut code = match generated.get(module) {
+                Some(&index) => {
+                    written[index] = path.clone();
+                    Generator::new(&programs, index, &self.options).generate()?
+                }
+                None => generator::autogen_comment() + "\n",
+            };
+            for child in children {
+                code.push_str(&format!("pub mod {};\n", child));
+            }
+            write_if_changed(&path, &code)?;
+        }
+
+        Ok(written)
+    }
+}
+
+fn write_if_changed(path: &Path, code: &str) -> Result<(), Error> {
+    if fs::read_to_string(path).ok().as_ref().map(|old| &old[..]) == Some(code) {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| Error::Io(dir.to_path_buf(), err))?;
+    }
+    fs::write(path, code).map_err(|err| Error::Io(path.to_path_buf(), err))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Neither `out_dir` nor `$OUT_DIR` were set
+    NoOutDir,
+    /// A file could not be read or written
+    Io(PathBuf, io::Error),
+    /// A Thrift file has a syntax error
+    Parse(PathBuf, thrift_idl::Error),
+    /// A Thrift file refers to something that doesn't exist or can't be
+    /// generated
+    Resolve(PathBuf, String),
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            Error::Io(_, ref err) => Some(err),
+            Error::Parse(_, ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NoOutDir => write!(f, "no output directory: OUT_DIR is not set"),
+            Error::Io(ref path, ref err) => write!(f, "{}: {}", path.display(), err),
+            Error::Parse(ref path, ref err) => write!(f, "{}:{}", path.display(), err),
+            Error::Resolve(ref path, ref message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,165 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use thrift_idl::ast::{Definition, Document, Type};
+
+use Error;
+
+/// A parsed Thrift file.
+pub struct Program {
+    /// The file name without `.thrift`, by which other files refer to it.
+    pub name: String,
+    pub path: PathBuf,
+    pub document: Document,
+    /// The programs included by this one, by name.
+    includes: HashMap<String, usize>,
+}
+
+/// Thrift files and those they include, each read once.
+#[derive(Default)]
+pub struct Programs {
+    programs: Vec<Program>,
+    /// The programs read, by canonical path.
+    loaded: HashMap<PathBuf, usize>,
+}
+
+impl Programs {
+    /// Reads the Thrift file at `path` and the files it includes, which are
+    /// looked for next to the including file, then in `include_dirs`.
+    pub fn load(&mut self, path: &Path, include_dirs: &[PathBuf]) -> Result<usize, Error> {
+        let canonical = fs::canonicalize(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+        if let Some(&index) = self.loaded.get(&canonical) {
+            return Ok(index);
+        }
+
+        let source = fs::read_to_string(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+        let document = thrift_idl::parse(&source).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+
+        let includes = document.includes.clone();
+        let index = self.programs.len();
+        self.loaded.insert(canonical, index);
+        self.programs.push(Program {
+            name: program_name(path),
+            path: path.to_path_buf(),
+            document,
+            includes: HashMap::new(),
+        });
+
+        for include in &includes {
+            let dir = path.parent().unwrap_or_else(|| Path::new(""));
+            let found = Some(dir).into_iter().chain(include_dirs.iter().map(|dir| dir.as_path()))
+                                 .map(|dir| dir.join(include))
+                                 .find(|path| path.is_file());
+            let included = match found {
+                Some(included) => self.load(&included, include_dirs)?,
+                None => return Err(Error::Resolve(path.to_path_buf(), format!("cannot find included file \"{}\"", include))),
+            };
+            let name = self.programs[included].name.clone();
+            self.programs[index].includes.insert(name, included);
+        }
+
+        Ok(index)
+    }
+
+    /// Fails if a typedef refers back to itself, possibly through other
+    /// typedefs or containers, as types could never be given to it.
+    pub fn check_typedefs(&self) -> Result<(), Error> {
+        for (index, program) in self.programs.iter().enumerate() {
+            for definition in &program.document.definitions {
+                if let Definition::Typedef(ref typedef) = *definition {
+                    let mut seen = vec![(index, typedef.name.clone())];
+                    self.check_typedef(index, &typedef.ty, &mut seen)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Follows the typedefs `ty` refers to in `program`, with the chain of
+    // those followed so far in `seen`.
+    fn check_typedef(&self, program: usize, ty: &Type, seen: &mut Vec<(usize, String)>) -> Result<(), Error> {
+        match *ty {
+            Type::List(ref elem) | Type::Set(ref elem) => self.check_typedef(program, elem, seen),
+            Type::Map(ref key, ref value) => {
+                self.check_typedef(program, key, seen)?;
+                self.check_typedef(program, value, seen)
+            }
+            Type::Named(ref name) => {
+                let (defining, definition) = self.lookup(program, name)?;
+                if let Definition::Typedef(ref typedef) = *definition {
+                    if seen.iter().any(|&(program, ref name)| program == defining && *name == typedef.name) {
+                        let message = format!("typedef `{}` refers to itself", typedef.name);
+                        return Err(Error::Resolve(self.programs[defining].path.clone(), message));
+                    }
+                    seen.push((defining, typedef.name.clone()));
+                    self.check_typedef(defining, &typedef.ty, seen)?;
+                    seen.pop();
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> &Program {
+        &self.programs[index]
+    }
+
+    /// The number of files read, which are numbered from 0 in the order they
+    /// were read.
+    pub fn count(&self) -> usize {
+        self.programs.len()
+    }
+
+    /// All the files read, in the order they were read.
+    pub fn paths(&self) -> Vec<&Path> {
+        self.programs.iter().map(|program| program.path.as_path()).collect()
+    }
+
+    /// Finds the definition `name` refers to in `program`, where it may be
+    /// qualified with the name of an included program. Returns the program
+    /// defining it too.
+    pub fn lookup(&self, program: usize, name: &str) -> Result<(usize, &Definition), Error> {
+        let current = &self.programs[program];
+        let (defining, local) = match name.find('.') {
+            Some(dot) => match current.includes.get(&name[..dot]) {
+                Some(&included) => (included, &name[dot + 1..]),
+                None => (program, name),
+            },
+            None => (program, name),
+        };
+
+        self.programs[defining].document.definitions.iter()
+            .find(|definition| definition.name() == local)
+            .map(|definition| (defining, definition))
+            .ok_or_else(|| Error::Resolve(current.path.clone(), format!("unknown name `{}`", name)))
+    }
+}
+
+fn program_name(path: &Path) -> String {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    match name.rfind(".thrift") {
+        Some(end) => String::from(&name[..end]),
+        None => name,
+    }
+}
@@ -0,0 +1,482 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use {compile, Error};
+
+const TUTORIAL: &str = "///////////////////////////////////////////////////////////////
+// Autogenerated by Thrift Compiler (1.0.0-dev)
+//
+// DO NOT EDIT UNLESS YOU ARE SURE YOU KNOW WHAT YOU ARE DOING
+///////////////////////////////////////////////////////////////
+
+#![allow(unused_mut, dead_code, non_snake_case)]
+#[allow(unused_imports)]
+use std::collections::{HashMap, HashSet};
+
+enom! {
+  name = Operation,
+  values = [
+    ADD = 1,
+    SUBTRACT = 2,
+    MULTIPLY = 3,
+    DIVIDE = 4,
+  ],
+  default = ADD,
+  unknown = Unknown
+}
+
+pub type MyInteger = i32;
+
+strukt! {
+  name = Work,
+  fields = {
+    num1: i32 => 1,
+    num2: i32 => 2,
+    op: Operation => 3,
+    comment: String => 4,
+  },
+  derive = [Eq, Hash, PartialOrd, Ord, ],
+  builder = WorkBuilder,
+  accessors = [
+    set_num1 take_num1 has_num1,
+    set_num2 take_num2 has_num2,
+    set_op take_op has_op,
+    set_comment take_comment has_comment,
+  ],
+  required = []
+}
+
+strukt! {
+  name = InvalidOperation,
+  fields = {
+    what_op: i32 => 1,
+    why: String => 2,
+  },
+  derive = [Eq, Hash, PartialOrd, Ord, ],
+  builder = InvalidOperationBuilder,
+  accessors = [
+    set_what_op take_what_op has_what_op,
+    set_why take_why has_why,
+  ],
+  required = []
+}
+
+service! {
+  trait_name = Calculator,
+  context_trait_name = CalculatorWithContext,
+  processor_name = CalculatorProcessor,
+  client_name = CalculatorClient,
+  parents = [crate::shared::SharedService => crate::shared::SharedServiceWithContext, ],
+  service_methods = [
+    CalculatorPingArgs -> CalculatorPingResult = ping(
+    ) -> () => [
+    ],
+    CalculatorAddArgs -> CalculatorAddResult = add(
+      num1: i32 => 1,
+      num2: i32 => 2,
+    ) -> i32 => [
+    ],
+    CalculatorCalculateArgs -> CalculatorCalculateResult = calculate(
+      logid: i32 => 1,
+      w: Work => 2,
+    ) -> i32 => [
+      ouch: InvalidOperation => 1,
+    ],
+    CalculatorZipArgs -> CalculatorZipResult = zip(
+    ) -> () => [
+    ],
+  ],
+  parent_methods = [
+    (crate::shared::SharedServiceGetStructArgs, crate::shared::SharedServiceGetStructResult) = getStruct(
+      key: i32 => 1,
+    ) -> crate::shared::SharedStruct => [
+    ],
+  ]
+}
+
+";
+
+// A fresh directory for the files of one test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("thrift-build-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn tutorial_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../tutorial")
+}
+
+#[test]
+fn generates_the_tutorial_like_the_cpp_compiler() {
+    let out = scratch("tutorial");
+    let written = compile(tutorial_dir().join("tutorial.thrift"))
+        .out_dir(&out)
+        .cargo_metadata(false)
+        .run()
+        .unwrap();
+
+    assert_eq!(written, vec![out.join("tutorial").join("mod.rs"), out.join("shared").join("mod.rs")]);
+    assert_eq!(fs::read_to_string(&written[0]).unwrap(), TUTORIAL);
+}
+
+#[test]
+fn declares_the_modules_for_the_crate() {
+    let out = scratch("declared");
+    compile(tutorial_dir().join("tutorial.thrift")).out_dir(&out).cargo_metadata(false).run().unwrap();
+
+    let modules = fs::read_to_string(out.join("thrift_modules.rs")).unwrap();
+    assert!(modules.contains(&format!("\n#[path = {:?}]\npub mod shared;\n",
+                                      out.join("shared").join("mod.rs").display().to_string())));
+    assert!(modules.contains(&format!("\n#[path = {:?}]\npub mod tutorial;\n",
+                                      out.join("tutorial").join("mod.rs").display().to_string())));
+}
+
+#[test]
+fn generates_several_files() {
+    let out = scratch("several");
+    let written = compile(tutorial_dir().join("tutorial.thrift"))
+        .file(tutorial_dir().join("shared.thrift"))
+        .out_dir(&out)
+        .cargo_metadata(false)
+        .run()
+        .unwrap();
+
+    assert_eq!(written, vec![out.join("tutorial").join("mod.rs"), out.join("shared").join("mod.rs")]);
+    let shared = fs::read_to_string(&written[1]).unwrap();
+    assert!(shared.contains("strukt! {\n  name = SharedStruct,\n  fields = {\n    key: i32 => 1,\n    value: String => 2,\n  },\n  derive = [Eq, Hash, PartialOrd, Ord, ],\n  builder = SharedStructBuilder,\n"));
+    assert!(shared.contains("  parents = [],\n"));
+    assert!(shared.contains("  parent_methods = [\n  ]\n}\n"));
+}
+
+#[test]
+fn rewrites_only_changed_modules() {
+    let dir = scratch("unchanged");
+    let file = write(&dir, "point.thrift", "struct Point { 1: i32 x, 2: i32 y }");
+    let module = dir.join("point").join("mod.rs");
+
+    fs::create_dir_all(module.parent().unwrap()).unwrap();
+    fs::write(&module, "stale").unwrap();
+    compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    assert!(fs::read_to_string(&module).unwrap().contains("name = Point,"));
+
+    let modified = fs::metadata(&module).unwrap().modified().unwrap();
+    compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    assert_eq!(fs::metadata(&module).unwrap().modified().unwrap(), modified);
+}
+
+#[test]
+fn resolves_includes_from_include_dirs() {
+    let dir = scratch("include_dirs");
+    let lib = dir.join("lib");
+    fs::create_dir_all(&lib).unwrap();
+    write(&lib, "base.thrift", "typedef list<i64> Ids\nstruct Base { 1: Ids ids }\nservice Root { void ping() }");
+    let file = write(&dir, "derived.thrift", r#"
+        include "base.thrift"
+        struct Derived {
+          1: base.Base base,
+          2: base.Ids more (ignored = "annotation"),
+          -3: optional map<string, set<binary>> type,
+          bool done
+        }
+        service Leaf extends base.Root {}
+    "#);
+
+    let err = compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap_err();
+    match err {
+        Error::Resolve(ref path, ref message) => {
+            assert_eq!(path, &file);
+            assert_eq!(message, "cannot find included file \"base.thrift\"");
+        }
+        _ => panic!("unexpected error {}", err),
+    }
+
+    let written = compile(&file).include_dir(&lib).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(code.contains("    base: crate::base::Base => 1,\n    more: Vec<i64> => 2,\n    type_: HashMap<String, HashSet<Vec<u8>>> => -1,\n    done: bool => -2,\n"));
+    assert!(code.contains("  parents = [crate::base::Root => crate::base::RootWithContext, ],\n"));
+    assert!(code.contains("    (crate::base::RootPingArgs, crate::base::RootPingResult) = ping(\n"));
+    assert_eq!(written[1], dir.join("base").join("mod.rs"));
+}
+
+#[test]
+fn flattens_long_inheritance_chains() {
+    let dir = scratch("chains");
+    let mut source = String::from("service S0 { void m0() }\n");
+    for i in 1..30 {
+        source.push_str(&format!("service S{} extends S{} {{ void m{}() }}\n", i, i - 1, i));
+    }
+    let file = write(&dir, "chain.thrift", &source);
+    write(&dir, "leaves.thrift", r#"
+        include "chain.thrift"
+        service Left extends chain.S29 {}
+        service Right extends chain.S28 {}
+    "#);
+    let leaves = dir.join("leaves.thrift");
+
+    let written = compile(&file).file(&leaves).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    let chain = fs::read_to_string(&written[0]).unwrap();
+    assert!(chain.contains("  parents = [S28 => S28WithContext, ],\n"));
+    assert!(chain.contains("    (S0M0Args, S0M0Result) = m0(\n"));
+
+    let leaves = fs::read_to_string(&written[1]).unwrap();
+    assert!(!leaves.contains("use super::"));
+    assert!(leaves.contains("  parents = [crate::chain::S29 => crate::chain::S29WithContext, ],\n"));
+    assert!(leaves.contains("    (crate::chain::S0M0Args, crate::chain::S0M0Result) = m0(\n    ) -> () => [\n    ],\n  ]\n}\n"));
+}
+
+#[test]
+fn generates_modules_from_rs_namespaces() {
+    let dir = scratch("namespaces");
+    write(&dir, "common.thrift", "namespace rs acme.common\nstruct Id { 1: i64 value }");
+    write(&dir, "acme.thrift", "namespace * com.acme\ninclude \"common.thrift\"\nstruct Tag { 1: common.Id id }");
+    let file = write(&dir, "user.thrift", r#"
+        namespace rs acme.type.user
+        namespace java com.acme.user
+        include "acme.thrift"
+        include "common.thrift"
+        struct User { 1: common.Id id, 2: list<acme.Tag> tags }
+    "#);
+
+    let out = dir.join("out");
+    let written = compile(&file).out_dir(&out).cargo_metadata(false).run().unwrap();
+    assert_eq!(written, vec![
+        out.join("acme").join("type_").join("user").join("mod.rs"),
+        out.join("acme").join("mod.rs"),
+        out.join("acme").join("common").join("mod.rs"),
+    ]);
+
+    let user = fs::read_to_string(&written[0]).unwrap();
+    assert!(user.contains("    id: crate::acme::common::Id => 1,\n    tags: Vec<crate::acme::Tag> => 2,\n"));
+
+    // Modules holding code declare their submodules after it, the others
+    // only declare them.
+    let acme = fs::read_to_string(&written[1]).unwrap();
+    assert!(acme.contains("    id: crate::acme::common::Id => 1,\n"));
+    assert!(acme.ends_with("}\n\npub mod common;\npub mod type_;\n"));
+    let type_ = fs::read_to_string(out.join("acme").join("type_").join("mod.rs")).unwrap();
+    assert!(type_.ends_with("///\n\npub mod user;\n"));
+
+    let modules = fs::read_to_string(out.join("thrift_modules.rs")).unwrap();
+    assert_eq!(modules.matches("pub mod ").count(), 1);
+    assert!(modules.contains("pub mod acme;\n"));
+}
+
+#[test]
+fn generates_modules_besides_the_thrift_crate() {
+    let dir = scratch("thrift_root");
+    let file = write(&dir, "thrift.thrift", "struct Test {}");
+    let test = write(&dir, "test.thrift", "namespace rs thrift.test\nstruct Test {}");
+
+    let written = compile(&file).file(&test).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    assert_eq!(written, vec![dir.join("thrift_").join("mod.rs"), dir.join("thrift_").join("test").join("mod.rs")]);
+}
+
+#[test]
+fn reports_files_generated_to_the_same_module() {
+    let dir = scratch("collisions");
+    let lib = dir.join("lib");
+    fs::create_dir_all(&lib).unwrap();
+    let first = write(&dir, "point.thrift", "struct Point { 1: i32 x }");
+    let second = write(&lib, "geometry.thrift", "namespace rs point\nstruct Point { 1: double x }");
+
+    let err = compile(&first).file(&second).out_dir(&dir).cargo_metadata(false).run().unwrap_err();
+    assert_eq!(err.to_string(),
+               format!("{}: generated to the module `crate::point`, like {}", second.display(), first.display()));
+}
+
+#[test]
+fn keeps_unknown_fields_on_request() {
+    let dir = scratch("unknown_fields");
+    let file = write(&dir, "things.thrift", r#"
+        struct Kept { 1: i32 x }
+        struct Dropped { 1: i32 x } (rs.unknown_fields = "false")
+        struct Clashing { 1: i32 unknown_fields, 2: i32 unknown_fields_ }
+    "#);
+
+    let written = compile(&file).out_dir(&dir).unknown_fields(true).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(code.contains("  name = Kept,\n  fields = {\n    x: i32 => 1,\n  },\n  unknown_fields = unknown_fields,\n  builder = KeptBuilder,\n"));
+    assert!(code.contains("  },\n  unknown_fields = unknown_fields__,\n  builder = ClashingBuilder,\n"));
+    assert!(code.contains("  name = Dropped,\n  fields = {\n    x: i32 => 1,\n  },\n  derive = [Eq, Hash, PartialOrd, Ord, ],\n  builder = DroppedBuilder,\n"));
+}
+
+#[test]
+fn generates_newtypes_and_custom_field_types() {
+    let dir = scratch("newtypes");
+    let file = write(&dir, "ids.thrift", r#"
+        typedef i64 UserId (rs.newtype = "true")
+        typedef UserId OwnerId
+        typedef double Score (rs.newtype = "true")
+        typedef list<UserId> Members (rs.newtype = "true")
+        typedef i64 Timestamp
+        struct Team {
+          1: OwnerId owner,
+          2: Members members,
+          3: string id (rs.type = "uuid::Uuid", rs.convert = "crate::convert::UuidString"),
+          4: string name (rs.type = "crate::Name"),
+          5: Timestamp created,
+        }
+    "#);
+
+    let written = compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(code.contains("newtype! {\n  name = UserId,\n  inner = i64,\n  derive = [Eq, Hash, PartialOrd, Ord, ]\n}\n"));
+    assert!(code.contains("pub type OwnerId = UserId;\n"));
+    assert!(code.contains("newtype! {\n  name = Score,\n  inner = f64,\n  derive = [PartialOrd, ]\n}\n"));
+    assert!(code.contains("newtype! {\n  name = Members,\n  inner = Vec<UserId>,\n  derive = [Eq, Hash, PartialOrd, Ord, ]\n}\n"));
+    assert!(code.contains("    owner: UserId => 1,\n    members: Members => 2,\n"));
+    assert!(code.contains("    id: uuid::Uuid as crate::convert::UuidString => 3,\n    name: crate::Name => 4,\n"));
+    assert!(code.contains("    created: i64 => 5,\n  },\n  builder = TeamBuilder,\n"));
+
+    // The option makes all typedefs newtypes, unless told otherwise.
+    let file = write(&dir, "times.thrift", "typedef i64 Timestamp\ntypedef i64 Millis (rs.newtype = \"false\")");
+    let written = compile(&file).out_dir(&dir).newtypes(true).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(code.contains("  name = Timestamp,\n"));
+    assert!(code.contains("pub type Millis = i64;\n"));
+
+    let file = write(&dir, "convert.thrift", "struct A { 1: string id (rs.convert = \"Uuids\") }");
+    let err = compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap_err();
+    assert_eq!(err.to_string(), format!("{}: field id has rs.convert but no rs.type", file.display()));
+}
+
+#[test]
+fn keeps_unknown_enum_values_unless_strict() {
+    let dir = scratch("enums");
+    let file = write(&dir, "enums.thrift", r#"
+        enum Open { A, Unknown, unknown_, b = 5 }
+        enum Closed { A } (rs.strict = "true")
+    "#);
+
+    let written = compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(code.contains("    A = 0,\n    Unknown = 1,\n    Unknown_ = 2 => \"unknown_\",\n    B = 5 => \"b\",\n"));
+    assert!(code.contains("  default = A,\n  unknown = Unknown__\n}\n"));
+    assert!(code.contains("  default = A\n}\n"));
+
+    let written = compile(&file).out_dir(&dir).strict_enums(true).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(!code.contains("unknown = "));
+}
+
+#[test]
+fn derives_what_all_fields_implement() {
+    let dir = scratch("derives");
+    let file = write(&dir, "derives.thrift", r#"
+        typedef list<double> Scores
+        enum Kind { A }
+        struct Tree { 1: Kind kind, 2: list<Tree> children, 3: binary data }
+        struct Scored { 1: Tree tree, 2: Scores scores }
+        struct Tagged { 1: Tree tree, 2: set<string> tags }
+        struct Weighted { 1: map<string, double> weights }
+        struct Unknown { 1: i32 x } (rs.unknown_fields = "true")
+        struct HasUnknown { 1: list<Unknown> unknown }
+        struct Empty {}
+    "#);
+
+    let written = compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(code.contains("    data: Vec<u8> => 3,\n  },\n  derive = [Eq, Hash, PartialOrd, Ord, ],\n"));
+    assert!(code.contains("    scores: Vec<f64> => 2,\n  },\n  derive = [PartialOrd, ],\n"));
+    assert!(code.contains("    tags: HashSet<String> => 2,\n  },\n  derive = [Eq, ],\n"));
+    assert!(code.contains("    weights: HashMap<String, f64> => 1,\n  },\n  builder"));
+    assert!(code.contains("    unknown: Vec<Unknown> => 1,\n  },\n"));
+    assert!(code.contains("  name = Empty,\n  fields = {\n  },\n  derive = [Eq, Hash, PartialOrd, Ord, ]\n}\n"));
+}
+
+#[test]
+fn generates_builders_with_required_fields() {
+    let dir = scratch("builders");
+    let file = write(&dir, "builders.thrift", r#"
+        struct Account {
+          1: required i64 id,
+          2: optional string displayName,
+          3: required string type,
+        }
+    "#);
+
+    let written = compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(code.contains("  builder = AccountBuilder,\n  accessors = [\n    set_id take_id has_id,\n    set_display_name take_display_name has_display_name,\n    set_type take_type has_type,\n  ],\n  required = [id, type_, ]\n}\n"));
+}
+
+#[test]
+fn builders_do_not_clash() {
+    let dir = scratch("builder_clashes");
+    let file = write(&dir, "clashes.thrift", r#"
+        struct Foo { 1: i32 x }
+        struct FooBuilder { 1: i32 y }
+    "#);
+
+    let written = compile(&file).out_dir(&dir).cargo_metadata(false).run().unwrap();
+    let code = fs::read_to_string(&written[0]).unwrap();
+    assert!(code.contains("  builder = FooBuilder_,\n"));
+    assert!(code.contains("  builder = FooBuilderBuilder,\n"));
+
+    let build = write(&dir, "build.thrift", "struct Task { 1: string build }");
+    let err = compile(&build).out_dir(&dir).cargo_metadata(false).run().unwrap_err();
+    assert_eq!(err.to_string(),
+               format!("{}: field build of Task clashes with the build method of its builder", build.display()));
+}
+
+#[test]
+fn reports_errors_with_the_file() {
+    let dir = scratch("errors");
+    let syntax = write(&dir, "syntax.thrift", "struct {}");
+    let err = compile(&syntax).out_dir(&dir).cargo_metadata(false).run().unwrap_err();
+    assert_eq!(err.to_string(), format!("{}:1:8: expected an identifier, found `{{`", syntax.display()));
+
+    let unknown = write(&dir, "unknown.thrift", "struct A { 1: B b }");
+    let err = compile(&unknown).out_dir(&dir).cargo_metadata(false).run().unwrap_err();
+    assert_eq!(err.to_string(), format!("{}: unknown name `B`", unknown.display()));
+
+    let missing = dir.join("missing.thrift");
+    match compile(&missing).out_dir(&dir).run().unwrap_err() {
+        Error::Io(ref path, _) => assert_eq!(path, &missing),
+        err => panic!("unexpected error {}", err),
+    }
+
+    let cycle = write(&dir, "cycle.thrift", "typedef B A\ntypedef list<A> B\nstruct S { 1: A a }");
+    let err = compile(&cycle).out_dir(&dir).cargo_metadata(false).run().unwrap_err();
+    assert_eq!(err.to_string(), format!("{}: typedef `A` refers to itself", cycle.display()));
+}
+
+#[test]
+fn reads_each_file_once() {
+    let dir = scratch("once");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    let file = write(&dir, "once.thrift", "struct Once {}");
+
+    let written = compile(&file).file(dir.join("sub").join("..").join("once.thrift"))
+                                .out_dir(&dir).cargo_metadata(false).run().unwrap();
+    assert_eq!(written.len(), 1);
+}
@@ -0,0 +1,209 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+//! The syntax tree of a Thrift file.
+//!
+//! Names are kept as written: references to definitions of included files
+//! are qualified with the name of the file, as in `shared.SharedStruct`.
+
+/// A parsed Thrift file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    /// The paths of the included Thrift files, as written.
+    pub includes: Vec<String>,
+    pub cpp_includes: Vec<String>,
+    pub namespaces: Vec<Namespace>,
+    pub definitions: Vec<Definition>,
+}
+
+impl Document {
+    /// The namespace for `scope`, falling back to the one declared for all
+    /// languages with `*`.
+    pub fn namespace(&self, scope: &str) -> Option<&str> {
+        let find = |scope: &str| {
+            self.namespaces.iter().find(|ns| ns.scope == scope).map(|ns| &ns.name[..])
+        };
+        find(scope).or_else(|| find("*"))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Namespace {
+    /// The language, or `*`.
+    pub scope: String,
+    pub name: String,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Definition {
+    Typedef(Typedef),
+    Const(Const),
+    Enum(Enum),
+    Senum(Senum),
+    Struct(Struct),
+    Union(Struct),
+    Exception(Struct),
+    Service(Service),
+}
+
+impl Definition {
+    pub fn name(&self) -> &str {
+        match *self {
+            Definition::Typedef(ref x) => &x.name,
+            Definition::Const(ref x) => &x.name,
+            Definition::Enum(ref x) => &x.name,
+            Definition::Senum(ref x) => &x.name,
+            Definition::Struct(ref x) | Definition::Union(ref x) | Definition::Exception(ref x) => &x.name,
+            Definition::Service(ref x) => &x.name,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Typedef {
+    pub name: String,
+    pub ty: Type,
+    /// The annotations of the aliased type, as in `typedef string (a = "b") Name`.
+    pub type_annotations: Vec<Annotation>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Const {
+    pub name: String,
+    pub ty: Type,
+    pub value: ConstValue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    pub values: Vec<EnumValue>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumValue {
+    pub name: String,
+    /// The explicit value, if any. Others follow the previous value.
+    pub value: Option<i64>,
+    pub annotations: Vec<Annotation>,
+}
+
+/// A deprecated enumeration of strings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Senum {
+    pub name: String,
+    pub values: Vec<String>,
+    pub annotations: Vec<Annotation>,
+}
+
+/// A struct, union or exception.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    /// The explicit id, if any. The C++ compiler numbers the others
+    /// downwards from -1.
+    pub id: Option<i16>,
+    pub requiredness: Requiredness,
+    pub ty: Type,
+    pub name: String,
+    pub default: Option<ConstValue>,
+    /// The annotations of the field's type, as in `1: string (a = "b") name`.
+    pub type_annotations: Vec<Annotation>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Requiredness {
+    Required,
+    Optional,
+    /// Neither `required` nor `optional`.
+    Default,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Service {
+    pub name: String,
+    pub extends: Option<String>,
+    pub functions: Vec<Function>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub oneway: bool,
+    /// `None` for `void`.
+    pub return_type: Option<Type>,
+    pub params: Vec<Field>,
+    pub throws: Vec<Field>,
+    pub annotations: Vec<Annotation>,
+}
+
+/// A type as written. Annotations of types nested in containers are not
+/// kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    Bool,
+    /// `byte` or `i8`.
+    Byte,
+    I16,
+    I32,
+    I64,
+    Double,
+    String,
+    Binary,
+    List(Box<Type>),
+    Set(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+    /// A typedef, enum, struct, union or exception.
+    Named(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstValue {
+    Int(i64),
+    Double(f64),
+    Literal(String),
+    /// An enum value or another constant.
+    Identifier(String),
+    List(Vec<ConstValue>),
+    Map(Vec<(ConstValue, ConstValue)>),
+}
+
+/// An annotation such as `(cpp.type = "DenseFoo")`. Annotations without a
+/// value have the value "1", as with the C++ compiler.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub name: String,
+    pub value: String,
+}
+
+/// The value of the last annotation named `name`.
+pub fn annotation<'a>(annotations: &'a [Annotation], name: &str) -> Option<&'a str> {
+    annotations.iter().rev().find(|a| a.name == name).map(|a| &a.value[..])
+}
@@ -0,0 +1,202 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use {Error, Position};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// An identifier or a keyword. Identifiers may contain dots.
+    Identifier(String),
+    Literal(String),
+    Int(i64),
+    Double(f64),
+    /// One of `{}()<>[],;:=*`.
+    Symbol(char),
+    Eof,
+}
+
+impl Token {
+    /// How the token is shown in error messages.
+    pub fn describe(&self) -> String {
+        match *self {
+            Token::Identifier(ref x) => format!("`{}`", x),
+            Token::Literal(ref x) => format!("\"{}\"", x),
+            Token::Int(x) => format!("`{}`", x),
+            Token::Double(x) => format!("`{}`", x),
+            Token::Symbol(x) => format!("`{}`", x),
+            Token::Eof => String::from("end of file"),
+        }
+    }
+}
+
+pub struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: Position,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Lexer<'a> {
+        Lexer {
+            chars: source.chars().peekable(),
+            position: Position { line: 1, column: 1 },
+        }
+    }
+
+    /// The next token and where it starts.
+    pub fn next_token(&mut self) -> Result<(Token, Position), Error> {
+        self.skip_blanks()?;
+
+        let start = self.position;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok((Token::Eof, start)),
+        };
+
+        let token = if c.is_ascii_alphabetic() || c == '_' {
+            Token::Identifier(self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+        } else if c.is_ascii_digit() || c == '+' || c == '-' {
+            self.number(start)?
+        } else if c == '"' || c == '\'' {
+            self.bump();
+            let literal = self.take_while(|x| x != c);
+            if self.bump().is_none() {
+                return Err(Error::new(start, "unterminated string literal"));
+            }
+            Token::Literal(literal)
+        } else if "{}()<>[],;:=*".contains(c) {
+            self.bump();
+            Token::Symbol(c)
+        } else {
+            return Err(Error::new(start, format!("unexpected character `{}`", c)));
+        };
+
+        Ok((token, start))
+    }
+
+    fn number(&mut self, start: Position) -> Result<Token, Error> {
+        let mut text = String::new();
+        if let Some(sign) = self.peek().filter(|&c| c == '+' || c == '-') {
+            self.bump();
+            text.push(sign);
+        }
+
+        if self.peek() == Some('0') {
+            self.bump();
+            if self.peek() == Some('x') || self.peek() == Some('X') {
+                self.bump();
+                let digits = self.take_while(|c| c.is_ascii_hexdigit());
+                return i64::from_str_radix(&digits, 16)
+                    .map(|x| Token::Int(if text == "-" { -x } else { x }))
+                    .map_err(|_| Error::new(start, "invalid hexadecimal constant"));
+            }
+            text.push('0');
+        }
+
+        text.push_str(&self.take_while(|c| c.is_ascii_digit()));
+        let mut double = false;
+        if self.peek() == Some('.') {
+            double = true;
+            self.bump();
+            text.push('.');
+            text.push_str(&self.take_while(|c| c.is_ascii_digit()));
+        }
+        if self.peek() == Some('e') || self.peek() == Some('E') {
+            double = true;
+            self.bump();
+            text.push('e');
+            if let Some(sign) = self.peek().filter(|&c| c == '+' || c == '-') {
+                self.bump();
+                text.push(sign);
+            }
+            text.push_str(&self.take_while(|c| c.is_ascii_digit()));
+        }
+
+        if double {
+            text.parse().map(Token::Double).map_err(|_| Error::new(start, "invalid double constant"))
+        } else {
+            text.parse().map(Token::Int).map_err(|_| Error::new(start, "invalid integer constant"))
+        }
+    }
+
+    fn skip_blanks(&mut self) -> Result<(), Error> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('#') => {
+                    self.take_while(|c| c != '\n');
+                }
+                Some('/') => {
+                    let start = self.position;
+                    self.bump();
+                    match self.bump() {
+                        Some('/') => {
+                            self.take_while(|c| c != '\n');
+                        }
+                        Some('*') => {
+                            let mut star = false;
+                            loop {
+                                match self.bump() {
+                                    Some('/') if star => break,
+                                    Some(c) => star = c == '*',
+                                    None => return Err(Error::new(start, "unterminated comment")),
+                                }
+                            }
+                        }
+                        _ => return Err(Error::new(start, "unexpected character `/`")),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().cloned()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        match c {
+            Some('\n') => {
+                self.position.line += 1;
+                self.position.column = 1;
+            }
+            Some(_) => self.position.column += 1,
+            None => {}
+        }
+        c
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> String {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break;
+            }
+            self.bump();
+            text.push(c);
+        }
+        text
+    }
+}
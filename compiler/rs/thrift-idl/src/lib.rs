@@ -0,0 +1,71 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+//! A parser for Thrift IDL files, for tools written in Rust.
+//!
+//! ```
+//! let document = thrift_idl::parse("struct Point { 1: i32 x, 2: i32 y }").unwrap();
+//! assert_eq!(document.definitions[0].name(), "Point");
+//! ```
+
+use std::error::Error as StdError;
+use std::fmt;
+
+pub mod ast;
+mod lexer;
+mod parser;
+
+pub use ast::Document;
+
+/// Parses the contents of a Thrift file. Included files are not read.
+pub fn parse(source: &str) -> Result<Document, Error> {
+    parser::Parser::new(source)?.document()
+}
+
+/// A place in a Thrift file. Lines and columns start at 1, and columns count
+/// characters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A syntax error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub position: Position,
+    pub message: String,
+}
+
+impl Error {
+    fn new<S: Into<String>>(position: Position, message: S) -> Error {
+        Error { position, message: message.into() }
+    }
+}
+
+impl StdError for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.position.line, self.position.column, self.message)
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,422 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::vec;
+
+use ast::*;
+use lexer::{Lexer, Token};
+use {Error, Position};
+
+/// A recursive descent parser following the grammar of the C++ compiler,
+/// `thrifty.yy`, with one token of lookahead.
+pub struct Parser {
+    tokens: vec::IntoIter<(Token, Position)>,
+    token: Token,
+    position: Position,
+}
+
+impl Parser {
+    /// Reads all the tokens up front, so that lexical errors are reported
+    /// before any other.
+    pub fn new(source: &str) -> Result<Parser, Error> {
+        let mut lexer = Lexer::new(source);
+        let mut tokens = Vec::new();
+        loop {
+            let (token, position) = lexer.next_token()?;
+            let eof = token == Token::Eof;
+            tokens.push((token, position));
+            if eof {
+                break;
+            }
+        }
+
+        let mut parser = Parser {
+            tokens: tokens.into_iter(),
+            token: Token::Eof,
+            position: Position { line: 1, column: 1 },
+        };
+        parser.advance();
+        Ok(parser)
+    }
+
+    pub fn document(&mut self) -> Result<Document, Error> {
+        let mut document = Document::default();
+
+        loop {
+            if self.eat_keyword("include") {
+                document.includes.push(self.literal()?);
+            } else if self.eat_keyword("cpp_include") {
+                document.cpp_includes.push(self.literal()?);
+            } else if self.eat_keyword("namespace") {
+                let scope = if self.eat_symbol('*') { String::from("*") } else { self.identifier()? };
+                let name = match self.token {
+                    Token::Literal(_) => self.literal()?,
+                    _ => self.identifier()?,
+                };
+                let annotations = self.annotations()?;
+                document.namespaces.push(Namespace { scope, name, annotations });
+            } else {
+                break;
+            }
+            self.separator();
+        }
+
+        while self.token != Token::Eof {
+            let definition = self.definition()?;
+            document.definitions.push(definition);
+            self.separator();
+        }
+
+        Ok(document)
+    }
+
+    fn definition(&mut self) -> Result<Definition, Error> {
+        let keyword = match self.token {
+            Token::Identifier(ref x) => x.clone(),
+            _ => return Err(self.unexpected("a definition")),
+        };
+
+        let definition = match &keyword[..] {
+            "typedef" => {
+                self.advance();
+                let (ty, type_annotations) = self.annotated_type()?;
+                let name = self.identifier()?;
+                let annotations = self.annotations()?;
+                Definition::Typedef(Typedef { name, ty, type_annotations, annotations })
+            }
+            "const" => {
+                self.advance();
+                let ty = self.annotated_type()?.0;
+                let name = self.identifier()?;
+                self.expect_symbol('=')?;
+                let value = self.const_value()?;
+                Definition::Const(Const { name, ty, value })
+            }
+            "enum" => {
+                self.advance();
+                Definition::Enum(self.enumeration()?)
+            }
+            "senum" => {
+                self.advance();
+                let name = self.identifier()?;
+                self.expect_symbol('{')?;
+                let mut values = Vec::new();
+                while !self.eat_symbol('}') {
+                    values.push(self.literal()?);
+                    self.separator();
+                }
+                let annotations = self.annotations()?;
+                Definition::Senum(Senum { name, values, annotations })
+            }
+            "struct" => {
+                self.advance();
+                Definition::Struct(self.structure()?)
+            }
+            "union" => {
+                self.advance();
+                Definition::Union(self.structure()?)
+            }
+            "exception" => {
+                self.advance();
+                Definition::Exception(self.structure()?)
+            }
+            "service" => {
+                self.advance();
+                Definition::Service(self.service()?)
+            }
+            "include" | "cpp_include" | "namespace" => {
+                return Err(Error::new(self.position, format!("`{}` must come before the definitions", keyword)));
+            }
+            _ => return Err(self.unexpected("a definition")),
+        };
+
+        Ok(definition)
+    }
+
+    fn enumeration(&mut self) -> Result<Enum, Error> {
+        let name = self.identifier()?;
+        self.expect_symbol('{')?;
+        let mut values = Vec::new();
+        while !self.eat_symbol('}') {
+            let name = self.identifier()?;
+            let value = if self.eat_symbol('=') { Some(self.int()?) } else { None };
+            let annotations = self.annotations()?;
+            values.push(EnumValue { name, value, annotations });
+            self.separator();
+        }
+        let annotations = self.annotations()?;
+        Ok(Enum { name, values, annotations })
+    }
+
+    fn structure(&mut self) -> Result<Struct, Error> {
+        let name = self.identifier()?;
+        // Accepted and ignored, like the C++ compiler does for other languages.
+        self.eat_keyword("xsd_all");
+        self.expect_symbol('{')?;
+        let fields = self.fields('}')?;
+        let annotations = self.annotations()?;
+        Ok(Struct { name, fields, annotations })
+    }
+
+    fn service(&mut self) -> Result<Service, Error> {
+        let name = self.identifier()?;
+        let extends = if self.eat_keyword("extends") { Some(self.identifier()?) } else { None };
+        self.expect_symbol('{')?;
+
+        let mut functions = Vec::new();
+        while !self.eat_symbol('}') {
+            let oneway = self.eat_keyword("oneway");
+            let return_type = if self.eat_keyword("void") { None } else { Some(self.annotated_type()?.0) };
+            let name = self.identifier()?;
+            self.expect_symbol('(')?;
+            let params = self.fields(')')?;
+            let throws = if self.eat_keyword("throws") {
+                self.expect_symbol('(')?;
+                self.fields(')')?
+            } else {
+                Vec::new()
+            };
+            let annotations = self.annotations()?;
+            functions.push(Function { name, oneway, return_type, params, throws, annotations });
+            self.separator();
+        }
+
+        let annotations = self.annotations()?;
+        Ok(Service { name, extends, functions, annotations })
+    }
+
+    // Fields up to the closing symbol, which is consumed.
+    fn fields(&mut self, close: char) -> Result<Vec<Field>, Error> {
+        let mut fields = Vec::new();
+        while !self.eat_symbol(close) {
+            fields.push(self.field()?);
+            self.separator();
+        }
+        Ok(fields)
+    }
+
+    fn field(&mut self) -> Result<Field, Error> {
+        let id = if let Token::Int(_) = self.token {
+            let position = self.position;
+            let id = self.int()?;
+            if id < i16::MIN as i64 || id > i16::MAX as i64 {
+                return Err(Error::new(position, format!("field id {} is out of range", id)));
+            }
+            self.expect_symbol(':')?;
+            Some(id as i16)
+        } else {
+            None
+        };
+
+        let requiredness = if self.eat_keyword("required") {
+            Requiredness::Required
+        } else if self.eat_keyword("optional") {
+            Requiredness::Optional
+        } else {
+            Requiredness::Default
+        };
+
+        let (ty, type_annotations) = self.annotated_type()?;
+        let name = self.identifier()?;
+        let default = if self.eat_symbol('=') { Some(self.const_value()?) } else { None };
+        for keyword in &["xsd_optional", "xsd_nillable"] {
+            self.eat_keyword(keyword);
+        }
+        let annotations = self.annotations()?;
+
+        Ok(Field { id, requiredness, ty, name, default, type_annotations, annotations })
+    }
+
+    fn annotated_type(&mut self) -> Result<(Type, Vec<Annotation>), Error> {
+        let ty = self.field_type()?;
+        let annotations = self.annotations()?;
+        Ok((ty, annotations))
+    }
+
+    fn field_type(&mut self) -> Result<Type, Error> {
+        let name = self.identifier_or("a type")?;
+        let ty = match &name[..] {
+            "bool" => Type::Bool,
+            "byte" | "i8" => Type::Byte,
+            "i16" => Type::I16,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "double" => Type::Double,
+            "string" | "slist" => Type::String,
+            "binary" => Type::Binary,
+            "map" => {
+                self.cpp_type()?;
+                self.expect_symbol('<')?;
+                let key = self.annotated_type()?.0;
+                self.expect_symbol(',')?;
+                let value = self.annotated_type()?.0;
+                self.expect_symbol('>')?;
+                Type::Map(Box::new(key), Box::new(value))
+            }
+            "set" => {
+                self.cpp_type()?;
+                self.expect_symbol('<')?;
+                let elem = self.annotated_type()?.0;
+                self.expect_symbol('>')?;
+                Type::Set(Box::new(elem))
+            }
+            "list" => {
+                self.expect_symbol('<')?;
+                let elem = self.annotated_type()?.0;
+                self.expect_symbol('>')?;
+                self.cpp_type()?;
+                Type::List(Box::new(elem))
+            }
+            _ => Type::Named(name),
+        };
+        Ok(ty)
+    }
+
+    fn cpp_type(&mut self) -> Result<(), Error> {
+        if self.eat_keyword("cpp_type") {
+            self.literal()?;
+        }
+        Ok(())
+    }
+
+    fn const_value(&mut self) -> Result<ConstValue, Error> {
+        let value = match self.token.clone() {
+            Token::Int(x) => ConstValue::Int(x),
+            Token::Double(x) => ConstValue::Double(x),
+            Token::Literal(x) => ConstValue::Literal(x),
+            Token::Identifier(ref x) if x == "true" => ConstValue::Int(1),
+            Token::Identifier(ref x) if x == "false" => ConstValue::Int(0),
+            Token::Identifier(x) => ConstValue::Identifier(x),
+            Token::Symbol('[') => {
+                self.advance();
+                let mut elems = Vec::new();
+                while !self.eat_symbol(']') {
+                    elems.push(self.const_value()?);
+                    self.separator();
+                }
+                return Ok(ConstValue::List(elems));
+            }
+            Token::Symbol('{') => {
+                self.advance();
+                let mut entries = Vec::new();
+                while !self.eat_symbol('}') {
+                    let key = self.const_value()?;
+                    self.expect_symbol(':')?;
+                    let value = self.const_value()?;
+                    entries.push((key, value));
+                    self.separator();
+                }
+                return Ok(ConstValue::Map(entries));
+            }
+            _ => return Err(self.unexpected("a constant")),
+        };
+        self.advance();
+        Ok(value)
+    }
+
+    fn annotations(&mut self) -> Result<Vec<Annotation>, Error> {
+        let mut annotations = Vec::new();
+        if self.eat_symbol('(') {
+            while !self.eat_symbol(')') {
+                let name = self.identifier()?;
+                let value = if self.eat_symbol('=') { self.literal()? } else { String::from("1") };
+                annotations.push(Annotation { name, value });
+                self.separator();
+            }
+        }
+        Ok(annotations)
+    }
+
+    // An optional `,` or `;`, as found after most items.
+    fn separator(&mut self) {
+        let _ = self.eat_symbol(',') || self.eat_symbol(';');
+    }
+
+    fn identifier(&mut self) -> Result<String, Error> {
+        self.identifier_or("an identifier")
+    }
+
+    fn identifier_or(&mut self, expected: &str) -> Result<String, Error> {
+        match self.token.clone() {
+            Token::Identifier(x) => {
+                self.advance();
+                Ok(x)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn literal(&mut self) -> Result<String, Error> {
+        match self.token.clone() {
+            Token::Literal(x) => {
+                self.advance();
+                Ok(x)
+            }
+            _ => Err(self.unexpected("a string literal")),
+        }
+    }
+
+    fn int(&mut self) -> Result<i64, Error> {
+        match self.token {
+            Token::Int(x) => {
+                self.advance();
+                Ok(x)
+            }
+            _ => Err(self.unexpected("an integer")),
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), Error> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", symbol)))
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: char) -> bool {
+        if self.token == Token::Symbol(symbol) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.token {
+            Token::Identifier(ref x) if x == keyword => {}
+            _ => return false,
+        }
+        self.advance();
+        true
+    }
+
+    // Moves to the next token, staying on the end of file once there.
+    fn advance(&mut self) {
+        if let Some((token, position)) = self.tokens.next() {
+            self.token = token;
+            self.position = position;
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        Error::new(self.position, format!("expected {}, found {}", expected, self.token.describe()))
+    }
+}
@@ -0,0 +1,270 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use ast::*;
+use {parse, Position};
+
+fn annotations(pairs: &[(&str, &str)]) -> Vec<Annotation> {
+    pairs.iter().map(|&(name, value)| Annotation { name: name.to_string(), value: value.to_string() }).collect()
+}
+
+fn error_at(source: &str) -> (usize, usize, String) {
+    let err = parse(source).unwrap_err();
+    (err.position.line, err.position.column, err.message)
+}
+
+#[test]
+fn headers() {
+    let document = parse(r#"
+        include "shared.thrift"
+        cpp_include "<map>"
+        namespace cpp tutorial
+        namespace * all.of.them
+        namespace rs tutorial.rs;
+    "#).unwrap();
+
+    assert_eq!(document.includes, vec!["shared.thrift"]);
+    assert_eq!(document.cpp_includes, vec!["<map>"]);
+    assert_eq!(document.namespace("cpp"), Some("tutorial"));
+    assert_eq!(document.namespace("rs"), Some("tutorial.rs"));
+    assert_eq!(document.namespace("java"), Some("all.of.them"));
+    assert!(document.definitions.is_empty());
+}
+
+#[test]
+fn typedefs_and_consts() {
+    let document = parse(r#"
+        typedef i32 MyInteger
+        typedef list<double (cpp.fixed_point = "16")> cpp_type "std::list" Floats
+        typedef string (unicode.encoding = "UTF-16") Text (foo = "bar")
+        const i32 INT32CONSTANT = 9853
+        const map<string, list<i16>> MAPCONSTANT = {'hello': [1, -0x10], "goodnight": []};
+        const double HALF = 5e-1
+        const bool YES = true
+        const Operation OP = Operation.ADD
+    "#).unwrap();
+
+    assert_eq!(document.definitions, vec![
+        Definition::Typedef(Typedef {
+            name: String::from("MyInteger"),
+            ty: Type::I32,
+            type_annotations: vec![],
+            annotations: vec![],
+        }),
+        Definition::Typedef(Typedef {
+            name: String::from("Floats"),
+            ty: Type::List(Box::new(Type::Double)),
+            type_annotations: vec![],
+            annotations: vec![],
+        }),
+        Definition::Typedef(Typedef {
+            name: String::from("Text"),
+            ty: Type::String,
+            type_annotations: annotations(&[("unicode.encoding", "UTF-16")]),
+            annotations: annotations(&[("foo", "bar")]),
+        }),
+        Definition::Const(Const {
+            name: String::from("INT32CONSTANT"),
+            ty: Type::I32,
+            value: ConstValue::Int(9853),
+        }),
+        Definition::Const(Const {
+            name: String::from("MAPCONSTANT"),
+            ty: Type::Map(Box::new(Type::String), Box::new(Type::List(Box::new(Type::I16)))),
+            value: ConstValue::Map(vec![
+                (ConstValue::Literal(String::from("hello")),
+                 ConstValue::List(vec![ConstValue::Int(1), ConstValue::Int(-16)])),
+                (ConstValue::Literal(String::from("goodnight")), ConstValue::List(vec![])),
+            ]),
+        }),
+        Definition::Const(Const {
+            name: String::from("HALF"),
+            ty: Type::Double,
+            value: ConstValue::Double(0.5),
+        }),
+        Definition::Const(Const {
+            name: String::from("YES"),
+            ty: Type::Bool,
+            value: ConstValue::Int(1),
+        }),
+        Definition::Const(Const {
+            name: String::from("OP"),
+            ty: Type::Named(String::from("Operation")),
+            value: ConstValue::Identifier(String::from("Operation.ADD")),
+        }),
+    ]);
+}
+
+#[test]
+fn enums() {
+    let document = parse(r#"
+        enum Weekdays {
+          SUNDAY = 1 (weekend = "yes"),
+          MONDAY;
+          TUESDAY = 0x10
+        } (foo.bar = "baz", flag)
+        senum Seasons { "Spring", "Summer" }
+    "#).unwrap();
+
+    assert_eq!(document.definitions, vec![
+        Definition::Enum(Enum {
+            name: String::from("Weekdays"),
+            values: vec![
+                EnumValue { name: String::from("SUNDAY"), value: Some(1), annotations: annotations(&[("weekend", "yes")]) },
+                EnumValue { name: String::from("MONDAY"), value: None, annotations: vec![] },
+                EnumValue { name: String::from("TUESDAY"), value: Some(16), annotations: vec![] },
+            ],
+            annotations: annotations(&[("foo.bar", "baz"), ("flag", "1")]),
+        }),
+        Definition::Senum(Senum {
+            name: String::from("Seasons"),
+            values: vec![String::from("Spring"), String::from("Summer")],
+            annotations: vec![],
+        }),
+    ]);
+}
+
+#[test]
+fn structs() {
+    let document = parse(r#"
+        struct Work {
+          1: i32 num1 = 0,
+          2: required i32 num2 (presence = "required"),
+          3: optional shared.Operation op
+          string comment
+        } (cpp.type = "DenseWork")
+        union Either { 1: binary left; 2: set<i8> right; }
+        exception InvalidOperation { 1: i32 what }
+    "#).unwrap();
+
+    let field = |id, requiredness, ty, name: &str| Field {
+        id,
+        requiredness,
+        ty,
+        name: name.to_string(),
+        default: None,
+        type_annotations: vec![],
+        annotations: vec![],
+    };
+
+    assert_eq!(document.definitions, vec![
+        Definition::Struct(Struct {
+            name: String::from("Work"),
+            fields: vec![
+                Field { default: Some(ConstValue::Int(0)), ..field(Some(1), Requiredness::Default, Type::I32, "num1") },
+                Field {
+                    annotations: annotations(&[("presence", "required")]),
+                    ..field(Some(2), Requiredness::Required, Type::I32, "num2")
+                },
+                field(Some(3), Requiredness::Optional, Type::Named(String::from("shared.Operation")), "op"),
+                field(None, Requiredness::Default, Type::String, "comment"),
+            ],
+            annotations: annotations(&[("cpp.type", "DenseWork")]),
+        }),
+        Definition::Union(Struct {
+            name: String::from("Either"),
+            fields: vec![
+                field(Some(1), Requiredness::Default, Type::Binary, "left"),
+                field(Some(2), Requiredness::Default, Type::Set(Box::new(Type::Byte)), "right"),
+            ],
+            annotations: vec![],
+        }),
+        Definition::Exception(Struct {
+            name: String::from("InvalidOperation"),
+            fields: vec![field(Some(1), Requiredness::Default, Type::I32, "what")],
+            annotations: vec![],
+        }),
+    ]);
+}
+
+#[test]
+fn services() {
+    let document = parse(r#"
+        service Calculator extends shared.SharedService {
+           void ping(),
+           i32 add(1:i32 num1, 2:i32 num2) (rs.idempotent = "true"),
+           i32 calculate(1:i32 logid, 2:Work w) throws (1:InvalidOperation ouch),
+           oneway void zip()
+        } (a.b = "c")
+    "#).unwrap();
+
+    let service = match document.definitions[0] {
+        Definition::Service(ref service) => service,
+        ref other => panic!("Expected a service, got {:?}", other),
+    };
+    assert_eq!(service.name, "Calculator");
+    assert_eq!(service.extends, Some(String::from("shared.SharedService")));
+    assert_eq!(service.annotations, annotations(&[("a.b", "c")]));
+
+    let names: Vec<_> = service.functions.iter().map(|f| &f.name[..]).collect();
+    assert_eq!(names, vec!["ping", "add", "calculate", "zip"]);
+    assert_eq!(service.functions[0].return_type, None);
+    assert_eq!(service.functions[1].return_type, Some(Type::I32));
+    assert_eq!(service.functions[1].params.len(), 2);
+    assert_eq!(service.functions[1].annotations, annotations(&[("rs.idempotent", "true")]));
+    assert_eq!(service.functions[2].throws[0].ty, Type::Named(String::from("InvalidOperation")));
+    assert!(service.functions[3].oneway);
+    assert!(!service.functions[2].oneway);
+}
+
+#[test]
+fn comments() {
+    let document = parse("# shell\n// line\n/* block\n * comment */ /** doc */ struct A {}").unwrap();
+    assert_eq!(document.definitions[0].name(), "A");
+}
+
+#[test]
+fn error_positions() {
+    assert_eq!(error_at("struct A {\n  1: i32 x\n  2 i32 y\n}"),
+               (3, 5, String::from("expected `:`, found `i32`")));
+    assert_eq!(error_at("struct A {\n  1: i32 x"),
+               (2, 11, String::from("expected a type, found end of file")));
+    assert_eq!(error_at("enum E { A = \"x\" }"),
+               (1, 14, String::from("expected an integer, found \"x\"")));
+    assert_eq!(error_at("struct A {}\ninclude \"b.thrift\""),
+               (2, 1, String::from("`include` must come before the definitions")));
+    assert_eq!(error_at("const string S = \"never closed"),
+               (1, 18, String::from("unterminated string literal")));
+    assert_eq!(error_at("struct A { 1: i32 é }"),
+               (1, 19, String::from("unexpected character `é`")));
+    assert_eq!(error_at("struct A { 40000: i32 x }"),
+               (1, 12, String::from("field id 40000 is out of range")));
+    assert_eq!(parse("/* no end").unwrap_err().position, Position { line: 1, column: 1 });
+}
+
+#[test]
+fn display_error() {
+    assert_eq!(parse("service").unwrap_err().to_string(), "1:8: expected an identifier, found end of file");
+}
+
+#[test]
+fn repository_idl_files() {
+    for source in &[
+        include_str!("../../../../../test/ThriftTest.thrift"),
+        include_str!("../../../../../test/AnnotationTest.thrift"),
+        include_str!("../../../../../test/DebugProtoTest.thrift"),
+        include_str!("../../../../../test/SmallTest.thrift"),
+        include_str!("../../../../../tutorial/tutorial.thrift"),
+        include_str!("../../../../../tutorial/shared.thrift"),
+    ] {
+        if let Err(err) = parse(source) {
+            panic!("{} in {}", err, &source[..200]);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use transport::SetTimeout;
+use {Error, Result};
+
+type SetTimeoutFn<T> = fn(&mut T, Option<Duration>) -> io::Result<()>;
+type AbandonFn<T> = fn(&mut T) -> bool;
+
+/// The timeout state of a generated client.
+///
+/// Clients keep one of these next to their transport. The hooks into the
+/// transport are only recorded once a timeout or deadline is configured,
+/// which requires the transport to implement `SetTimeout`; clients over
+/// other transports never time out.
+pub struct Deadlines<T> {
+    timeout: Option<Duration>,
+    next_deadline: Option<Instant>,
+    hooks: Option<(SetTimeoutFn<T>, AbandonFn<T>)>,
+    usable: bool,
+}
+
+impl<T> Deadlines<T> {
+    pub fn new() -> Deadlines<T> {
+        Deadlines {
+            timeout: None,
+            next_deadline: None,
+            hooks: None,
+            usable: true,
+        }
+    }
+
+    /// Sets the timeout applied to every call without an explicit deadline.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) where T: SetTimeout {
+        self.timeout = timeout;
+        self.hooks = Some((<T as SetTimeout>::set_timeout, <T as SetTimeout>::abandon));
+    }
+
+    /// Sets the deadline of the next call only.
+    pub fn set_next_deadline(&mut self, deadline: Instant) where T: SetTimeout {
+        self.next_deadline = Some(deadline);
+        self.hooks = Some((<T as SetTimeout>::set_timeout, <T as SetTimeout>::abandon));
+    }
+
+    /// Whether the transport can still be used, which is no longer the case
+    /// after a call timed out halfway on a transport that cannot reconnect.
+    pub fn is_usable(&self) -> bool {
+        self.usable
+    }
+
+    /// Starts a call, returning its deadline if it has one.
+    pub fn start(&mut self) -> Option<Instant> {
+        match self.next_deadline.take() {
+            Some(deadline) => Some(deadline),
+            None => self.timeout.map(|timeout| Instant::now() + timeout)
+        }
+    }
+
+    /// Runs one attempt of a call over `transport`, bounded by `deadline`.
+    pub fn run<R, F>(&mut self, transport: &mut T, deadline: Option<Instant>, call: F) -> Result<R>
+    where F: FnOnce(&mut DeadlineTransport<T>) -> Result<R> {
+        if !self.usable {
+            return Err(Error::from(io::Error::new(io::ErrorKind::Other,
+                                                  "connection abandoned after a timeout")));
+        }
+
+        let (set_timeout, abandon) = match (deadline, self.hooks) {
+            (Some(_), Some(hooks)) => hooks,
+            _ => return call(&mut DeadlineTransport { inner: transport, deadline: None, armed: None })
+        };
+
+        let result = {
+            let mut bounded = DeadlineTransport {
+                inner: &mut *transport,
+                deadline: Some((deadline.unwrap(), set_timeout)),
+                armed: None,
+            };
+            bounded.arm().map_err(Error::from).and_then(|_| call(&mut bounded))
+        };
+
+        match result {
+            Err(Error::TransportError(ref err)) if is_timeout(err) => {
+                self.usable = abandon(transport);
+                Err(Error::Timeout)
+            }
+            result => {
+                // The deadline no longer applies to the connection once the
+                // call is over.
+                if let Err(err) = set_timeout(transport, None) {
+                    self.usable = abandon(transport);
+                    return Err(Error::from(err));
+                }
+                result
+            }
+        }
+    }
+}
+
+// How far the time left may fall below the timeout last set on the
+// transport before it is set again, which is about as fine as socket timeouts
+// get anyway.
+const REARM_SLACK_MS: u64 = 1;
+
+/// A transport bounding every read and write by a deadline.
+///
+/// The transport's timeout is set when the call starts, and only set again
+/// when an operation was cut short or the time left has fallen below it, so
+/// that most operations cost no system calls besides their own.
+pub struct DeadlineTransport<'a, T: 'a> {
+    inner: &'a mut T,
+    deadline: Option<(Instant, SetTimeoutFn<T>)>,
+    // The timeout last set on `inner`, if it still holds.
+    armed: Option<Duration>,
+}
+
+impl<'a, T> DeadlineTransport<'a, T> {
+    fn arm(&mut self) -> io::Result<()> {
+        if let Some((deadline, set_timeout)) = self.deadline {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "deadline exceeded"));
+            }
+
+            let left = deadline - now;
+            match self.armed {
+                Some(armed) if left + Duration::from_millis(REARM_SLACK_MS) >= armed => {}
+                _ => {
+                    try!(set_timeout(self.inner, Some(left)));
+                    self.armed = Some(left);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // After an interrupted or timed out operation the transport may have
+    // reconnected or otherwise lost its timeout, so the next one sets it again.
+    fn disarm_on<R>(&mut self, result: io::Result<R>) -> io::Result<R> {
+        if let Err(ref err) = result {
+            if err.kind() == io::ErrorKind::Interrupted || is_timeout(err) {
+                self.armed = None;
+            }
+        }
+        result
+    }
+}
+
+impl<'a, T: Read> Read for DeadlineTransport<'a, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        try!(self.arm());
+        let result = self.inner.read(buf);
+        self.disarm_on(result)
+    }
+}
+
+impl<'a, T: Write> Write for DeadlineTransport<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        try!(self.arm());
+        let result = self.inner.write(buf);
+        self.disarm_on(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        try!(self.arm());
+        let result = self.inner.flush();
+        self.disarm_on(result)
+    }
+}
+
+// Socket timeouts surface as `WouldBlock` on Unix and `TimedOut` on Windows.
+fn is_timeout(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => true,
+        _ => false
+    }
+}
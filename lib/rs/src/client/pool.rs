@@ -0,0 +1,215 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use {Error, Result};
+
+/// A pool of up to `size` clients, shared between threads.
+///
+/// Clients are created on demand by the factory and checked out for the
+/// duration of a call. A client whose call fails with a transport error or
+/// times out is dropped rather than returned, and a new one is created in its
+/// place.
+pub struct ClientPool<C> {
+    factory: Box<Fn() -> Result<C> + Send + Sync>,
+    validator: Option<Box<Fn(&mut C) -> bool + Send + Sync>>,
+    size: usize,
+    max_idle: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    state: Mutex<PoolState<C>>,
+    available: Condvar,
+}
+
+struct PoolState<C> {
+    idle: Vec<Connection<C>>,
+    open: usize,
+}
+
+struct Connection<C> {
+    client: C,
+    created: Instant,
+    last_used: Instant,
+}
+
+impl<C> ClientPool<C> {
+    pub fn new<F>(size: usize, factory: F) -> ClientPool<C>
+    where F: Fn() -> Result<C> + Send + Sync + 'static {
+        assert!(size != 0, "Can't create a pool of 0 clients.");
+
+        ClientPool {
+            factory: Box::new(factory),
+            validator: None,
+            size: size,
+            max_idle: None,
+            max_lifetime: None,
+            state: Mutex::new(PoolState { idle: Vec::new(), open: 0 }),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Closes clients that have been idle in the pool for longer than `max_idle`.
+    pub fn set_max_idle(&mut self, max_idle: Duration) {
+        self.max_idle = Some(max_idle);
+    }
+
+    /// Closes clients once they are older than `max_lifetime`.
+    pub fn set_max_lifetime(&mut self, max_lifetime: Duration) {
+        self.max_lifetime = Some(max_lifetime);
+    }
+
+    /// Checks idle clients with `validator` before handing them out, replacing
+    /// those for which it returns `false`.
+    pub fn set_validator<F>(&mut self, validator: F)
+    where F: Fn(&mut C) -> bool + Send + Sync + 'static {
+        self.validator = Some(Box::new(validator));
+    }
+
+    /// Checks out a client, blocking while all `size` clients are in use.
+    pub fn get(&self) -> Result<PooledClient<C>> {
+        loop {
+            let mut conn = {
+                let mut state = self.state.lock().unwrap();
+                loop {
+                    if let Some(conn) = state.idle.pop() {
+                        break conn;
+                    }
+
+                    if state.open < self.size {
+                        state.open += 1;
+                        drop(state);
+                        return self.connect();
+                    }
+
+                    state = self.available.wait(state).unwrap();
+                }
+            };
+
+            // The validator may make calls, so the pool is left unlocked
+            // meanwhile.
+            if self.is_usable(&mut conn) {
+                return Ok(PooledClient { pool: self, conn: Some(conn), broken: false });
+            }
+            drop(conn);
+            self.release(None);
+        }
+    }
+
+    /// Runs `call` with a checked out client, discarding the client if the call
+    /// fails with a transport error or times out.
+    pub fn call<R, F>(&self, call: F) -> Result<R>
+    where F: FnOnce(&mut C) -> Result<R> {
+        let mut client = try!(self.get());
+        let result = call(&mut client);
+        match result {
+            Err(Error::TransportError(_)) | Err(Error::Timeout) => client.discard(),
+            _ => {}
+        }
+        result
+    }
+
+    /// The number of clients currently open, both idle and checked out.
+    pub fn open(&self) -> usize {
+        self.state.lock().unwrap().open
+    }
+
+    fn connect(&self) -> Result<PooledClient<C>> {
+        match (self.factory)() {
+            Ok(client) => {
+                let now = Instant::now();
+                let conn = Connection { client: client, created: now, last_used: now };
+                Ok(PooledClient { pool: self, conn: Some(conn), broken: false })
+            }
+            Err(err) => {
+                self.release(None);
+                Err(err)
+            }
+        }
+    }
+
+    fn is_usable(&self, conn: &mut Connection<C>) -> bool {
+        if let Some(max_idle) = self.max_idle {
+            if conn.last_used.elapsed() > max_idle { return false; }
+        }
+
+        if let Some(max_lifetime) = self.max_lifetime {
+            if conn.created.elapsed() > max_lifetime { return false; }
+        }
+
+        match self.validator {
+            Some(ref validator) => validator(&mut conn.client),
+            None => true
+        }
+    }
+
+    fn release(&self, conn: Option<Connection<C>>) {
+        let mut state = self.state.lock().unwrap();
+        match conn {
+            Some(mut conn) => {
+                conn.last_used = Instant::now();
+                state.idle.push(conn);
+            }
+            None => state.open -= 1,
+        }
+        self.available.notify_one();
+    }
+}
+
+/// A client checked out of a `ClientPool`, returned to the pool when dropped.
+pub struct PooledClient<'a, C: 'a> {
+    pool: &'a ClientPool<C>,
+    conn: Option<Connection<C>>,
+    broken: bool,
+}
+
+impl<'a, C> PooledClient<'a, C> {
+    /// Closes the client instead of returning it to the pool, for instance
+    /// because the connection is in an unknown state.
+    pub fn discard(&mut self) {
+        self.broken = true;
+    }
+}
+
+impl<'a, C> Deref for PooledClient<'a, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.conn.as_ref().unwrap().client
+    }
+}
+
+impl<'a, C> DerefMut for PooledClient<'a, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.conn.as_mut().unwrap().client
+    }
+}
+
+impl<'a, C> Drop for PooledClient<'a, C> {
+    fn drop(&mut self) {
+        let conn = self.conn.take();
+        if self.broken {
+            drop(conn);
+            self.pool.release(None);
+        } else {
+            self.pool.release(conn);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use {Error, Result};
+
+/// When and how often failed calls are retried.
+///
+/// Attempts are spaced by an exponential backoff starting at
+/// `initial_backoff` and capped at `max_backoff`, each delay randomized by
+/// up to `jitter` of its value. Only errors accepted by the classifier are
+/// retried, by default those of `is_retryable`.
+///
+/// Generated clients only apply the policy to methods marked idempotent.
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: u32,
+    jitter: f64,
+    classifier: Box<Fn(&Error) -> bool + Send + Sync>,
+}
+
+impl RetryPolicy {
+    /// Creates a policy making at most `max_attempts` attempts in total,
+    /// backing off from 100ms up to 10s with 20% jitter.
+    pub fn new(max_attempts: u32) -> RetryPolicy {
+        assert!(max_attempts != 0, "Can't make 0 attempts.");
+
+        RetryPolicy {
+            max_attempts: max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2,
+            jitter: 0.2,
+            classifier: Box::new(is_retryable),
+        }
+    }
+
+    pub fn set_backoff(&mut self, initial: Duration, max: Duration) {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+    }
+
+    pub fn set_multiplier(&mut self, multiplier: u32) {
+        self.multiplier = multiplier;
+    }
+
+    /// Sets the fraction, between 0 and 1, by which delays are randomized.
+    pub fn set_jitter(&mut self, jitter: f64) {
+        assert!(jitter >= 0.0 && jitter <= 1.0, "Jitter must be between 0 and 1.");
+        self.jitter = jitter;
+    }
+
+    pub fn set_classifier<F>(&mut self, classifier: F)
+    where F: Fn(&Error) -> bool + Send + Sync + 'static {
+        self.classifier = Box::new(classifier);
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether a call that failed with `err` on its `attempt`th attempt
+    /// should be attempted again.
+    pub fn should_retry(&self, attempt: u32, err: &Error) -> bool {
+        attempt < self.max_attempts && (self.classifier)(err)
+    }
+
+    /// The delay before the attempt following the `attempt`th one.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let mut delay = self.initial_backoff;
+        for _ in 1..attempt {
+            delay = delay * self.multiplier;
+            if delay >= self.max_backoff { break; }
+        }
+        if delay > self.max_backoff { delay = self.max_backoff; }
+
+        let nanos = delay.as_secs() as f64 * 1e9 + delay.subsec_nanos() as f64;
+        let nanos = nanos * (1.0 - self.jitter * random_fraction());
+        Duration::new((nanos / 1e9) as u64, (nanos % 1e9) as u32)
+    }
+
+    /// Runs `call` until it succeeds, fails with an error that should not be
+    /// retried or runs out of attempts, sleeping between attempts.
+    pub fn call<R, F>(&self, mut call: F) -> Result<R>
+    where F: FnMut() -> Result<R> {
+        let mut attempt = 1;
+        loop {
+            match call() {
+                Err(ref err) if self.should_retry(attempt, err) => {
+                    debug!("retrying failed call after attempt {}: {}", attempt, err);
+                    thread::sleep(self.backoff(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// The default classifier: retries transport errors that indicate a lost or
+/// unavailable connection.
+pub fn is_retryable(err: &Error) -> bool {
+    match *err {
+        Error::TransportError(ref err) => match err.kind() {
+            io::ErrorKind::ConnectionRefused |
+            io::ErrorKind::ConnectionReset |
+            io::ErrorKind::ConnectionAborted |
+            io::ErrorKind::NotConnected |
+            io::ErrorKind::BrokenPipe |
+            io::ErrorKind::UnexpectedEof |
+            io::ErrorKind::TimedOut => true,
+            _ => false
+        },
+        _ => false
+    }
+}
+
+/// Runs `call` under `policy`, or just once when there is no policy.
+pub fn call<R, F>(policy: Option<&RetryPolicy>, mut call: F) -> Result<R>
+where F: FnMut() -> Result<R> {
+    match policy {
+        Some(policy) => policy.call(call),
+        None => call()
+    }
+}
+
+// A uniformly distributed number in [0, 1); `RandomState` is seeded randomly
+// for every instance, which is plenty for jitter.
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,537 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{ClientPool, Deadlines, RetryPolicy};
+
+use compiletest::*;
+use protocol::binary_protocol::BinaryProtocol;
+use server::SimpleServer;
+use transport::{ReconnectingTransport, SetTimeout};
+use {Error, Processor};
+
+fn counting_pool(size: usize) -> (ClientPool<usize>, Arc<AtomicUsize>) {
+    let created = Arc::new(AtomicUsize::new(0));
+    let counter = created.clone();
+    let pool = ClientPool::new(size, move || Ok(counter.fetch_add(1, Ordering::SeqCst)));
+    (pool, created)
+}
+
+fn transport_error() -> Error {
+    Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+}
+
+#[test]
+fn reuses_clients() {
+    let (pool, created) = counting_pool(2);
+
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 0);
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 0);
+    assert_eq!(created.load(Ordering::SeqCst), 1);
+    assert_eq!(pool.open(), 1);
+}
+
+#[test]
+fn opens_up_to_size() {
+    let (pool, _) = counting_pool(2);
+
+    let first = pool.get().unwrap();
+    let second = pool.get().unwrap();
+    assert_eq!((*first, *second), (0, 1));
+    assert_eq!(pool.open(), 2);
+}
+
+#[test]
+fn blocks_when_exhausted() {
+    let (pool, _) = counting_pool(1);
+    let pool = Arc::new(pool);
+
+    let client = pool.get().unwrap();
+    let other = pool.clone();
+    let waiter = thread::spawn(move || *other.get().unwrap());
+
+    thread::sleep(Duration::from_millis(50));
+    drop(client);
+    assert_eq!(waiter.join().unwrap(), 0);
+}
+
+#[test]
+fn replaces_client_after_transport_error() {
+    let (pool, created) = counting_pool(1);
+
+    assert!(pool.call(|_| -> ::Result<()> { Err(transport_error()) }).is_err());
+    assert_eq!(pool.open(), 0);
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 1);
+    assert_eq!(created.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn keeps_client_after_user_exception() {
+    let (pool, _) = counting_pool(1);
+
+    assert!(pool.call(|_| -> ::Result<()> { Err(Error::UserException) }).is_err());
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 0);
+}
+
+#[test]
+fn validator_replaces_clients() {
+    let (mut pool, _) = counting_pool(1);
+    pool.set_validator(|c| *c != 0);
+
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 0);
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 1);
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 1);
+}
+
+#[test]
+fn validates_without_locking_the_pool() {
+    let (entered, validating) = mpsc::channel();
+    let (proceed, waiting) = mpsc::channel();
+    let (entered, waiting) = (Mutex::new(entered), Mutex::new(waiting));
+
+    let (mut pool, _) = counting_pool(2);
+    pool.set_validator(move |_| {
+        entered.lock().unwrap().send(()).unwrap();
+        waiting.lock().unwrap().recv().unwrap();
+        true
+    });
+    drop(pool.get().unwrap());
+
+    let pool = Arc::new(pool);
+    let other = pool.clone();
+    let validated = thread::spawn(move || *other.get().unwrap());
+
+    validating.recv().unwrap();
+    assert_eq!(pool.open(), 1);
+    assert_eq!(*pool.get().unwrap(), 1);
+    proceed.send(()).unwrap();
+    assert_eq!(validated.join().unwrap(), 0);
+}
+
+#[test]
+fn max_idle() {
+    let (mut pool, _) = counting_pool(1);
+    pool.set_max_idle(Duration::from_millis(10));
+
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 0);
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 0);
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(pool.call(|c| Ok(*c)).unwrap(), 1);
+}
+
+#[test]
+fn max_lifetime() {
+    let (mut pool, _) = counting_pool(1);
+    pool.set_max_lifetime(Duration::from_millis(20));
+
+    let mut seen = Vec::new();
+    for _ in 0..4 {
+        seen.push(pool.call(|c| Ok(*c)).unwrap());
+        thread::sleep(Duration::from_millis(8));
+    }
+    assert_eq!(seen[0], 0);
+    assert!(seen[3] > 0);
+}
+
+#[test]
+fn factory_error_frees_slot() {
+    let pool: ClientPool<usize> = ClientPool::new(1, || Err(transport_error()));
+
+    assert!(pool.get().is_err());
+    assert!(pool.get().is_err());
+    assert_eq!(pool.open(), 0);
+}
+
+struct Handler;
+
+impl SharedService for Handler {
+    fn get_struct(&self, _key: i32) -> SharedServiceGetStructResult {
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: None }) }
+    }
+}
+
+#[test]
+fn pooled_generated_clients() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        SimpleServer::new(SharedServiceProcessor::new(Handler), listener, || BinaryProtocol::new()).serve();
+    });
+
+    let pool = Arc::new(ClientPool::new(1, move || {
+        let stream = try!(TcpStream::connect(addr));
+        Ok(SharedServiceClient::new(BinaryProtocol::new(), stream))
+    }));
+
+    let threads: Vec<_> = (0..4).map(|i| {
+        let pool = pool.clone();
+        thread::spawn(move || pool.call(|client| client.get_struct(i)).unwrap())
+    }).collect();
+
+    for thread in threads {
+        assert!(thread.join().unwrap().success.is_some());
+    }
+    assert_eq!(pool.open(), 1);
+}
+
+fn fast_retry(attempts: u32) -> RetryPolicy {
+    let mut policy = RetryPolicy::new(attempts);
+    policy.set_backoff(Duration::from_millis(0), Duration::from_millis(0));
+    policy
+}
+
+#[test]
+fn retry_until_success() {
+    let mut attempts = 0;
+    let result = fast_retry(3).call(|| {
+        attempts += 1;
+        if attempts < 3 { Err(transport_error()) } else { Ok(attempts) }
+    });
+
+    assert_eq!(result.unwrap(), 3);
+}
+
+#[test]
+fn retry_gives_up_after_max_attempts() {
+    let mut attempts = 0;
+    let result: ::Result<()> = fast_retry(3).call(|| {
+        attempts += 1;
+        Err(transport_error())
+    });
+
+    assert!(result.is_err());
+    assert_eq!(attempts, 3);
+}
+
+#[test]
+fn retry_only_retryable_errors() {
+    let mut attempts = 0;
+    let result: ::Result<()> = fast_retry(3).call(|| {
+        attempts += 1;
+        Err(Error::UserException)
+    });
+
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+
+    let mut policy = fast_retry(3);
+    policy.set_classifier(|_| true);
+    assert!(policy.should_retry(1, &Error::UserException));
+    assert!(!policy.should_retry(3, &Error::UserException));
+}
+
+#[test]
+fn exponential_backoff() {
+    let mut policy = RetryPolicy::new(10);
+    policy.set_backoff(Duration::from_millis(100), Duration::from_millis(1000));
+    policy.set_jitter(0.0);
+
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(4), Duration::from_millis(800));
+    assert_eq!(policy.backoff(5), Duration::from_millis(1000));
+    assert_eq!(policy.backoff(9), Duration::from_millis(1000));
+
+    policy.set_jitter(0.5);
+    for _ in 0..100 {
+        let delay = policy.backoff(2);
+        assert!(delay > Duration::from_millis(100) && delay <= Duration::from_millis(200));
+    }
+}
+
+#[test]
+fn idempotent_methods() {
+    assert!(SharedServiceClient::<BinaryProtocol, TcpStream>::is_idempotent("get_struct"));
+    assert!(ChildServiceClient::<BinaryProtocol, TcpStream>::is_idempotent("get_struct"));
+    assert!(!ChildServiceClient::<BinaryProtocol, TcpStream>::is_idempotent("operation"));
+}
+
+// Serves a single call per connection, as if the server restarted after each.
+fn spawn_flaky_server() -> ::std::net::SocketAddr {
+    spawn_flaky(SharedServiceProcessor::new(Handler))
+}
+
+fn spawn_flaky<P>(processor: P) -> ::std::net::SocketAddr
+where P: Processor<BinaryProtocol, TcpStream> + Send + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let _ = processor.process(&mut BinaryProtocol::new(), &mut stream.unwrap());
+        }
+    });
+
+    addr
+}
+
+#[test]
+fn reconnect_and_retry() {
+    let addr = spawn_flaky_server();
+    let transport = ReconnectingTransport::new(move || TcpStream::connect(addr));
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), transport);
+    client.set_retry_policy(fast_retry(3));
+
+    for i in 0..3 {
+        assert!(client.get_struct(i).unwrap().success.is_some());
+    }
+}
+
+impl ChildService for Handler {
+    fn operation(&self, _one: String, _another: i32) -> ChildServiceOperationResult {
+        ChildServiceOperationResult { success: Some(Operation::Add) }
+    }
+}
+
+#[test]
+fn retry_inherited_idempotent_methods() {
+    let addr = spawn_flaky(ChildServiceProcessor::new(Handler));
+    let transport = ReconnectingTransport::new(move || TcpStream::connect(addr));
+    let mut client = ChildServiceClient::new(BinaryProtocol::new(), transport);
+    client.set_retry_policy(fast_retry(3));
+
+    for i in 0..3 {
+        assert!(client.get_struct(i).unwrap().success.is_some());
+    }
+
+    // Not idempotent, so the call on the stale connection is not retried.
+    assert!(client.operation(String::new(), 0).is_err());
+    assert!(client.operation(String::new(), 0).is_ok());
+}
+
+#[test]
+fn reconnect_without_retry_fails_once() {
+    let addr = spawn_flaky_server();
+    let transport = ReconnectingTransport::new(move || TcpStream::connect(addr));
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), transport);
+
+    assert!(client.get_struct(1).is_ok());
+    match client.get_struct(2) {
+        Err(Error::TransportError(_)) => {}
+        other => panic!("Expected a transport error, got {:?}", other.map(|_| ()))
+    }
+    assert!(!client.transport.is_connected());
+    assert!(client.get_struct(3).is_ok());
+}
+
+// Fails every other operation with `Interrupted`, as a signal would.
+struct Interrupting(bool);
+
+impl io::Read for Interrupting {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0 = !self.0;
+        if self.0 { Err(io::Error::new(io::ErrorKind::Interrupted, "signal")) } else { Ok(buf.len()) }
+    }
+}
+
+impl io::Write for Interrupting {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 = !self.0;
+        if self.0 { Err(io::Error::new(io::ErrorKind::Interrupted, "signal")) } else { Ok(buf.len()) }
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+#[test]
+fn reconnecting_transport_keeps_connection_when_interrupted() {
+    use std::io::{Read, Write};
+
+    let dials = AtomicUsize::new(0);
+    let mut transport = ReconnectingTransport::new(|| {
+        dials.fetch_add(1, Ordering::SeqCst);
+        Ok(Interrupting(false))
+    });
+
+    let mut buf = [0; 4];
+    transport.read_exact(&mut buf).unwrap();
+    transport.write_all(&buf).unwrap();
+    assert!(transport.is_connected());
+    assert_eq!(dials.load(Ordering::SeqCst), 1);
+    assert!(!super::retry::is_retryable(&Error::from(io::Error::new(io::ErrorKind::Interrupted, "signal"))));
+}
+
+// Sleeps for `key` milliseconds before replying.
+struct SlowHandler;
+
+impl SharedService for SlowHandler {
+    fn get_struct(&self, key: i32) -> SharedServiceGetStructResult {
+        thread::sleep(Duration::from_millis(key as u64));
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: None }) }
+    }
+}
+
+fn spawn_slow_server() -> ::std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let processor = Arc::new(SharedServiceProcessor::new(SlowHandler));
+        for stream in listener.incoming() {
+            let processor = processor.clone();
+            let mut stream = stream.unwrap();
+            thread::spawn(move || {
+                while let Ok(_) = processor.process(&mut BinaryProtocol::new(), &mut stream) { }
+            });
+        }
+    });
+
+    addr
+}
+
+#[test]
+fn default_timeout() {
+    let addr = spawn_slow_server();
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    client.set_timeout(Some(Duration::from_millis(100)));
+
+    assert!(client.get_struct(0).is_ok());
+    assert!(client.transport.read_timeout().unwrap().is_none());
+
+    match client.get_struct(500) {
+        Err(Error::Timeout) => {}
+        other => panic!("Expected a timeout, got {:?}", other.map(|_| ()))
+    }
+    assert!(!client.is_usable());
+    assert!(client.get_struct(0).is_err());
+}
+
+#[test]
+fn call_with_deadline() {
+    let addr = spawn_slow_server();
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+
+    let deadline = Instant::now() + Duration::from_millis(500);
+    assert!(client.with_deadline(deadline).get_struct(10).is_ok());
+
+    // The deadline only applies to a single call.
+    assert!(client.get_struct(200).is_ok());
+
+    let deadline = Instant::now() + Duration::from_millis(50);
+    match client.with_deadline(deadline).get_struct(500) {
+        Err(Error::Timeout) => {}
+        other => panic!("Expected a timeout, got {:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn expired_deadline() {
+    let addr = spawn_slow_server();
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+
+    match client.with_deadline(Instant::now()).get_struct(0) {
+        Err(Error::Timeout) => {}
+        other => panic!("Expected a timeout, got {:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn timeout_with_reconnecting_transport() {
+    let addr = spawn_slow_server();
+    let transport = ReconnectingTransport::new(move || TcpStream::connect(addr));
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), transport);
+    client.set_timeout(Some(Duration::from_millis(100)));
+
+    match client.get_struct(500) {
+        Err(Error::Timeout) => {}
+        other => panic!("Expected a timeout, got {:?}", other.map(|_| ()))
+    }
+    assert!(client.is_usable());
+    assert!(client.get_struct(0).is_ok());
+}
+
+// Records the timeouts set on it, interrupting every other operation if
+// `interrupting`.
+struct Timed {
+    interrupting: Option<Interrupting>,
+    timeouts: Vec<Option<Duration>>,
+}
+
+impl io::Read for Timed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.interrupting {
+            Some(ref mut interrupting) => interrupting.read(buf),
+            None => Ok(buf.len()),
+        }
+    }
+}
+
+impl io::Write for Timed {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.interrupting {
+            Some(ref mut interrupting) => interrupting.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+impl SetTimeout for Timed {
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeouts.push(timeout);
+        Ok(())
+    }
+}
+
+// Reads and writes a byte at a time, many times over.
+fn chatty_call(deadlines: &mut Deadlines<Timed>, transport: &mut Timed) {
+    use std::io::{Read, Write};
+
+    let deadline = deadlines.start();
+    deadlines.run(transport, deadline, |transport| {
+        let mut buf = [0; 1];
+        for _ in 0..100 {
+            try!(transport.write_all(&buf));
+            try!(transport.flush());
+            try!(transport.read_exact(&mut buf));
+        }
+        Ok(())
+    }).unwrap();
+}
+
+#[test]
+fn deadline_sets_timeout_when_call_starts() {
+    let mut deadlines = Deadlines::new();
+    deadlines.set_timeout(Some(Duration::from_secs(10)));
+    let mut transport = Timed { interrupting: None, timeouts: Vec::new() };
+
+    chatty_call(&mut deadlines, &mut transport);
+    assert!(transport.timeouts.len() < 10);
+    assert!(transport.timeouts[0].is_some());
+    assert_eq!(transport.timeouts.last(), Some(&None));
+}
+
+#[test]
+fn deadline_rearms_after_interruption() {
+    let mut deadlines = Deadlines::new();
+    deadlines.set_timeout(Some(Duration::from_secs(10)));
+    let mut transport = Timed { interrupting: Some(Interrupting(false)), timeouts: Vec::new() };
+
+    // Every operation is interrupted once, and set again before its retry.
+    chatty_call(&mut deadlines, &mut transport);
+    assert!(transport.timeouts.len() > 200);
+    assert_eq!(transport.timeouts.last(), Some(&None));
+}
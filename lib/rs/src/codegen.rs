@@ -3,70 +3,177 @@ macro_rules! service {
     (trait_name = $name:ident,
      processor_name = $processor_name:ident,
      client_name = $client_name:ident,
-     service_methods = [$($siname:ident -> $soname:ident = $smfname:ident.$smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
-     parent_methods = [$($piname:ident -> $poname:ident = $pmfname:ident.$pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*],
-     bounds = [$($boundty:ident: $bound:ident,)*],
-     fields = [$($fname:ident: $fty:ty,)*]) => {
-        pub trait $name {
+     parents = [$($parent:path,)*],
+     service_methods = [$($siname:ident -> $soname:ident = $smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
+     parent_methods = [$(($piname:ty, $poname:ty) = $pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*]) => {
+        service! {
+            trait_name = $name,
+            processor_name = $processor_name,
+            client_name = $client_name,
+            parents = [$($parent,)*],
+            service_methods = [$($siname -> $soname = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
+            parent_methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*],
+            idempotent = []
+        }
+    };
+    (trait_name = $name:ident,
+     processor_name = $processor_name:ident,
+     client_name = $client_name:ident,
+     parents = [$($parent:path,)*],
+     service_methods = [$($siname:ident -> $soname:ident = $smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
+     parent_methods = [$(($piname:ty, $poname:ty) = $pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*],
+     idempotent = [$($idempotent:ident,)*]) => {
+        /// The handler of the service. It also handles the methods of the
+        /// services this one extends, through their traits.
+        pub trait $name: $($parent +)* {
             $(fn $smname(&self, $($saname: $saty),*) -> $soname;)*
         }
 
         service_processor! {
             processor_name = $processor_name,
-            service_methods = [$($siname -> $soname = $smfname.$smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
-            parent_methods = [$($piname -> $poname = $pmfname.$pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*],
-            bounds = [$($boundty: $bound,)*],
-            fields = [$($fname: $fty,)*]
+            handler_trait = $name,
+            service_methods = [$($siname -> $soname = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
+            parent_methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*],
+            context = []
+        }
+
+        service_client! {
+            client_name = $client_name,
+            service_methods = [$($siname -> $soname = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
+            parent_methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*],
+            idempotent = [$($idempotent,)*]
+        }
+    };
+    (trait_name = $name:ident,
+     context_trait_name = $context_name:ident,
+     processor_name = $processor_name:ident,
+     client_name = $client_name:ident,
+     parents = [$($parent:path => $parent_context:path,)*],
+     service_methods = [$($siname:ident -> $soname:ident = $smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
+     parent_methods = [$(($piname:ty, $poname:ty) = $pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*]) => {
+        service! {
+            trait_name = $name,
+            context_trait_name = $context_name,
+            processor_name = $processor_name,
+            client_name = $client_name,
+            parents = [$($parent => $parent_context,)*],
+            service_methods = [$($siname -> $soname = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
+            parent_methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*],
+            idempotent = []
+        }
+    };
+    (trait_name = $name:ident,
+     context_trait_name = $context_name:ident,
+     processor_name = $processor_name:ident,
+     client_name = $client_name:ident,
+     parents = [$($parent:path => $parent_context:path,)*],
+     service_methods = [$($siname:ident -> $soname:ident = $smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
+     parent_methods = [$(($piname:ty, $poname:ty) = $pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*],
+     idempotent = [$($idempotent:ident,)*]) => {
+        /// The handler of the service. It also handles the methods of the
+        /// services this one extends, through their traits.
+        pub trait $name: $($parent +)* {
+            $(fn $smname(&self, $($saname: $saty),*) -> $soname;)*
+        }
+
+        /// A variant of the service trait whose methods also receive the
+        /// context of the connection the call arrived on. Every handler of the
+        /// plain trait implements it by ignoring the context.
+        pub trait $context_name: $($parent_context +)* {
+            $(fn $smname(&self, context: &$crate::ConnectionContext, $($saname: $saty),*) -> $soname;)*
+        }
+
+        impl<H: $name + ?Sized> $context_name for H {
+            $(fn $smname(&self, _context: &$crate::ConnectionContext, $($saname: $saty),*) -> $soname {
+                $name::$smname(self, $($saname),*)
+            })*
+        }
+
+        service_processor! {
+            processor_name = $processor_name,
+            handler_trait = $context_name,
+            service_methods = [$($siname -> $soname = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
+            parent_methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*],
+            context = [$context_name]
         }
 
         service_client! {
             client_name = $client_name,
-            service_methods = [$($siname -> $soname = $smfname.$smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
-            parent_methods = [$($piname -> $poname = $pmfname.$pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*]
+            service_methods = [$($siname -> $soname = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
+            parent_methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*],
+            idempotent = [$($idempotent,)*]
         }
     }
 }
 
+// The argument and result structs of the parent methods are those the parent
+// services defined, given by path.
 #[macro_export]
 macro_rules! service_processor {
     (processor_name = $name:ident,
-     service_methods = [$($siname:ident -> $soname:ident = $smfname:ident.$smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
-     parent_methods = [$($piname:ident -> $poname:ident = $pmfname:ident.$pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*],
-     bounds = [$($boundty:ident: $bound:ident,)*],
-     fields = [$($fname:ident: $fty:ty,)*]) => {
-        pub struct $name<$($boundty: $bound),*> {
-            $($fname: $fty,)*
-            _ugh: ()
+     handler_trait = $handler_trait:ident,
+     service_methods = [$($siname:ident -> $soname:ident = $smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
+     parent_methods = [$(($piname:ty, $poname:ty) = $pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*],
+     context = $context:tt) => {
+        pub struct $name<H: $handler_trait> {
+            handler: H,
+            interceptors: $crate::interceptor::Interceptors
         }
 
         $(strukt! { name = $siname, fields = { $($saname: $saty => $said,)* } }
-          strukt! { name = $soname, fields = { success: $srty => 0, $($sename: $sety => $seid,)* } })*
+          strukt! { name = $soname, fields = { success: $srty => 0, $($sename: $sety => $seid,)* } }
+
+          // Handlers return either the result or one of the exceptions.
+          impl $soname {
+              pub fn success(success: $srty) -> Self {
+                  let mut result = $soname::default();
+                  result.success = Some(success);
+                  result
+              }
+
+              $(pub fn $sename($sename: $sety) -> Self {
+                  let mut result = $soname::default();
+                  result.$sename = Some($sename);
+                  result
+              })*
+          })*
+
+        impl<H: $handler_trait> $name<H> {
+            pub fn new(handler: H) -> Self {
+                $name { handler: handler, interceptors: $crate::interceptor::Interceptors::new() }
+            }
 
-        impl<$($boundty: $bound),*> $name<$($boundty),*> {
-            pub fn new($($fname: $fty),*) -> Self {
-                $name { $($fname: $fname,)* _ugh: () }
+            /// Runs `interceptor` around every call handled by this processor.
+            pub fn add_interceptor<I: $crate::Interceptor + 'static>(&mut self, interceptor: I) {
+                self.interceptors.add(interceptor);
             }
 
             pub fn dispatch<P: $crate::Protocol, T: $crate::Transport>(&self, prot: &mut P, transport: &mut T,
+                                                                       context: &$crate::ConnectionContext,
                                                                        name: &str, ty: $crate::protocol::MessageType, id: i32) -> $crate::Result<()> {
                 match name {
-                    $(stringify!($smname) => self.$smname(prot, transport, ty, id),)*
-                    $(stringify!($pmname) => self.$pmname(prot, transport, ty, id),)*
+                    $(stringify!($smname) => self.$smname(prot, transport, context, ty, id),)*
+                    $(stringify!($pmname) => self.$pmname(prot, transport, context, ty, id),)*
                     _ => Err($crate::Error::from($crate::protocol::Error::ProtocolViolation))
                 }
             }
 
-            service_processor_methods! { methods = [$($siname -> $soname = $smfname.$smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*] }
-            service_processor_methods! { methods = [$($piname -> $poname = $pmfname.$pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*] }
+            service_processor_methods! { methods = [$(($siname, $soname) = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*], context = $context }
+            service_processor_methods! { methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*], context = $context }
         }
 
-        impl<P: $crate::Protocol, T: $crate::Transport, $($boundty: $bound),*> $crate::Processor<P, T> for $name<$($boundty),*> {
+        impl<P: $crate::Protocol, T: $crate::Transport, H: $handler_trait> $crate::Processor<P, T> for $name<H> {
             fn process(&self, protocol: &mut P, transport: &mut T) -> $crate::Result<()> {
+                self.process_in_context(protocol, transport, &$crate::ConnectionContext::new())
+            }
+
+            fn process_in_context(&self, protocol: &mut P, transport: &mut T,
+                                  context: &$crate::ConnectionContext) -> $crate::Result<()> {
                 #[allow(unused_imports)]
                 use $crate::Protocol;
 
                 let (name, ty, id) = try!(protocol.read_message_begin(transport));
-                self.dispatch(protocol, transport, &name, ty, id)
+                self.dispatch(protocol, transport, context, &name, ty, id)
             }
         }
     }
@@ -74,65 +181,166 @@ macro_rules! service_processor {
 
 #[macro_export]
 macro_rules! service_processor_methods {
-    (methods = [$($iname:ident -> $oname:ident = $fname:ident.$mname:ident($($aname:ident: $aty:ty => $aid:expr,)*) -> $rty:ty => [$($ename:ident: $ety:ty => $eid:expr,)*],)*]) => {
+    (methods = [$(($iname:ty, $oname:ty) = $mname:ident($($aname:ident: $aty:ty => $aid:expr,)*) -> $rty:ty => [$($ename:ident: $ety:ty => $eid:expr,)*],)*],
+     context = $context:tt) => {
         $(fn $mname<P: $crate::Protocol, T: $crate::Transport>(&self, prot: &mut P, transport: &mut T,
+                                                               context: &$crate::ConnectionContext,
                                                                ty: $crate::protocol::MessageType, id: i32) -> $crate::Result<()> {
             static MNAME: &'static str = stringify!($mname);
 
-            let mut args = $iname::default();
+            let mut args = <$iname>::default();
             try!($crate::protocol::helpers::receive_body(prot, transport, MNAME,
                                                          &mut args, MNAME, ty, id));
 
-            // TODO: Further investigate this unwrap.
-            let result = self.$fname.$mname($(args.$aname.unwrap()),*);
-            try!($crate::protocol::helpers::send(prot, transport, MNAME,
-                                                 $crate::protocol::MessageType::Reply, &result));
-
-            Ok(())
+            let call = $crate::interceptor::Call { method: MNAME, sequence_id: id };
+            let mut handled = false;
+            let result = self.interceptors.intercept(&call, || {
+                handled = true;
+                // TODO: Further investigate this unwrap.
+                let result = service_handler_call!($context, self.handler, $mname, context,
+                                                   ($(args.$aname.unwrap()),*));
+                $crate::protocol::helpers::send_message(prot, transport, MNAME,
+                                                        $crate::protocol::MessageType::Reply, id, &result)
+            });
+
+            // An interceptor rejected the call, which the client is told
+            // about rather than left waiting for a reply.
+            match result {
+                Err(ref err) if !handled => {
+                    let exception = $crate::exception::ApplicationException::new(
+                        $crate::exception::ExceptionKind::Unknown, &err.to_string());
+                    $crate::protocol::helpers::send_message(prot, transport, MNAME,
+                                                            $crate::protocol::MessageType::Exception, id, &exception)
+                }
+                result => result
+            }
         })*
     }
 }
 
+// Calls a handler method, passing it the connection context only if the
+// service has a context-aware trait.
+#[macro_export]
+macro_rules! service_handler_call {
+    ([], $handler:expr, $method:ident, $context:expr, ($($arg:expr),*)) => {{
+        let _ = $context;
+        $handler.$method($($arg),*)
+    }};
+    ([$context_name:ident], $handler:expr, $method:ident, $context:expr, ($($arg:expr),*)) => {
+        $handler.$method($context, $($arg),*)
+    }
+}
+
 #[macro_export]
 macro_rules! service_client {
     (client_name = $client_name:ident,
-     service_methods = [$($siname:ident -> $soname:ident = $smfname:ident.$smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
-     parent_methods = [$($piname:ident -> $poname:ident = $pmfname:ident.$pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*]) => {
+     service_methods = [$($siname:ident -> $soname:ident = $smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
+     parent_methods = [$(($piname:ty, $poname:ty) = $pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*]) => {
+        service_client! {
+            client_name = $client_name,
+            service_methods = [$($siname -> $soname = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*],
+            parent_methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*],
+            idempotent = []
+        }
+    };
+    (client_name = $client_name:ident,
+     service_methods = [$($siname:ident -> $soname:ident = $smname:ident($($saname:ident: $saty:ty => $said:expr,)*) -> $srty:ty => [$($sename:ident: $sety:ty => $seid:expr,)*],)*],
+     parent_methods = [$(($piname:ty, $poname:ty) = $pmname:ident($($paname:ident: $paty:ty => $paid:expr,)*) -> $prty:ty => [$($pename:ident: $pety:ty => $peid:expr,)*],)*],
+     idempotent = [$($idempotent:ident,)*]) => {
         pub struct $client_name<P: $crate::Protocol, T: $crate::Transport> {
             pub protocol: P,
-            pub transport: T
+            pub transport: T,
+            pub retry_policy: Option<$crate::client::RetryPolicy>,
+            deadlines: $crate::client::Deadlines<T>,
+            interceptors: $crate::interceptor::Interceptors,
+            sequence_id: i32
         }
 
         impl<P: $crate::Protocol, T: $crate::Transport> $client_name<P, T> {
             pub fn new(protocol: P, transport: T) -> Self {
                 $client_name {
                     protocol: protocol,
-                    transport: transport
+                    transport: transport,
+                    retry_policy: None,
+                    deadlines: $crate::client::Deadlines::new(),
+                    interceptors: $crate::interceptor::Interceptors::new(),
+                    sequence_id: 0
+                }
+            }
+
+            /// Runs `interceptor` around every call made by this client.
+            pub fn add_interceptor<I: $crate::Interceptor + 'static>(&mut self, interceptor: I) {
+                self.interceptors.add(interceptor);
+            }
+
+            /// Whether the client can still make calls. A call that times out
+            /// halfway leaves the connection in an unknown state, so unless
+            /// the transport reconnects, every later call fails.
+            pub fn is_usable(&self) -> bool {
+                self.deadlines.is_usable()
+            }
+
+            /// Retries failed calls to idempotent methods according to `policy`.
+            pub fn set_retry_policy(&mut self, policy: $crate::client::RetryPolicy) {
+                self.retry_policy = Some(policy);
+            }
+
+            pub fn is_idempotent(method: &str) -> bool {
+                match method {
+                    $(stringify!($idempotent) => true,)*
+                    _ => false
                 }
             }
 
-            service_client_methods! { methods = [$($siname -> $soname = $smfname.$smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*] }
-            service_client_methods! { methods = [$($piname -> $poname = $pmfname.$pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*] }
+            service_client_methods! { methods = [$(($siname, $soname) = $smname($($saname: $saty => $said,)*) -> $srty => [$($sename: $sety => $seid,)*],)*] }
+            service_client_methods! { methods = [$(($piname, $poname) = $pmname($($paname: $paty => $paid,)*) -> $prty => [$($pename: $pety => $peid,)*],)*] }
+        }
+
+        impl<P: $crate::Protocol, T: $crate::Transport + $crate::transport::SetTimeout> $client_name<P, T> {
+            /// Bounds every call without an explicit deadline by `timeout`.
+            pub fn set_timeout(&mut self, timeout: Option<::std::time::Duration>) {
+                self.deadlines.set_timeout(timeout);
+            }
+
+            /// Bounds the next call by `deadline`, as in
+            /// `client.with_deadline(deadline).method(args)`.
+            pub fn with_deadline(&mut self, deadline: ::std::time::Instant) -> &mut Self {
+                self.deadlines.set_next_deadline(deadline);
+                self
+            }
         }
     }
 }
 
 #[macro_export]
 macro_rules! service_client_methods {
-    (methods = [$($iname:ident -> $oname:ident = $fname:ident.$mname:ident($($aname:ident: $aty:ty => $aid:expr,)*) -> $rty:ty => [$($ename:ident: $ety:ty => $eid:expr,)*],)*]) => {
+    (methods = [$(($iname:ty, $oname:ty) = $mname:ident($($aname:ident: $aty:ty => $aid:expr,)*) -> $rty:ty => [$($ename:ident: $ety:ty => $eid:expr,)*],)*]) => {
         $(pub fn $mname(&mut self, $($aname: $aty,)*) -> $crate::Result<$oname> {
             static MNAME: &'static str = stringify!($mname);
 
-            let mut args = $iname::default();
+            let mut args = <$iname>::default();
             $(args.$aname = Some($aname);)*
-            try!($crate::protocol::helpers::send(&mut self.protocol, &mut self.transport,
-                                                 MNAME, $crate::protocol::MessageType::Call, &mut args));
 
-            let mut result = $oname::default();
-            try!($crate::protocol::helpers::receive(&mut self.protocol, &mut self.transport,
-                                                    MNAME, &mut result));
+            self.sequence_id = self.sequence_id.wrapping_add(1);
+            let call = $crate::interceptor::Call { method: MNAME, sequence_id: self.sequence_id };
+
+            let policy = if Self::is_idempotent(MNAME) { self.retry_policy.as_ref() } else { None };
+            let (protocol, transport, deadlines) = (&mut self.protocol, &mut self.transport, &mut self.deadlines);
+            let deadline = deadlines.start();
 
-            Ok(result)
+            self.interceptors.intercept(&call, || {
+                $crate::client::retry::call(policy, || deadlines.run(&mut *transport, deadline, |transport| {
+                    try!($crate::protocol::helpers::send_message(&mut *protocol, transport,
+                                                                 MNAME, $crate::protocol::MessageType::Call,
+                                                                 call.sequence_id, &args));
+
+                    let mut result = <$oname>::default();
+                    try!($crate::protocol::helpers::receive(&mut *protocol, transport,
+                                                            MNAME, &mut result));
+
+                    Ok(result)
+                }))
+            })
         })*
     }
 }
@@ -140,12 +348,20 @@ macro_rules! service_client_methods {
 #[macro_export]
 macro_rules! strukt {
     (name = $name:ident,
-     fields = { $($fname:ident: $fty:ty => $id:expr,)+ }) => {
-        #[derive(Debug, Clone, Default)]
+     fields = { $($fname:ident: $fty:ty $(as $conv:path)* => $id:expr,)* },
+     unknown_fields = [$($uname:ident)*],
+     derive = [$($derive:ident,)*],
+     builder = [$($builder:ident)*],
+     accessors = [$($set:ident $take:ident $has:ident,)*],
+     required = [$($required:ident,)*]) => {
+        #[derive(Debug, Clone, Default, PartialEq, $($derive),*)]
         pub struct $name {
-            $(pub $fname: Option<$fty>,)+
+            $(pub $fname: Option<$fty>,)*
+            $(pub $uname: ::std::collections::BTreeMap<i16, $crate::value::Value>,)*
         }
 
+        strukt_builder!([$($builder)*], $name, { $($fname: $fty,)* }, [$($set $take $has,)*], [$($required,)*]);
+
         impl $crate::protocol::ThriftTyped for $name {
             fn typ() -> $crate::protocol::Type { $crate::protocol::Type::Struct }
         }
@@ -161,8 +377,17 @@ macro_rules! strukt {
                 try!(protocol.write_struct_begin(transport, stringify!($name)));
 
                 $(if let Some(ref x) = self.$fname {
-                    try!(protocol.write_field_begin(transport, stringify!($fname), <$fty as ThriftTyped>::typ(), $id));
-                    try!(x.encode(protocol, transport));
+                    try!(protocol.write_field_begin(transport, stringify!($fname), strukt_field_type!([$($conv)*], $fty), $id));
+                    strukt_field_encode!([$($conv)*], $fty, x, protocol, transport);
+                    try!(protocol.write_field_end(transport));
+                })*
+
+                // An id of a declared field would be written twice.
+                #[allow(unused_variables)]
+                let declared: &[i16] = &[$($id,)*];
+                $(for (&id, value) in self.$uname.iter().filter(|&(id, _)| !declared.contains(id)) {
+                    try!(protocol.write_field_begin(transport, "", value.value_type(), id));
+                    try!(value.encode(protocol, transport));
                     try!(protocol.write_field_end(transport));
                 })*
 
@@ -188,10 +413,16 @@ macro_rules! strukt {
 
                     if typ == $crate::protocol::Type::Stop {
                         break;
-                    } $(else if (typ, id) == (<$fty as ThriftTyped>::typ(), $id) {
-                        try!(self.$fname.decode(protocol, transport));
+                    } $(else if id == $id {
+                        // A declared field of another type is from an
+                        // incompatible version of the struct, and dropped.
+                        if typ == strukt_field_type!([$($conv)*], $fty) {
+                            strukt_field_decode!([$($conv)*], $fty, self, $fname, protocol, transport);
+                        } else {
+                            try!(protocol.skip(transport, typ));
+                        }
                     })* else {
-                        try!(protocol.skip(transport, typ));
+                        strukt_unknown_field!([$($uname)*], self, protocol, transport, typ, id);
                     }
 
                     try!(protocol.read_field_end(transport));
@@ -203,7 +434,7 @@ macro_rules! strukt {
             }
         }
     };
-    (name = $name:ident, fields = {}) => {
+    (name = $name:ident, fields = {} $(, derive = [$($derive:ident,)*])*) => {
         #[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
         pub struct $name;
 
@@ -243,24 +474,265 @@ macro_rules! strukt {
                 Ok(())
             }
         }
+    };
+    (name = $name:ident,
+     fields = { $($fname:ident: $fty:ty $(as $conv:path)* => $id:expr,)+ }
+     $(, derive = [$($derive:ident,)*])*
+     $(, builder = $builder:ident,
+       accessors = [$($set:ident $take:ident $has:ident,)*],
+       required = [$($required:ident,)*])*) => {
+        strukt! {
+            name = $name,
+            fields = { $($fname: $fty $(as $conv)* => $id,)+ },
+            unknown_fields = [],
+            derive = [$($($derive,)*)*],
+            builder = [$($builder)*],
+            accessors = [$($($set $take $has,)*)*],
+            required = [$($($required,)*)*]
+        }
+    };
+    (name = $name:ident,
+     fields = { $($fname:ident: $fty:ty $(as $conv:path)* => $id:expr,)* },
+     unknown_fields = $uname:ident
+     $(, derive = [$($derive:ident,)*])*
+     $(, builder = $builder:ident,
+       accessors = [$($set:ident $take:ident $has:ident,)*],
+       required = [$($required:ident,)*])*) => {
+        strukt! {
+            name = $name,
+            fields = { $($fname: $fty $(as $conv)* => $id,)* },
+            unknown_fields = [$uname],
+            derive = [$($($derive,)*)*],
+            builder = [$($builder)*],
+            accessors = [$($($set $take $has,)*)*],
+            required = [$($($required,)*)*]
+        }
+    }
+}
+
+// The builder of a struct and the accessors of its fields, when the struct
+// has a builder. Each field has the setter, taker and checker at its
+// position in the accessors.
+#[macro_export]
+macro_rules! strukt_builder {
+    ([], $name:ident, { $($fname:ident: $fty:ty,)* }, [$($set:ident $take:ident $has:ident,)*], [$($required:ident,)*]) => {};
+    ([$builder:ident], $name:ident, { $($fname:ident: $fty:ty,)* }, [$($set:ident $take:ident $has:ident,)*], [$($required:ident,)*]) => {
+        impl $name {
+            pub fn builder() -> $builder {
+                $builder { value: $name::default() }
+            }
+
+            $(pub fn $set(&mut self, value: $fty) {
+                self.$fname = Some(value);
+            }
+
+            pub fn $take(&mut self) -> Option<$fty> {
+                self.$fname.take()
+            }
+
+            pub fn $has(&self) -> bool {
+                self.$fname.is_some()
+            })*
+        }
+
+        #[derive(Debug, Clone, Default)]
+        pub struct $builder {
+            value: $name
+        }
+
+        impl $builder {
+            $(pub fn $fname(mut self, value: $fty) -> Self {
+                self.value.$fname = Some(value);
+                self
+            })*
+
+            /// Fails when a required field isn't set.
+            pub fn build(self) -> ::std::result::Result<$name, $crate::protocol::BuildError> {
+                $(if self.value.$required.is_none() {
+                    return Err($crate::protocol::BuildError::new(stringify!($name), stringify!($required)));
+                })*
+                Ok(self.value)
+            }
+        }
+    }
+}
+
+// The wire type of a field, which fields declared `name: Type as Converter`
+// take from the type the converter encodes them as.
+#[macro_export]
+macro_rules! strukt_field_type {
+    ([], $fty:ty) => {
+        <$fty as $crate::protocol::ThriftTyped>::typ()
+    };
+    ([$conv:path], $fty:ty) => {
+        <<$conv as $crate::protocol::Convert<$fty>>::Thrift as $crate::protocol::ThriftTyped>::typ()
+    }
+}
+
+#[macro_export]
+macro_rules! strukt_field_encode {
+    ([], $fty:ty, $x:ident, $protocol:ident, $transport:ident) => {
+        try!($x.encode($protocol, $transport));
+    };
+    ([$conv:path], $fty:ty, $x:ident, $protocol:ident, $transport:ident) => {
+        try!(<$conv as $crate::protocol::Convert<$fty>>::to_thrift($x).encode($protocol, $transport));
+    }
+}
+
+#[macro_export]
+macro_rules! strukt_field_decode {
+    ([], $fty:ty, $this:ident, $fname:ident, $protocol:ident, $transport:ident) => {
+        try!($this.$fname.decode($protocol, $transport));
+    };
+    ([$conv:path], $fty:ty, $this:ident, $fname:ident, $protocol:ident, $transport:ident) => {{
+        let mut x = <<$conv as $crate::protocol::Convert<$fty>>::Thrift as Default>::default();
+        try!(x.decode($protocol, $transport));
+        $this.$fname = Some(try!(<$conv as $crate::protocol::Convert<$fty>>::from_thrift(x)));
+    }}
+}
+
+// Skips a field the struct doesn't know, or keeps it if the struct has a
+// field for unknown fields.
+#[macro_export]
+macro_rules! strukt_unknown_field {
+    ([], $this:ident, $protocol:ident, $transport:ident, $typ:ident, $id:ident) => {{
+        let _ = $id;
+        try!($protocol.skip($transport, $typ));
+    }};
+    ([$uname:ident], $this:ident, $protocol:ident, $transport:ident, $typ:ident, $id:ident) => {{
+        let value = try!($crate::value::Value::read($protocol, $transport, $typ));
+        $this.$uname.insert($id, value);
+    }}
+}
+
+// The name of an enum value in the Thrift file, when it isn't that of the
+// variant.
+#[macro_export]
+macro_rules! enom_value_name {
+    ($vname:ident) => { stringify!($vname) };
+    ($vname:ident => $vstr:expr) => { $vstr }
+}
+
+// Compares enum values by their number, so that enums order the same whether
+// or not they keep unknown values.
+#[macro_export]
+macro_rules! enom_compare {
+    ($name:ident) => {
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                i32::from(*self) == i32::from(*other)
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl ::std::hash::Hash for $name {
+            fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                i32::from(*self).hash(state)
+            }
+        }
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &$name) -> Option<::std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &$name) -> ::std::cmp::Ordering {
+                i32::from(*self).cmp(&i32::from(*other))
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! newtype {
+    (name = $name:ident, inner = $inner:ty, derive = [$($derive:ident,)*]) => {
+        #[derive(Debug, Clone, Default, PartialEq, $($derive),*)]
+        pub struct $name(pub $inner);
+
+        impl From<$inner> for $name {
+            fn from(inner: $inner) -> Self { $name(inner) }
+        }
+
+        impl $crate::protocol::ThriftTyped for $name {
+            fn typ() -> $crate::protocol::Type { <$inner as $crate::protocol::ThriftTyped>::typ() }
+        }
+
+        impl $crate::protocol::Encode for $name {
+            fn encode<P, T>(&self, protocol: &mut P, transport: &mut T) -> $crate::Result<()>
+            where P: $crate::Protocol, T: $crate::Transport {
+                $crate::protocol::Encode::encode(&self.0, protocol, transport)
+            }
+        }
+
+        impl $crate::protocol::Decode for $name {
+            fn decode<P, T>(&mut self, protocol: &mut P, transport: &mut T) -> $crate::Result<()>
+            where P: $crate::Protocol, T: $crate::Transport {
+                $crate::protocol::Decode::decode(&mut self.0, protocol, transport)
+            }
+        }
+    };
+    (name = $name:ident, inner = $inner:ty) => {
+        newtype! { name = $name, inner = $inner, derive = [] }
     }
 }
 
 #[macro_export]
 macro_rules! enom {
     (name = $name:ident,
-     values = [$($vname:ident = $val:expr,)*],
+     values = [$($vname:ident = $val:expr $(=> $vstr:expr)*,)*],
      default = $dname:ident) => {
-        #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+        #[derive(Copy, Clone, Debug)]
         #[repr(i32)]
         pub enum $name {
             $($vname = $val),*
         }
 
+        impl $name {
+            /// All the values, in the order of the Thrift file.
+            pub const VALUES: &'static [$name] = &[$($name::$vname),*];
+
+            /// The name of the value in the Thrift file.
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $($name::$vname => enom_value_name!($vname $(=> $vstr)*)),*
+                }
+            }
+
+            pub fn to_i32(&self) -> i32 {
+                *self as i32
+            }
+        }
+
         impl Default for $name {
             fn default() -> Self { $name::$dname }
         }
 
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = $crate::protocol::ParseEnumError;
+
+            fn from_str(name: &str) -> ::std::result::Result<Self, Self::Err> {
+                $(if name == enom_value_name!($vname $(=> $vstr)*) {
+                    return Ok($name::$vname);
+                })*
+                Err($crate::protocol::ParseEnumError::new(stringify!($name), name))
+            }
+        }
+
+        impl From<$name> for i32 {
+            fn from(value: $name) -> i32 { value as i32 }
+        }
+
+        enom_compare!($name);
+
         impl $crate::protocol::FromNum for $name {
             fn from_num(num: i32) -> Option<Self> {
                 match num {
@@ -291,6 +763,112 @@ macro_rules! enom {
                 Ok(())
             }
         }
+    };
+    // Values this side doesn't know are decoded as `$uname(value)` rather
+    // than failing, and encoded back as they came.
+    (name = $name:ident,
+     values = [$($vname:ident = $val:expr $(=> $vstr:expr)*,)*],
+     default = $dname:ident,
+     unknown = $uname:ident) => {
+        #[derive(Copy, Clone, Debug)]
+        pub enum $name {
+            $($vname,)*
+            $uname(i32)
+        }
+
+        impl $name {
+            /// All the known values, in the order of the Thrift file.
+            pub const VALUES: &'static [$name] = &[$($name::$vname),*];
+
+            /// The name of the value in the Thrift file, or the name of the
+            /// variant holding unknown values.
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $($name::$vname => enom_value_name!($vname $(=> $vstr)*),)*
+                    $name::$uname(_) => stringify!($uname)
+                }
+            }
+
+            pub fn to_i32(&self) -> i32 {
+                i32::from(*self)
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self { $name::$dname }
+        }
+
+        // Unknown values are written as their number, which parses back.
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                match *self {
+                    $name::$uname(num) => write!(f, "{}", num),
+                    _ => f.write_str(self.name()),
+                }
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = $crate::protocol::ParseEnumError;
+
+            fn from_str(name: &str) -> ::std::result::Result<Self, Self::Err> {
+                $(if name == enom_value_name!($vname $(=> $vstr)*) {
+                    return Ok($name::$vname);
+                })*
+                match name.parse::<i32>() {
+                    Ok(num) => Ok(<$name as $crate::protocol::FromNum>::from_num(num).unwrap_or($name::$uname(num))),
+                    Err(_) => Err($crate::protocol::ParseEnumError::new(stringify!($name), name)),
+                }
+            }
+        }
+
+        impl From<$name> for i32 {
+            fn from(value: $name) -> i32 {
+                match value {
+                    $($name::$vname => $val,)*
+                    $name::$uname(num) => num
+                }
+            }
+        }
+
+        enom_compare!($name);
+
+        // Only the known values, so that the enum also works with
+        // `helpers::read_enum` when unknown values should be rejected.
+        impl $crate::protocol::FromNum for $name {
+            fn from_num(num: i32) -> Option<Self> {
+                match num {
+                    $($val => Some($name::$vname)),*,
+                    _ => None
+                }
+            }
+        }
+
+        impl $crate::protocol::ThriftTyped for $name {
+            fn typ() -> $crate::protocol::Type { $crate::protocol::Type::I32 }
+        }
+
+        impl $crate::protocol::Encode for $name {
+            fn encode<P, T>(&self, protocol: &mut P, transport: &mut T) -> $crate::Result<()>
+            where P: $crate::Protocol, T: $crate::Transport {
+                #[allow(unused_imports)]
+                use $crate::Protocol;
+
+                protocol.write_i32(transport, i32::from(*self))
+            }
+        }
+
+        impl $crate::protocol::Decode for $name {
+            fn decode<P, T>(&mut self, protocol: &mut P, transport: &mut T) -> $crate::Result<()>
+            where P: $crate::Protocol, T: $crate::Transport {
+                #[allow(unused_imports)]
+                use $crate::Protocol;
+
+                let num = try!(protocol.read_i32(transport));
+                *self = <$name as $crate::protocol::FromNum>::from_num(num).unwrap_or($name::$uname(num));
+                Ok(())
+            }
+        }
     }
 }
 
@@ -32,31 +32,32 @@ enom! {
 
 service! {
     trait_name = SharedService,
+    context_trait_name = SharedServiceWithContext,
     processor_name = SharedServiceProcessor,
     client_name = SharedServiceClient,
+    parents = [],
     service_methods = [
-        SharedServiceGetStructArgs -> SharedServiceGetStructResult = shared.get_struct(key: i32 => 1,) -> DeeplyNested => [],
+        SharedServiceGetStructArgs -> SharedServiceGetStructResult = get_struct(key: i32 => 1,) -> DeeplyNested => [],
     ],
     parent_methods = [],
-    bounds = [S: SharedService,],
-    fields = [shared: S,]
+    idempotent = [get_struct,]
 }
 
 service! {
      trait_name = ChildService,
      processor_name = ChildServiceProcessor,
      client_name = ChildServiceClient,
+     parents = [SharedService,],
      service_methods = [
-         ChildServiceOperationArgs -> ChildServiceOperationResult = child.operation(
+         ChildServiceOperationArgs -> ChildServiceOperationResult = operation(
              one: String => 2,
              another: i32 => 3,
          ) -> Operation => [],
      ],
      parent_methods = [
-        SharedServiceGetStructArgs -> SharedServiceGetStructResult = shared.get_struct(key: i32 => 1,) -> DeeplyNested => [],
+        (SharedServiceGetStructArgs, SharedServiceGetStructResult) = get_struct(key: i32 => 1,) -> DeeplyNested => [],
      ],
-     bounds = [S: SharedService, C: ChildService,],
-     fields = [shared: S, child: C,]
+     idempotent = [get_struct,]
 }
 
 strukt! {
@@ -71,11 +72,10 @@ service! {
     trait_name = ServiceWithException,
     processor_name = ServiceWithExceptionProcessor,
     client_name = ServiceWithExceptionClient,
+    parents = [],
     service_methods = [
-        ServiceWithExceptionOperationArgs -> ServiceWithExceptionOperationResult = this.operation() -> i32 => [bad: Exception => 1,],
+        ServiceWithExceptionOperationArgs -> ServiceWithExceptionOperationResult = operation() -> i32 => [bad: Exception => 1,],
     ],
-    parent_methods = [],
-    bounds = [S: ServiceWithException,],
-    fields = [this: S,]
+    parent_methods = []
 }
 
@@ -0,0 +1,68 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// What is known about the other end of a connection, as reported by the
+/// `TransportServer` that accepted it.
+///
+/// Handlers implementing the `WithContext` variant of a service trait receive
+/// it with every call, for instance for audit logging or authorization.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionContext {
+    /// The address of the peer, if the transport has one.
+    pub peer_addr: Option<PeerAddr>,
+
+    /// The credentials of the peer process, for local transports that
+    /// provide them.
+    pub credentials: Option<Credentials>,
+
+    /// The DER-encoded certificate chain the peer presented over TLS, leaf
+    /// first.
+    pub certificates: Option<Vec<Vec<u8>>>,
+
+    /// The host name the peer asked for with TLS server name indication.
+    pub server_name: Option<String>,
+}
+
+impl ConnectionContext {
+    /// A context for a connection about which nothing is known.
+    pub fn new() -> ConnectionContext {
+        ConnectionContext::default()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PeerAddr {
+    Tcp(SocketAddr),
+
+    /// A Unix domain socket peer, with its path if it is bound to one.
+    Unix(Option<PathBuf>),
+}
+
+/// The identity of the process on the other end of a Unix domain socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    pub uid: u32,
+    pub gid: u32,
+
+    /// Not every platform reports the pid of the peer.
+    pub pid: Option<i32>,
+}
@@ -0,0 +1,122 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+//! The exception a server replies with when a call fails before or outside
+//! the handler, as `TApplicationException` in the other Thrift libraries.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use protocol::{Decode, Encode, ThriftTyped, Type};
+use {Protocol, Result, Transport};
+
+/// What went wrong, with the codes of the other Thrift libraries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExceptionKind {
+    Unknown = 0,
+    UnknownMethod = 1,
+    InvalidMessageType = 2,
+    WrongMethodName = 3,
+    BadSequenceId = 4,
+    MissingResult = 5,
+    InternalError = 6,
+    ProtocolError = 7,
+}
+
+impl ExceptionKind {
+    /// The kind with code `num`, codes not known here being `Unknown`.
+    pub fn from_i32(num: i32) -> ExceptionKind {
+        match num {
+            1 => ExceptionKind::UnknownMethod,
+            2 => ExceptionKind::InvalidMessageType,
+            3 => ExceptionKind::WrongMethodName,
+            4 => ExceptionKind::BadSequenceId,
+            5 => ExceptionKind::MissingResult,
+            6 => ExceptionKind::InternalError,
+            7 => ExceptionKind::ProtocolError,
+            _ => ExceptionKind::Unknown,
+        }
+    }
+}
+
+impl Default for ExceptionKind {
+    fn default() -> ExceptionKind {
+        ExceptionKind::Unknown
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ApplicationException {
+    pub message: String,
+    pub kind: ExceptionKind,
+}
+
+impl ApplicationException {
+    pub fn new(kind: ExceptionKind, message: &str) -> ApplicationException {
+        ApplicationException { message: String::from(message), kind: kind }
+    }
+}
+
+impl StdError for ApplicationException {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ApplicationException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)
+    }
+}
+
+impl ThriftTyped for ApplicationException {
+    fn typ() -> Type { Type::Struct }
+}
+
+impl Encode for ApplicationException {
+    fn encode<P, T>(&self, protocol: &mut P, transport: &mut T) -> Result<()>
+    where P: Protocol, T: Transport {
+        try!(protocol.write_struct_begin(transport, "TApplicationException"));
+        try!(protocol.write_field_begin(transport, "message", Type::String, 1));
+        try!(protocol.write_str(transport, &self.message));
+        try!(protocol.write_field_end(transport));
+        try!(protocol.write_field_begin(transport, "type", Type::I32, 2));
+        try!(protocol.write_i32(transport, self.kind as i32));
+        try!(protocol.write_field_end(transport));
+        try!(protocol.write_field_stop(transport));
+        protocol.write_struct_end(transport)
+    }
+}
+
+impl Decode for ApplicationException {
+    fn decode<P, T>(&mut self, protocol: &mut P, transport: &mut T) -> Result<()>
+    where P: Protocol, T: Transport {
+        try!(protocol.read_struct_begin(transport));
+        loop {
+            match try!(protocol.read_field_begin(transport)) {
+                (_, Type::Stop, _) => break,
+                (_, Type::String, 1) => self.message = try!(protocol.read_string(transport)),
+                (_, Type::I32, 2) => self.kind = ExceptionKind::from_i32(try!(protocol.read_i32(transport))),
+                (_, typ, _) => try!(protocol.skip(transport, typ)),
+            }
+            try!(protocol.read_field_end(transport));
+        }
+        protocol.read_struct_end(transport)
+    }
+}
@@ -4,6 +4,12 @@ pub use {Protocol, Transport, Result, Error};
 pub use std::collections::{HashSet, HashMap};
 pub use std::hash::Hash;
 
+use std::cmp;
+
+// Containers preallocate no more than this many elements: the size comes from
+// the wire, and the elements themselves may never arrive.
+const RESERVE_LIMIT: usize = 1024;
+
 impl ThriftTyped for bool { fn typ() -> Type { Type::Bool } }
 impl ThriftTyped for i8  { fn typ() -> Type { Type::Byte } }
 impl ThriftTyped for i16 { fn typ() -> Type { Type::I16 } }
@@ -13,6 +19,8 @@ impl ThriftTyped for f64 { fn typ() -> Type { Type::Double } }
 impl ThriftTyped for () { fn typ() -> Type { Type::Void } }
 impl ThriftTyped for String { fn typ() -> Type { Type::String } }
 impl<T: ThriftTyped> ThriftTyped for Vec<T> { fn typ() -> Type { Type::List } }
+// Thrift `binary`, which bytes aren't a type of their own to make lists of.
+impl ThriftTyped for Vec<u8> { fn typ() -> Type { Type::String } }
 impl<T: ThriftTyped> ThriftTyped for Option<T> { fn typ() -> Type { T::typ() } }
 impl<T: ThriftTyped> ThriftTyped for HashSet<T> { fn typ() -> Type { Type::Set } }
 impl<K: ThriftTyped, V: ThriftTyped> ThriftTyped for HashMap<K, V> { fn typ() -> Type { Type::Map } }
@@ -78,6 +86,13 @@ impl Encode for String {
     }
 }
 
+impl Encode for Vec<u8> {
+    fn encode<P, T>(&self, protocol: &mut P, transport: &mut T) -> Result<()>
+    where P: Protocol, T: Transport {
+        protocol.write_binary(transport, self)
+    }
+}
+
 impl Encode for () {
     fn encode<P, T>(&self, _: &mut P, _: &mut T) -> Result<()>
     where P: Protocol, T: Transport { Ok(()) }
@@ -100,6 +115,14 @@ prim_encode! {
     i32 => write_i32, i64 => write_i64, f64 => write_double
 }
 
+fn reserve_size(len: i32) -> Result<usize> {
+    if len < 0 {
+        Err(Error::from(protocol::Error::NegativeSize))
+    } else {
+        Ok(cmp::min(len as usize, RESERVE_LIMIT))
+    }
+}
+
 fn decode<D, P, T>(protocol: &mut P, transport: &mut T) -> Result<D>
 where D: Decode, P: Protocol, T: Transport {
      let mut elem = D::default();
@@ -113,7 +136,7 @@ impl<X: Decode> Decode for Vec<X> {
         let (typ, len) = try!(protocol.read_list_begin(transport));
 
         if typ == X::typ() {
-            self.reserve(len as usize);
+            self.reserve(try!(reserve_size(len)));
             for _ in 0..len { self.push(try!(decode(protocol, transport))); }
             try!(protocol.read_list_end(transport));
             Ok(())
@@ -129,7 +152,7 @@ impl<X: Decode + Eq + Hash> Decode for HashSet<X> {
         let (typ, len) = try!(protocol.read_set_begin(transport));
 
         if typ == X::typ() {
-            self.reserve(len as usize);
+            self.reserve(try!(reserve_size(len)));
             for _ in 0..len { self.insert(try!(decode(protocol, transport))); }
             try!(protocol.read_set_end(transport));
             Ok(())
@@ -145,7 +168,7 @@ impl<K: Decode + Eq + Hash, V: Decode> Decode for HashMap<K, V> {
         let (ktyp, vtyp, len) = try!(protocol.read_map_begin(transport));
 
         if ktyp == K::typ() && vtyp == V::typ() {
-            self.reserve(len as usize);
+            self.reserve(try!(reserve_size(len)));
             for _ in 0..len {
                 let key = try!(decode(protocol, transport));
                 let value = try!(decode(protocol, transport));
@@ -190,6 +213,6 @@ macro_rules! prim_decode {
 prim_decode! {
     bool => read_bool, i8 => read_byte, i16 => read_i16,
     i32 => read_i32, i64 => read_i64, f64 => read_double,
-    String => read_string
+    String => read_string, Vec<u8> => read_binary
 }
 
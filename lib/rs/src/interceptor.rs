@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use {Error, Result};
+
+/// A method call seen by an interceptor.
+#[derive(Copy, Clone, Debug)]
+pub struct Call<'a> {
+    pub method: &'a str,
+    pub sequence_id: i32,
+}
+
+/// How a call ended: `Ok` when a reply was sent or received, which includes
+/// replies carrying a user-defined exception.
+pub type Outcome<'a> = ::std::result::Result<(), &'a Error>;
+
+/// A hook around calls, installed on generated clients and processors with
+/// `add_interceptor`.
+///
+/// On a client the hooks run around the whole call, retries included. On a
+/// processor they run after the arguments have been decoded, around the
+/// handler and the reply.
+pub trait Interceptor: Send + Sync {
+    /// Runs before the call. Returning an error aborts the call, and the
+    /// error is returned in its place. On a processor the client is sent an
+    /// `ApplicationException` with the error instead of the reply.
+    fn before(&self, _call: &Call) -> Result<()> { Ok(()) }
+
+    /// Runs after the call, or after a later interceptor aborted it.
+    fn after(&self, _call: &Call, _outcome: Outcome, _elapsed: Duration) { }
+}
+
+impl<I: Interceptor + ?Sized> Interceptor for Arc<I> {
+    fn before(&self, call: &Call) -> Result<()> {
+        (**self).before(call)
+    }
+
+    fn after(&self, call: &Call, outcome: Outcome, elapsed: Duration) {
+        (**self).after(call, outcome, elapsed)
+    }
+}
+
+/// A chain of interceptors. `before` hooks run in the order the interceptors
+/// were added and `after` hooks in the reverse order.
+#[derive(Default)]
+pub struct Interceptors {
+    chain: Vec<Box<Interceptor>>,
+}
+
+impl Interceptors {
+    pub fn new() -> Interceptors {
+        Interceptors { chain: Vec::new() }
+    }
+
+    pub fn add<I: Interceptor + 'static>(&mut self, interceptor: I) {
+        self.chain.push(Box::new(interceptor));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chain.is_empty()
+    }
+
+    /// Runs `f` inside the chain.
+    pub fn intercept<R, F>(&self, call: &Call, f: F) -> Result<R>
+    where F: FnOnce() -> Result<R> {
+        if self.chain.is_empty() {
+            return f();
+        }
+
+        let start = Instant::now();
+
+        for (i, interceptor) in self.chain.iter().enumerate() {
+            if let Err(err) = interceptor.before(call) {
+                self.after(&self.chain[..i], call, Err(&err), start);
+                return Err(err);
+            }
+        }
+
+        let result = f();
+        match result {
+            Ok(_) => self.after(&self.chain, call, Ok(()), start),
+            Err(ref err) => self.after(&self.chain, call, Err(err), start),
+        }
+        result
+    }
+
+    fn after(&self, chain: &[Box<Interceptor>], call: &Call, outcome: Outcome, start: Instant) {
+        let elapsed = start.elapsed();
+        for interceptor in chain.iter().rev() {
+            interceptor.after(call, outcome, elapsed);
+        }
+    }
+}
@@ -1,5 +1,11 @@
 #![feature(optin_builtin_traits)]
 extern crate podio;
+extern crate bufstream;
+extern crate libc;
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(all(test, feature = "tls"))]
+extern crate rcgen;
 
 #[macro_use]
 extern crate log;
@@ -10,11 +16,18 @@ use std::error::Error as StdError;
 pub use protocol::Protocol;
 pub use transport::Transport;
 pub use processor::Processor;
+pub use interceptor::Interceptor;
+pub use context::ConnectionContext;
 
 pub mod protocol;
 pub mod transport;
 pub mod server;
+pub mod client;
 pub mod processor;
+pub mod interceptor;
+pub mod context;
+pub mod exception;
+pub mod value;
 
 #[macro_use]
 mod codegen;
@@ -37,6 +50,12 @@ pub enum Error {
 
     /// The server code threw a user-defined exception
     UserException,
+
+    /// The call did not complete before its deadline
+    Timeout,
+
+    /// The server failed the call before or outside the handler
+    ApplicationError(exception::ApplicationException),
 }
 
 impl From<protocol::Error> for Error {
@@ -60,6 +79,7 @@ impl StdError for Error {
         match *self {
             Error::TransportError(ref err) => Some(err),
             Error::ProtocolError(ref err) => Some(err),
+            Error::ApplicationError(ref err) => Some(err),
             _ => None
         }
     }
@@ -1,8 +1,16 @@
 use protocol::Protocol;
 use transport::Transport;
-use Result;
+use {ConnectionContext, Result};
 
 pub trait Processor<P: Protocol, T: Transport> {
     fn process(&self, prot: &mut P, transport: &mut T) -> Result<()>;
-}
 
+    /// Processes a message received on the connection described by `context`.
+    ///
+    /// Processors that don't use the context can rely on the default, which
+    /// ignores it.
+    fn process_in_context(&self, prot: &mut P, transport: &mut T,
+                          _context: &ConnectionContext) -> Result<()> {
+        self.process(prot, transport)
+    }
+}
@@ -25,23 +25,131 @@ use podio::{ReadPodExt, WritePodExt, BigEndian};
 
 static BINARY_PROTOCOL_VERSION_1: u16 = 0x8001;
 
+/// The default `string_limit`, in bytes.
+pub const DEFAULT_STRING_LIMIT: usize = 16 * 1024 * 1024;
+
+/// The default `container_limit`, in elements.
+pub const DEFAULT_CONTAINER_LIMIT: usize = 1024 * 1024;
+
+/// The default `depth_limit`.
+pub const DEFAULT_DEPTH_LIMIT: usize = 64;
+
+/// The binary protocol.
+///
+/// Like the C++ `TBinaryProtocol`, messages are written with a versioned
+/// header when `strict_write` is set, and messages without one are refused
+/// when `strict_read` is set. Old peers may use the unversioned header, which
+/// starts with the length of the method name.
+///
+/// Sizes read from the wire are checked before anything is allocated:
+/// strings and binaries longer than `string_limit` bytes, and containers with
+/// more than `container_limit` elements, are refused with `SizeLimit`.
+/// Structs and containers nested more than `depth_limit` levels deep are
+/// refused with `DepthLimit`, before decoding or skipping them could exhaust
+/// the stack. The depth starts over with each message, and after any read
+/// that fails.
 #[derive(Copy, Clone, Debug)]
-pub struct BinaryProtocol;
+pub struct BinaryProtocol {
+    strict_read: bool,
+    strict_write: bool,
+    string_limit: usize,
+    container_limit: usize,
+    depth_limit: usize,
+    depth: usize,
+}
 
 impl BinaryProtocol {
+    /// A protocol writing strict headers and reading both kinds, the C++
+    /// defaults, with the default size limits.
+    pub fn new() -> BinaryProtocol {
+        BinaryProtocol {
+            strict_read: false,
+            strict_write: true,
+            string_limit: DEFAULT_STRING_LIMIT,
+            container_limit: DEFAULT_CONTAINER_LIMIT,
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+            depth: 0,
+        }
+    }
+
+    /// Refuses messages without a versioned header.
+    pub fn strict_read(mut self, strict: bool) -> BinaryProtocol {
+        self.strict_read = strict;
+        self
+    }
+
+    /// Writes messages with a versioned header.
+    pub fn strict_write(mut self, strict: bool) -> BinaryProtocol {
+        self.strict_write = strict;
+        self
+    }
+
+    /// The longest string or binary read, in bytes.
+    pub fn string_limit(mut self, limit: usize) -> BinaryProtocol {
+        self.string_limit = limit;
+        self
+    }
+
+    /// The most elements read in a container.
+    pub fn container_limit(mut self, limit: usize) -> BinaryProtocol {
+        self.container_limit = limit;
+        self
+    }
+
+    /// The most levels of structs and containers read nested.
+    pub fn depth_limit(mut self, limit: usize) -> BinaryProtocol {
+        self.depth_limit = limit;
+        self
+    }
+
     fn write_type<T: Transport>(&mut self, transport: &mut T, type_: Type) -> Result<()> {
         self.write_byte(transport, type_ as i8)
     }
 
+    fn read_bytes<T: Transport>(&mut self, transport: &mut T, len: usize) -> Result<Vec<u8>> {
+        ReadPodExt::read_exact(transport, len).map_err(|err| self.failed(err))
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.depth_limit {
+            return Err(self.failed(protocol::Error::DepthLimit));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    // Forgets the depth when a read fails: the value being read is then
+    // abandoned, and the next read starts another.
+    fn failed<E>(&mut self, err: E) -> Error where Error: From<E> {
+        self.depth = 0;
+        Error::from(err)
+    }
+
+    fn read_size<T: Transport>(&mut self, transport: &mut T, limit: usize) -> Result<i32> {
+        let size = try!(self.read_i32(transport));
+        try!(check_size(size, limit).map_err(|err| self.failed(err)));
+        Ok(size)
+    }
+
     fn read_type<T: Transport>(&mut self, transport: &mut T) -> Result<Type> {
         let raw = try!(self.read_byte(transport));
         match Type::from_num(raw as u64) {
             Some(type_) => Ok(type_),
-            None => Err(Error::from(protocol::Error::ProtocolViolation)),
+            None => Err(self.failed(protocol::Error::ProtocolViolation)),
         }
     }
 }
 
+impl Default for BinaryProtocol {
+    fn default() -> BinaryProtocol {
+        BinaryProtocol::new()
+    }
+}
+
 impl Protocol for BinaryProtocol {
     fn write_message_begin<T: Transport>(
         &mut self,
@@ -50,9 +158,14 @@ impl Protocol for BinaryProtocol {
         message_type: MessageType,
         sequence_id: i32
     ) -> Result<()> {
-        let version = ((BINARY_PROTOCOL_VERSION_1 as i32) << 16) | message_type as i32;
-        try!(self.write_i32(transport, version));
-        try!(self.write_str(transport, name));
+        if self.strict_write {
+            let version = ((BINARY_PROTOCOL_VERSION_1 as i32) << 16) | message_type as i32;
+            try!(self.write_i32(transport, version));
+            try!(self.write_str(transport, name));
+        } else {
+            try!(self.write_str(transport, name));
+            try!(self.write_byte(transport, message_type as i8));
+        }
         self.write_i32(transport, sequence_id)
     }
 
@@ -159,13 +272,24 @@ impl Protocol for BinaryProtocol {
     }
 
     fn read_message_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(String, MessageType, i32)> {
+        // A previous message may have been abandoned half way through.
+        self.depth = 0;
         let header = try!(self.read_i32(transport));
-        let version = (header >> 16) as u16;
-        if version != BINARY_PROTOCOL_VERSION_1 {
+        let (name, raw_type) = if header < 0 {
+            let version = (header >> 16) as u16;
+            if version != BINARY_PROTOCOL_VERSION_1 {
+                return Err(Error::from(protocol::Error::BadVersion));
+            };
+            (try!(self.read_string(transport)), header & 0xff)
+        } else if self.strict_read {
             return Err(Error::from(protocol::Error::BadVersion));
+        } else {
+            // The unversioned header: the header was the length of the name.
+            try!(check_size(header, self.string_limit));
+            let name = try!(self.read_bytes(transport, header as usize));
+            let name = try!(String::from_utf8(name).map_err(|e| protocol::Error::from(e.utf8_error())));
+            (name, try!(self.read_byte(transport)) as i32)
         };
-        let name = try!(self.read_string(transport));
-        let raw_type = header & 0xff;
         let message_type = match MessageType::from_num(raw_type as u64) {
             Some(t) => t,
             None => return Err(Error::from(protocol::Error::ProtocolViolation)),
@@ -179,10 +303,12 @@ impl Protocol for BinaryProtocol {
     }
 
     fn read_struct_begin<T: Transport>(&mut self, _transport: &mut T) -> Result<String> {
+        try!(self.enter());
         Ok(String::new())
     }
 
     fn read_struct_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        self.leave();
         Ok(())
     }
 
@@ -200,33 +326,42 @@ impl Protocol for BinaryProtocol {
     }
 
     fn read_map_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, Type, i32)> {
+        try!(self.enter());
         let key_type = try!(self.read_type(transport));
         let value_type = try!(self.read_type(transport));
-        let size = try!(self.read_i32(transport));
+        let limit = self.container_limit;
+        let size = try!(self.read_size(transport, limit));
         Ok((key_type, value_type, size))
     }
 
     fn read_map_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        self.leave();
         Ok(())
     }
 
     fn read_list_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, i32)> {
+        try!(self.enter());
         let elem_type = try!(self.read_type(transport));
-        let size = try!(self.read_i32(transport));
+        let limit = self.container_limit;
+        let size = try!(self.read_size(transport, limit));
         Ok((elem_type, size))
     }
 
     fn read_list_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        self.leave();
         Ok(())
     }
 
     fn read_set_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, i32)> {
+        try!(self.enter());
         let elem_type = try!(self.read_type(transport));
-        let size = try!(self.read_i32(transport));
+        let limit = self.container_limit;
+        let size = try!(self.read_size(transport, limit));
         Ok((elem_type, size))
     }
 
     fn read_set_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        self.leave();
         Ok(())
     }
 
@@ -238,33 +373,34 @@ impl Protocol for BinaryProtocol {
     }
 
     fn read_byte<T: Transport>(&mut self, transport: &mut T) -> Result<i8> {
-        Ok(try!(transport.read_i8()))
+        transport.read_i8().map_err(|err| self.failed(err))
     }
 
     fn read_i16<T: Transport>(&mut self, transport: &mut T) -> Result<i16> {
-        Ok(try!(transport.read_i16::<BigEndian>()))
+        transport.read_i16::<BigEndian>().map_err(|err| self.failed(err))
     }
 
     fn read_i32<T: Transport>(&mut self, transport: &mut T) -> Result<i32> {
-        Ok(try!(transport.read_i32::<BigEndian>()))
+        transport.read_i32::<BigEndian>().map_err(|err| self.failed(err))
     }
 
     fn read_i64<T: Transport>(&mut self, transport: &mut T) -> Result<i64> {
-        Ok(try!(transport.read_i64::<BigEndian>()))
+        transport.read_i64::<BigEndian>().map_err(|err| self.failed(err))
     }
 
     fn read_double<T: Transport>(&mut self, transport: &mut T) -> Result<f64> {
-        Ok(try!(transport.read_f64::<BigEndian>()))
+        transport.read_f64::<BigEndian>().map_err(|err| self.failed(err))
     }
 
     fn read_string<T: Transport>(&mut self, transport: &mut T) -> Result<String> {
         let bytes = try!(self.read_binary(transport));
-        Ok(try!(String::from_utf8(bytes).map_err(|e| protocol::Error::from(e.utf8_error()))))
+        String::from_utf8(bytes).map_err(|e| self.failed(protocol::Error::from(e.utf8_error())))
     }
 
     fn read_binary<T: Transport>(&mut self, transport: &mut T) -> Result<Vec<u8>> {
-        let len = try!(self.read_i32(transport)) as usize;
-        Ok(try!(transport.read_exact(len)))
+        let limit = self.string_limit;
+        let len = try!(self.read_size(transport, limit));
+        self.read_bytes(transport, len as usize)
     }
 
     fn skip<T: Transport>(&mut self, transport: &mut T, type_: Type) -> Result<()> {
@@ -318,5 +454,15 @@ impl Protocol for BinaryProtocol {
     }
 }
 
+fn check_size(size: i32, limit: usize) -> Result<()> {
+    if size < 0 {
+        Err(Error::from(protocol::Error::NegativeSize))
+    } else if size as usize > limit {
+        Err(Error::from(protocol::Error::SizeLimit))
+    } else {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 pub mod test;
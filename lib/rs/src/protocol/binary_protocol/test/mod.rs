@@ -19,14 +19,15 @@
 
 use super::BinaryProtocol;
 
+use compiletest::DeeplyNested;
 use mock::MockTransport;
-use protocol::{self, Protocol};
+use protocol::{self, Decode, Encode, Protocol};
 use Error;
 
 #[test]
 fn read_bool() {
     let transport = &mut MockTransport::new(vec!(0x00, 0x01, 0xff));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::default();
     assert_eq!(protocol.read_bool(transport).unwrap(), false);
     assert_eq!(protocol.read_bool(transport).unwrap(), true);
     assert_eq!(protocol.read_bool(transport).unwrap(), true);
@@ -35,7 +36,7 @@ fn read_bool() {
 #[test]
 fn read_byte() {
     let transport = &mut MockTransport::new(vec!(0xa4, 0x27));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(protocol.read_byte(transport).unwrap(), -0x5c);
     assert_eq!(protocol.read_byte(transport).unwrap(), 0x27);
 }
@@ -43,7 +44,7 @@ fn read_byte() {
 #[test]
 fn read_i16() {
     let transport = &mut MockTransport::new(vec!(0xf2, 0xf8, 0xa1, 0x40));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(protocol.read_i16(transport).unwrap(), -0x0d08);
     assert_eq!(protocol.read_i16(transport).unwrap(), -0x5ec0);
 }
@@ -51,7 +52,7 @@ fn read_i16() {
 #[test]
 fn read_i32() {
     let transport = &mut MockTransport::new(vec!(0x27, 0xd0, 0x39, 0x49, 0xe5, 0xd8, 0xfe, 0x8b));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(protocol.read_i32(transport).unwrap(), 0x27d03949);
     assert_eq!(protocol.read_i32(transport).unwrap(), -0x1a270175);
 }
@@ -62,7 +63,7 @@ fn read_i64() {
         0x27, 0xd0, 0x39, 0x49, 0xe5, 0xd8, 0xfe, 0x8b,
         0xa7, 0x2e, 0x82, 0xea, 0xd1, 0x28, 0x0b, 0xe2,
     ));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(protocol.read_i64(transport).unwrap(), 0x27d03949e5d8fe8b);
     assert_eq!(protocol.read_i64(transport).unwrap(), -0x58d17d152ed7f41e);
 }
@@ -73,7 +74,7 @@ fn read_double() {
         0x40, 0xa9, 0x5e, 0xaf, 0x39, 0x4b, 0x7b, 0x29,
         0xbf, 0xe9, 0x3a, 0xe4, 0x21, 0xd3, 0x0e, 0x85,
     ));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(protocol.read_double(transport).unwrap(), 3247.342234);
     assert_eq!(protocol.read_double(transport).unwrap(), -0.78843886);
 }
@@ -85,7 +86,7 @@ fn read_string() {
         0x00, 0x00, 0x00, 0x04, 0x41, 0x73, 0x64, 0x66,
         0x00, 0x00, 0x00, 0x0d, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x57, 0x6f, 0x72, 0x6c, 0x64, 0x21,
     ));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(&protocol.read_string(transport).unwrap(), "");
     assert_eq!(&protocol.read_string(transport).unwrap(), "Asdf");
     assert_eq!(&protocol.read_string(transport).unwrap(), "Hello, World!");
@@ -98,16 +99,38 @@ fn read_binary() {
         0x00, 0x00, 0x00, 0x04, 0x41, 0x73, 0x64, 0x66,
         0x00, 0x00, 0x00, 0x0d, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x57, 0x6f, 0x72, 0x6c, 0x64, 0x21,
     ));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(&protocol.read_binary(transport).unwrap(), &[]);
     assert_eq!(&protocol.read_binary(transport).unwrap(), &[0x41, 0x73, 0x64, 0x66]);
     assert_eq!(&protocol.read_binary(transport).unwrap(), &[0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x57, 0x6f, 0x72, 0x6c, 0x64, 0x21]);
 }
 
+#[test]
+fn read_binary_negative_size() {
+    let transport = &mut MockTransport::new(vec!(0xff, 0xff, 0xff, 0xfe, 0x00, 0x00));
+    let mut protocol = BinaryProtocol::new();
+    let err = protocol.read_binary(transport).unwrap_err();
+    match err {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::NegativeSize),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
+
+#[test]
+fn read_binary_size_limit() {
+    let transport = &mut MockTransport::new(vec!(0x00, 0x00, 0x00, 0x04, 0x01, 0x02, 0x03, 0x04));
+    let mut protocol = BinaryProtocol::new().string_limit(3);
+    let err = protocol.read_binary(transport).unwrap_err();
+    match err {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::SizeLimit),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
+
 #[test]
 fn read_set_begin() {
     let transport = &mut MockTransport::new(vec!(0x0b, 0x00, 0x00, 0x01, 0x0f));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(
         protocol.read_set_begin(transport).unwrap(),
         (protocol::Type::String, 0x0000010f)
@@ -117,7 +140,7 @@ fn read_set_begin() {
 #[test]
 fn read_list_begin() {
     let transport = &mut MockTransport::new(vec!(0x0b, 0x00, 0x00, 0x01, 0x0f));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(
         protocol.read_list_begin(transport).unwrap(),
         (protocol::Type::String, 0x0000010f)
@@ -127,17 +150,39 @@ fn read_list_begin() {
 #[test]
 fn read_map_begin() {
     let transport = &mut MockTransport::new(vec!(0x0b, 0x08, 0x00, 0x00, 0x01, 0x0f));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(
         protocol.read_map_begin(transport).unwrap(),
         (protocol::Type::String, protocol::Type::I32, 0x0000010f)
     );
 }
 
+#[test]
+fn read_list_begin_negative_size() {
+    let transport = &mut MockTransport::new(vec!(0x08, 0x80, 0x00, 0x00, 0x00));
+    let mut protocol = BinaryProtocol::new();
+    let err = protocol.read_list_begin(transport).unwrap_err();
+    match err {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::NegativeSize),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
+
+#[test]
+fn read_map_begin_size_limit() {
+    let transport = &mut MockTransport::new(vec!(0x0b, 0x08, 0x00, 0x00, 0x01, 0x00));
+    let mut protocol = BinaryProtocol::new().container_limit(0xff);
+    let err = protocol.read_map_begin(transport).unwrap_err();
+    match err {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::SizeLimit),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
+
 #[test]
 fn read_field_begin() {
     let transport = &mut MockTransport::new(vec!(0x00, 0x0d, 0x14, 0x0e));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(
         protocol.read_field_begin(transport).unwrap(),
         ("".to_string(), protocol::Type::Stop, 0)
@@ -155,13 +200,66 @@ fn read_message_begin() {
         0x00, 0x00, 0x00, 0x03, 0x66, 0x6f, 0x6f,
         0x00, 0x02, 0x47, 0x1e
     ));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
+    assert_eq!(
+        protocol.read_message_begin(transport).unwrap(),
+        ("foo".to_string(), protocol::MessageType::Call, 0x0002471e)
+    );
+}
+
+#[test]
+fn read_message_begin_non_strict() {
+    let transport = &mut MockTransport::new(vec!(
+        0x00, 0x00, 0x00, 0x03, 0x66, 0x6f, 0x6f,
+        0x01,
+        0x00, 0x02, 0x47, 0x1e
+    ));
+    let mut protocol = BinaryProtocol::new();
     assert_eq!(
         protocol.read_message_begin(transport).unwrap(),
         ("foo".to_string(), protocol::MessageType::Call, 0x0002471e)
     );
 }
 
+#[test]
+fn read_message_begin_non_strict_refused() {
+    let transport = &mut MockTransport::new(vec!(
+        0x00, 0x00, 0x00, 0x03, 0x66, 0x6f, 0x6f,
+        0x01,
+        0x00, 0x02, 0x47, 0x1e
+    ));
+    let mut protocol = BinaryProtocol::new().strict_read(true);
+    let err = protocol.read_message_begin(transport).unwrap_err();
+    match err {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::BadVersion),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
+
+#[test]
+fn write_message_begin() {
+    let transport = &mut MockTransport::new(vec!());
+    let mut protocol = BinaryProtocol::new();
+    protocol.write_message_begin(transport, "foo", protocol::MessageType::Reply, 0x0002471e).unwrap();
+    assert_eq!(transport.written(), &[
+        0x80, 0x01, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x03, 0x66, 0x6f, 0x6f,
+        0x00, 0x02, 0x47, 0x1e
+    ][..]);
+}
+
+#[test]
+fn write_message_begin_non_strict() {
+    let transport = &mut MockTransport::new(vec!());
+    let mut protocol = BinaryProtocol::new().strict_write(false);
+    protocol.write_message_begin(transport, "foo", protocol::MessageType::Reply, 0x0002471e).unwrap();
+    assert_eq!(transport.written(), &[
+        0x00, 0x00, 0x00, 0x03, 0x66, 0x6f, 0x6f,
+        0x02,
+        0x00, 0x02, 0x47, 0x1e
+    ][..]);
+}
+
 #[test]
 fn read_message_begin_bad_version() {
     let transport = &mut MockTransport::new(vec!(
@@ -169,7 +267,7 @@ fn read_message_begin_bad_version() {
         0x00, 0x00, 0x00, 0x03, 0x66, 0x6f, 0x6f,
         0x00, 0x02, 0x47, 0x1e
     ));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     let err = protocol.read_message_begin(transport).unwrap_err();
     match err {
         Error::ProtocolError(e) => assert_eq!(e, protocol::Error::BadVersion),
@@ -184,7 +282,7 @@ fn read_message_begin_invalid_message_type() {
         0x00, 0x00, 0x00, 0x03, 0x66, 0x6f, 0x6f,
         0x00, 0x02, 0x47, 0x1e
     ));
-    let mut protocol = BinaryProtocol;
+    let mut protocol = BinaryProtocol::new();
     let err = protocol.read_message_begin(transport).unwrap_err();
     match err {
         Error::ProtocolError(e) => assert_eq!(e, protocol::Error::ProtocolViolation),
@@ -192,3 +290,83 @@ fn read_message_begin_invalid_message_type() {
     }
 }
 
+#[test]
+fn decode_list_negative_size() {
+    let transport = &mut MockTransport::new(vec!(0x08, 0xff, 0xff, 0xff, 0xff));
+    let mut protocol = BinaryProtocol::new();
+    let mut list: Vec<i32> = Vec::new();
+    match list.decode(&mut protocol, transport).unwrap_err() {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::NegativeSize),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
+
+// A list holding a list, and so on `depth` times, down to an empty i32 list.
+fn nested_lists(depth: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for _ in 1..depth {
+        bytes.extend_from_slice(&[0x0f, 0x00, 0x00, 0x00, 0x01]);
+    }
+    bytes.extend_from_slice(&[0x08, 0x00, 0x00, 0x00, 0x00]);
+    bytes
+}
+
+#[test]
+fn skip_depth_limit() {
+    let mut protocol = BinaryProtocol::new();
+    let transport = &mut MockTransport::new(nested_lists(64));
+    protocol.skip(transport, protocol::Type::List).unwrap();
+
+    let transport = &mut MockTransport::new(nested_lists(65));
+    match protocol.skip(transport, protocol::Type::List).unwrap_err() {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::DepthLimit),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
+
+#[test]
+fn decode_depth_limit() {
+    let mut protocol = BinaryProtocol::new().depth_limit(2);
+    let mut lists: Vec<Vec<i32>> = Vec::new();
+    lists.decode(&mut protocol, &mut MockTransport::new(nested_lists(2))).unwrap();
+
+    let mut lists: Vec<Vec<Vec<i32>>> = Vec::new();
+    match lists.decode(&mut protocol, &mut MockTransport::new(nested_lists(3))).unwrap_err() {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::DepthLimit),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
+
+#[test]
+fn failed_decode_resets_depth() {
+    let mut protocol = BinaryProtocol::new().depth_limit(2);
+    let mut truncated = nested_lists(2);
+    truncated.pop();
+    let mut lists: Vec<Vec<i32>> = Vec::new();
+    assert!(lists.decode(&mut protocol, &mut MockTransport::new(truncated)).is_err());
+    lists.decode(&mut protocol, &mut MockTransport::new(nested_lists(2))).unwrap();
+
+    let mut deeper: Vec<Vec<Vec<i32>>> = Vec::new();
+    assert!(deeper.decode(&mut protocol, &mut MockTransport::new(nested_lists(3))).is_err());
+    lists.decode(&mut protocol, &mut MockTransport::new(nested_lists(2))).unwrap();
+}
+
+#[test]
+fn decode_struct_depth_limit() {
+    let nested = DeeplyNested { nested: Some(vec![vec![vec![vec![vec![1]]]]].into_iter().collect()) };
+    let transport = &mut MockTransport::new(vec!());
+    nested.encode(&mut BinaryProtocol::new(), transport).unwrap();
+    let bytes = transport.written().to_vec();
+
+    // The struct, the set and four lists.
+    let mut protocol = BinaryProtocol::new().depth_limit(6);
+    let mut decoded = DeeplyNested::default();
+    decoded.decode(&mut protocol, &mut MockTransport::new(bytes.clone())).unwrap();
+    assert_eq!(decoded.nested, nested.nested);
+
+    let mut protocol = BinaryProtocol::new().depth_limit(5);
+    match DeeplyNested::default().decode(&mut protocol, &mut MockTransport::new(bytes)).unwrap_err() {
+        Error::ProtocolError(e) => assert_eq!(e, protocol::Error::DepthLimit),
+        e => panic!("Expected a protocol error, got {:?}", e)
+    }
+}
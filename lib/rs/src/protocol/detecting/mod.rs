@@ -0,0 +1,951 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io::{self, Cursor, Read, Write};
+
+use protocol::{self, MessageType, Protocol, ProtocolFactory, Type};
+use transport::Transport;
+use {Result, Error};
+
+use podio::{ReadPodExt, WritePodExt, BigEndian};
+
+/// The largest frame accepted by default, as in the other Thrift libraries.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16384000;
+
+/// A protocol factory for servers accepting several protocols on the same
+/// port.
+///
+/// The protocol of a connection is picked from the first two bytes it
+/// receives, among those registered: `0x80 0x01` for strict binary, `0x82`
+/// and version 1 for compact, `[1` for JSON, or any signature given to
+/// `register`. When these are preceded by a four-byte size the connection is
+/// taken to be framed: each message is then read from its whole frame, and
+/// replies are framed in turn. Connections starting otherwise fail with
+/// `UnknownProtocol`.
+pub struct DetectingProtocolFactory<L = Unregistered> {
+    protocols: L,
+    max_frame_size: usize,
+}
+
+impl DetectingProtocolFactory {
+    pub fn new() -> DetectingProtocolFactory {
+        DetectingProtocolFactory { protocols: Unregistered, max_frame_size: DEFAULT_MAX_FRAME_SIZE }
+    }
+}
+
+impl<L> DetectingProtocolFactory<L> {
+    /// Accepts the protocol created by `factory` on connections whose first
+    /// two bytes satisfy `matches`. Protocols registered last are tried first.
+    pub fn register<F: ProtocolFactory>(self, factory: F, matches: fn(&[u8; 2]) -> bool)
+                                        -> DetectingProtocolFactory<Registered<F, L>> {
+        DetectingProtocolFactory {
+            protocols: Registered { first: factory, matches: matches, rest: self.protocols },
+            max_frame_size: self.max_frame_size,
+        }
+    }
+
+    pub fn binary<F: ProtocolFactory>(self, factory: F) -> DetectingProtocolFactory<Registered<F, L>> {
+        self.register(factory, is_binary)
+    }
+
+    pub fn compact<F: ProtocolFactory>(self, factory: F) -> DetectingProtocolFactory<Registered<F, L>> {
+        self.register(factory, is_compact)
+    }
+
+    pub fn json<F: ProtocolFactory>(self, factory: F) -> DetectingProtocolFactory<Registered<F, L>> {
+        self.register(factory, is_json)
+    }
+
+    /// The largest frame read on framed connections, larger ones failing with
+    /// `SizeLimit`. Defaults to `DEFAULT_MAX_FRAME_SIZE`.
+    pub fn max_frame_size(mut self, size: usize) -> DetectingProtocolFactory<L> {
+        self.max_frame_size = size;
+        self
+    }
+}
+
+impl<L: ProtocolFactory> ProtocolFactory for DetectingProtocolFactory<L> where L::Protocol: Detect {
+    type Protocol = DetectingProtocol<L::Protocol>;
+
+    fn new_protocol(&self) -> Self::Protocol {
+        DetectingProtocol {
+            protocol: self.protocols.new_protocol(),
+            detected: false,
+            framed: false,
+            max_frame_size: self.max_frame_size,
+            replay: Cursor::new(Vec::new()),
+            write_buffer: Vec::new(),
+        }
+    }
+}
+
+// Strict binary messages start with the version, 0x8001.
+fn is_binary(signature: &[u8; 2]) -> bool {
+    *signature == [0x80, 0x01]
+}
+
+// Compact messages start with the protocol id, then the version in the low
+// five bits.
+fn is_compact(signature: &[u8; 2]) -> bool {
+    signature[0] == 0x82 && signature[1] & 0x1f == 0x01
+}
+
+// JSON messages are arrays starting with the version.
+fn is_json(signature: &[u8; 2]) -> bool {
+    *signature == [b'[', b'1']
+}
+
+/// The protocols registered with a `DetectingProtocolFactory`: the factory
+/// registered last, and those registered before it.
+pub struct Registered<F, L> {
+    first: F,
+    matches: fn(&[u8; 2]) -> bool,
+    rest: L,
+}
+
+impl<F: ProtocolFactory, L: ProtocolFactory> ProtocolFactory for Registered<F, L> {
+    type Protocol = Candidate<F::Protocol, L::Protocol>;
+
+    fn new_protocol(&self) -> Self::Protocol {
+        Candidate {
+            first: self.first.new_protocol(),
+            matches: self.matches,
+            rest: self.rest.new_protocol(),
+            selected: false,
+        }
+    }
+}
+
+/// The protocols a `DetectingProtocol` picks from.
+pub trait Detect: Protocol {
+    /// Selects the protocol of messages starting with `signature`, returning
+    /// whether there is one.
+    fn select(&mut self, signature: &[u8; 2]) -> bool;
+}
+
+/// A protocol of a `DetectingProtocol`, and those registered before it. Calls
+/// go to the protocol once it is selected, and to the others until then.
+pub struct Candidate<P, L> {
+    first: P,
+    matches: fn(&[u8; 2]) -> bool,
+    rest: L,
+    selected: bool,
+}
+
+impl<P: Protocol, L: Detect> Detect for Candidate<P, L> {
+    fn select(&mut self, signature: &[u8; 2]) -> bool {
+        self.selected = (self.matches)(signature);
+        self.selected || self.rest.select(signature)
+    }
+}
+
+// Calls `$method` on the selected protocol, or on the others until then.
+macro_rules! selected {
+    ($this:ident, $method:ident($($arg:expr),*)) => {
+        if $this.selected { $this.first.$method($($arg),*) } else { $this.rest.$method($($arg),*) }
+    }
+}
+
+impl<P: Protocol, L: Protocol> Protocol for Candidate<P, L> {
+    fn write_message_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        name: &str,
+        message_type: MessageType,
+        sequence_id: i32
+    ) -> Result<()> {
+        selected!(self, write_message_begin(transport, name, message_type, sequence_id))
+    }
+
+    fn write_message_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, write_message_end(transport))
+    }
+
+    fn write_struct_begin<T: Transport>(&mut self, transport: &mut T, name: &str) -> Result<()> {
+        selected!(self, write_struct_begin(transport, name))
+    }
+
+    fn write_struct_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, write_struct_end(transport))
+    }
+
+    fn write_field_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        name: &str,
+        field_type: Type,
+        field_id: i16
+    ) -> Result<()> {
+        selected!(self, write_field_begin(transport, name, field_type, field_id))
+    }
+
+    fn write_field_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, write_field_end(transport))
+    }
+
+    fn write_field_stop<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, write_field_stop(transport))
+    }
+
+    fn write_map_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        key_type: Type,
+        value_type: Type,
+        size: usize
+    ) -> Result<()> {
+        selected!(self, write_map_begin(transport, key_type, value_type, size))
+    }
+
+    fn write_map_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, write_map_end(transport))
+    }
+
+    fn write_list_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        elem_type: Type,
+        size: usize
+    ) -> Result<()> {
+        selected!(self, write_list_begin(transport, elem_type, size))
+    }
+
+    fn write_list_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, write_list_end(transport))
+    }
+
+    fn write_set_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        elem_type: Type,
+        size: usize
+    ) -> Result<()> {
+        selected!(self, write_set_begin(transport, elem_type, size))
+    }
+
+    fn write_set_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, write_set_end(transport))
+    }
+
+    fn write_bool<T: Transport>(&mut self, transport: &mut T, value: bool) -> Result<()> {
+        selected!(self, write_bool(transport, value))
+    }
+
+    fn write_byte<T: Transport>(&mut self, transport: &mut T, value: i8) -> Result<()> {
+        selected!(self, write_byte(transport, value))
+    }
+
+    fn write_i16<T: Transport>(&mut self, transport: &mut T, value: i16) -> Result<()> {
+        selected!(self, write_i16(transport, value))
+    }
+
+    fn write_i32<T: Transport>(&mut self, transport: &mut T, value: i32) -> Result<()> {
+        selected!(self, write_i32(transport, value))
+    }
+
+    fn write_i64<T: Transport>(&mut self, transport: &mut T, value: i64) -> Result<()> {
+        selected!(self, write_i64(transport, value))
+    }
+
+    fn write_double<T: Transport>(&mut self, transport: &mut T, value: f64) -> Result<()> {
+        selected!(self, write_double(transport, value))
+    }
+
+    fn write_str<T: Transport>(&mut self, transport: &mut T, value: &str) -> Result<()> {
+        selected!(self, write_str(transport, value))
+    }
+
+    fn write_string<T: Transport>(&mut self, transport: &mut T, value: &String) -> Result<()> {
+        selected!(self, write_string(transport, value))
+    }
+
+    fn write_binary<T: Transport>(&mut self, transport: &mut T, value: &[u8]) -> Result<()> {
+        selected!(self, write_binary(transport, value))
+    }
+
+    fn read_message_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(String, MessageType, i32)> {
+        selected!(self, read_message_begin(transport))
+    }
+
+    fn read_message_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, read_message_end(transport))
+    }
+
+    fn read_struct_begin<T: Transport>(&mut self, transport: &mut T) -> Result<String> {
+        selected!(self, read_struct_begin(transport))
+    }
+
+    fn read_struct_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, read_struct_end(transport))
+    }
+
+    fn read_field_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(String, Type, i16)> {
+        selected!(self, read_field_begin(transport))
+    }
+
+    fn read_field_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, read_field_end(transport))
+    }
+
+    fn read_map_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, Type, i32)> {
+        selected!(self, read_map_begin(transport))
+    }
+
+    fn read_map_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, read_map_end(transport))
+    }
+
+    fn read_list_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, i32)> {
+        selected!(self, read_list_begin(transport))
+    }
+
+    fn read_list_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, read_list_end(transport))
+    }
+
+    fn read_set_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, i32)> {
+        selected!(self, read_set_begin(transport))
+    }
+
+    fn read_set_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        selected!(self, read_set_end(transport))
+    }
+
+    fn read_bool<T: Transport>(&mut self, transport: &mut T) -> Result<bool> {
+        selected!(self, read_bool(transport))
+    }
+
+    fn read_byte<T: Transport>(&mut self, transport: &mut T) -> Result<i8> {
+        selected!(self, read_byte(transport))
+    }
+
+    fn read_i16<T: Transport>(&mut self, transport: &mut T) -> Result<i16> {
+        selected!(self, read_i16(transport))
+    }
+
+    fn read_i32<T: Transport>(&mut self, transport: &mut T) -> Result<i32> {
+        selected!(self, read_i32(transport))
+    }
+
+    fn read_i64<T: Transport>(&mut self, transport: &mut T) -> Result<i64> {
+        selected!(self, read_i64(transport))
+    }
+
+    fn read_double<T: Transport>(&mut self, transport: &mut T) -> Result<f64> {
+        selected!(self, read_double(transport))
+    }
+
+    fn read_string<T: Transport>(&mut self, transport: &mut T) -> Result<String> {
+        selected!(self, read_string(transport))
+    }
+
+    fn read_binary<T: Transport>(&mut self, transport: &mut T) -> Result<Vec<u8>> {
+        selected!(self, read_binary(transport))
+    }
+
+    fn skip<T: Transport>(&mut self, transport: &mut T, type_: Type) -> Result<()> {
+        selected!(self, skip(transport, type_))
+    }
+}
+
+/// The protocol created by `DetectingProtocolFactory`, which delegates to the
+/// protocol detected on the first message.
+pub struct DetectingProtocol<P> {
+    protocol: P,
+    detected: bool,
+    framed: bool,
+    max_frame_size: usize,
+    // The current frame, or the bytes consumed by the detection on unframed
+    // connections, to be read by the protocol.
+    replay: Cursor<Vec<u8>>,
+    // The reply being built, when it has to be framed.
+    write_buffer: Vec<u8>,
+}
+
+impl<P: Detect> DetectingProtocol<P> {
+    /// Whether the connection was detected to be framed.
+    pub fn is_framed(&self) -> bool {
+        self.framed
+    }
+
+    fn detect<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        let mut start = [0; 6];
+        try!(read_fully(transport, &mut start[..2]));
+        if self.protocol.select(&[start[0], start[1]]) {
+            self.replay = Cursor::new(start[..2].to_vec());
+        } else {
+            // Maybe the frame size, followed by the signature.
+            try!(read_fully(transport, &mut start[2..]));
+            if !self.protocol.select(&[start[4], start[5]]) {
+                return Err(Error::from(protocol::Error::UnknownProtocol));
+            }
+            self.framed = true;
+            let size = try!(Cursor::new(&start[..4]).read_u32::<BigEndian>());
+            try!(self.read_frame(transport, size, &start[4..]));
+        }
+
+        self.detected = true;
+        Ok(())
+    }
+
+    // Reads a whole frame of `size` bytes, the first of which were already
+    // read into `start`.
+    fn read_frame<T: Transport>(&mut self, transport: &mut T, size: u32, start: &[u8]) -> Result<()> {
+        let size = size as usize;
+        if size > self.max_frame_size {
+            return Err(Error::from(protocol::Error::SizeLimit));
+        }
+        if size < start.len() {
+            return Err(Error::from(protocol::Error::ProtocolViolation));
+        }
+
+        let mut frame = start.to_vec();
+        frame.resize(size, 0);
+        try!(read_fully(transport, &mut frame[start.len()..]));
+        self.replay = Cursor::new(frame);
+        Ok(())
+    }
+}
+
+// Reads with the detected protocol, from the current frame on framed
+// connections, and after the bytes consumed by the detection otherwise.
+macro_rules! read_with {
+    ($this:ident, $transport:ident, $method:ident($($arg:expr),*)) => {{
+        if $this.framed || ($this.replay.position() as usize) < $this.replay.get_ref().len() {
+            let mut transport = Replay { prefix: &mut $this.replay, inner: $transport, framed: $this.framed };
+            $this.protocol.$method(&mut transport, $($arg),*)
+        } else {
+            $this.protocol.$method($transport, $($arg),*)
+        }
+    }}
+}
+
+// Writes with the detected protocol, into the frame buffer if the connection
+// is framed.
+macro_rules! write_with {
+    ($this:ident, $transport:ident, $method:ident($($arg:expr),*)) => {{
+        if $this.framed {
+            let mut buffer = FrameBuffer(&mut $this.write_buffer);
+            $this.protocol.$method(&mut buffer, $($arg),*)
+        } else {
+            $this.protocol.$method($transport, $($arg),*)
+        }
+    }}
+}
+
+impl<P: Detect> Protocol for DetectingProtocol<P> {
+    fn write_message_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        name: &str,
+        message_type: MessageType,
+        sequence_id: i32
+    ) -> Result<()> {
+        write_with!(self, transport, write_message_begin(name, message_type, sequence_id))
+    }
+
+    fn write_message_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        try!(write_with!(self, transport, write_message_end()));
+        if self.framed {
+            try!(transport.write_u32::<BigEndian>(self.write_buffer.len() as u32));
+            try!(transport.write_all(&self.write_buffer));
+            self.write_buffer.clear();
+        }
+        Ok(())
+    }
+
+    fn write_struct_begin<T: Transport>(&mut self, transport: &mut T, name: &str) -> Result<()> {
+        write_with!(self, transport, write_struct_begin(name))
+    }
+
+    fn write_struct_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        write_with!(self, transport, write_struct_end())
+    }
+
+    fn write_field_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        name: &str,
+        field_type: Type,
+        field_id: i16
+    ) -> Result<()> {
+        write_with!(self, transport, write_field_begin(name, field_type, field_id))
+    }
+
+    fn write_field_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        write_with!(self, transport, write_field_end())
+    }
+
+    fn write_field_stop<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        write_with!(self, transport, write_field_stop())
+    }
+
+    fn write_map_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        key_type: Type,
+        value_type: Type,
+        size: usize
+    ) -> Result<()> {
+        write_with!(self, transport, write_map_begin(key_type, value_type, size))
+    }
+
+    fn write_map_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        write_with!(self, transport, write_map_end())
+    }
+
+    fn write_list_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        elem_type: Type,
+        size: usize
+    ) -> Result<()> {
+        write_with!(self, transport, write_list_begin(elem_type, size))
+    }
+
+    fn write_list_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        write_with!(self, transport, write_list_end())
+    }
+
+    fn write_set_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        elem_type: Type,
+        size: usize
+    ) -> Result<()> {
+        write_with!(self, transport, write_set_begin(elem_type, size))
+    }
+
+    fn write_set_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        write_with!(self, transport, write_set_end())
+    }
+
+    fn write_bool<T: Transport>(&mut self, transport: &mut T, value: bool) -> Result<()> {
+        write_with!(self, transport, write_bool(value))
+    }
+
+    fn write_byte<T: Transport>(&mut self, transport: &mut T, value: i8) -> Result<()> {
+        write_with!(self, transport, write_byte(value))
+    }
+
+    fn write_i16<T: Transport>(&mut self, transport: &mut T, value: i16) -> Result<()> {
+        write_with!(self, transport, write_i16(value))
+    }
+
+    fn write_i32<T: Transport>(&mut self, transport: &mut T, value: i32) -> Result<()> {
+        write_with!(self, transport, write_i32(value))
+    }
+
+    fn write_i64<T: Transport>(&mut self, transport: &mut T, value: i64) -> Result<()> {
+        write_with!(self, transport, write_i64(value))
+    }
+
+    fn write_double<T: Transport>(&mut self, transport: &mut T, value: f64) -> Result<()> {
+        write_with!(self, transport, write_double(value))
+    }
+
+    fn write_str<T: Transport>(&mut self, transport: &mut T, value: &str) -> Result<()> {
+        write_with!(self, transport, write_str(value))
+    }
+
+    fn write_string<T: Transport>(&mut self, transport: &mut T, value: &String) -> Result<()> {
+        write_with!(self, transport, write_string(value))
+    }
+
+    fn write_binary<T: Transport>(&mut self, transport: &mut T, value: &[u8]) -> Result<()> {
+        write_with!(self, transport, write_binary(value))
+    }
+
+    fn read_message_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(String, MessageType, i32)> {
+        if !self.detected {
+            try!(self.detect(transport));
+        } else if self.framed {
+            let size = try!(transport.read_u32::<BigEndian>());
+            try!(self.read_frame(transport, size, &[]));
+        }
+        read_with!(self, transport, read_message_begin())
+    }
+
+    fn read_message_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        try!(read_with!(self, transport, read_message_end()));
+        // The message must fill its frame.
+        if self.framed && (self.replay.position() as usize) < self.replay.get_ref().len() {
+            return Err(Error::from(protocol::Error::ProtocolViolation));
+        }
+        Ok(())
+    }
+
+    fn read_struct_begin<T: Transport>(&mut self, transport: &mut T) -> Result<String> {
+        read_with!(self, transport, read_struct_begin())
+    }
+
+    fn read_struct_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        read_with!(self, transport, read_struct_end())
+    }
+
+    fn read_field_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(String, Type, i16)> {
+        read_with!(self, transport, read_field_begin())
+    }
+
+    fn read_field_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        read_with!(self, transport, read_field_end())
+    }
+
+    fn read_map_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, Type, i32)> {
+        read_with!(self, transport, read_map_begin())
+    }
+
+    fn read_map_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        read_with!(self, transport, read_map_end())
+    }
+
+    fn read_list_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, i32)> {
+        read_with!(self, transport, read_list_begin())
+    }
+
+    fn read_list_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        read_with!(self, transport, read_list_end())
+    }
+
+    fn read_set_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, i32)> {
+        read_with!(self, transport, read_set_begin())
+    }
+
+    fn read_set_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        read_with!(self, transport, read_set_end())
+    }
+
+    fn read_bool<T: Transport>(&mut self, transport: &mut T) -> Result<bool> {
+        read_with!(self, transport, read_bool())
+    }
+
+    fn read_byte<T: Transport>(&mut self, transport: &mut T) -> Result<i8> {
+        read_with!(self, transport, read_byte())
+    }
+
+    fn read_i16<T: Transport>(&mut self, transport: &mut T) -> Result<i16> {
+        read_with!(self, transport, read_i16())
+    }
+
+    fn read_i32<T: Transport>(&mut self, transport: &mut T) -> Result<i32> {
+        read_with!(self, transport, read_i32())
+    }
+
+    fn read_i64<T: Transport>(&mut self, transport: &mut T) -> Result<i64> {
+        read_with!(self, transport, read_i64())
+    }
+
+    fn read_double<T: Transport>(&mut self, transport: &mut T) -> Result<f64> {
+        read_with!(self, transport, read_double())
+    }
+
+    fn read_string<T: Transport>(&mut self, transport: &mut T) -> Result<String> {
+        read_with!(self, transport, read_string())
+    }
+
+    fn read_binary<T: Transport>(&mut self, transport: &mut T) -> Result<Vec<u8>> {
+        read_with!(self, transport, read_binary())
+    }
+
+    fn skip<T: Transport>(&mut self, transport: &mut T, type_: Type) -> Result<()> {
+        read_with!(self, transport, skip(type_))
+    }
+}
+
+struct Replay<'a, T: 'a> {
+    prefix: &'a mut Cursor<Vec<u8>>,
+    inner: &'a mut T,
+    // Whether the prefix is a frame, which must not be read past.
+    framed: bool,
+}
+
+impl<'a, T: Read> Read for Replay<'a, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match try!(self.prefix.read(buf)) {
+            0 if self.framed && !buf.is_empty() => {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "read past the end of the frame"))
+            }
+            0 => self.inner.read(buf),
+            n => Ok(n)
+        }
+    }
+}
+
+impl<'a, T: Write> Write for Replay<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct FrameBuffer<'a>(&'a mut Vec<u8>);
+
+impl<'a> Read for FrameBuffer<'a> {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::Other, "can't read while writing a frame"))
+    }
+}
+
+impl<'a> Write for FrameBuffer<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn read_fully<T: Read>(transport: &mut T, buf: &mut [u8]) -> Result<()> {
+    let mut read = 0;
+    while read < buf.len() {
+        match try!(transport.read(&mut buf[read..])) {
+            0 => return Err(Error::from(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"))),
+            n => read += n,
+        }
+    }
+    Ok(())
+}
+
+/// Ends the protocols registered with a `DetectingProtocolFactory`. It
+/// matches no signature, and fails all calls since it is only called when no
+/// protocol was detected.
+pub struct Unregistered;
+
+impl ProtocolFactory for Unregistered {
+    type Protocol = Unregistered;
+
+    fn new_protocol(&self) -> Unregistered {
+        Unregistered
+    }
+}
+
+impl Detect for Unregistered {
+    fn select(&mut self, _signature: &[u8; 2]) -> bool {
+        false
+    }
+}
+
+impl Protocol for Unregistered {
+    fn write_message_begin<T: Transport>(
+        &mut self,
+        _transport: &mut T,
+        _name: &str,
+        _message_type: MessageType,
+        _sequence_id: i32
+    ) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_message_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_struct_begin<T: Transport>(&mut self, _transport: &mut T, _name: &str) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_struct_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_field_begin<T: Transport>(
+        &mut self,
+        _transport: &mut T,
+        _name: &str,
+        _field_type: Type,
+        _field_id: i16
+    ) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_field_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_field_stop<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_map_begin<T: Transport>(
+        &mut self,
+        _transport: &mut T,
+        _key_type: Type,
+        _value_type: Type,
+        _size: usize
+    ) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_map_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_list_begin<T: Transport>(
+        &mut self,
+        _transport: &mut T,
+        _elem_type: Type,
+        _size: usize
+    ) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_list_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_set_begin<T: Transport>(
+        &mut self,
+        _transport: &mut T,
+        _elem_type: Type,
+        _size: usize
+    ) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_set_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_bool<T: Transport>(&mut self, _transport: &mut T, _value: bool) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_byte<T: Transport>(&mut self, _transport: &mut T, _value: i8) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_i16<T: Transport>(&mut self, _transport: &mut T, _value: i16) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_i32<T: Transport>(&mut self, _transport: &mut T, _value: i32) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_i64<T: Transport>(&mut self, _transport: &mut T, _value: i64) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_double<T: Transport>(&mut self, _transport: &mut T, _value: f64) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_str<T: Transport>(&mut self, _transport: &mut T, _value: &str) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_string<T: Transport>(&mut self, _transport: &mut T, _value: &String) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn write_binary<T: Transport>(&mut self, _transport: &mut T, _value: &[u8]) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_message_begin<T: Transport>(&mut self, _transport: &mut T) -> Result<(String, MessageType, i32)> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_message_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_struct_begin<T: Transport>(&mut self, _transport: &mut T) -> Result<String> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_struct_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_field_begin<T: Transport>(&mut self, _transport: &mut T) -> Result<(String, Type, i16)> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_field_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_map_begin<T: Transport>(&mut self, _transport: &mut T) -> Result<(Type, Type, i32)> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_map_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_list_begin<T: Transport>(&mut self, _transport: &mut T) -> Result<(Type, i32)> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_list_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_set_begin<T: Transport>(&mut self, _transport: &mut T) -> Result<(Type, i32)> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_set_end<T: Transport>(&mut self, _transport: &mut T) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_bool<T: Transport>(&mut self, _transport: &mut T) -> Result<bool> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_byte<T: Transport>(&mut self, _transport: &mut T) -> Result<i8> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_i16<T: Transport>(&mut self, _transport: &mut T) -> Result<i16> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_i32<T: Transport>(&mut self, _transport: &mut T) -> Result<i32> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_i64<T: Transport>(&mut self, _transport: &mut T) -> Result<i64> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_double<T: Transport>(&mut self, _transport: &mut T) -> Result<f64> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_string<T: Transport>(&mut self, _transport: &mut T) -> Result<String> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn read_binary<T: Transport>(&mut self, _transport: &mut T) -> Result<Vec<u8>> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+
+    fn skip<T: Transport>(&mut self, _transport: &mut T, _type_: Type) -> Result<()> {
+        Err(Error::from(protocol::Error::ProtocolViolation))
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,181 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::collections::HashSet;
+use std::io::{self, Cursor};
+
+use super::{Detect, DetectingProtocolFactory};
+
+use compiletest::*;
+use protocol::binary_protocol::BinaryProtocol;
+use protocol::{helpers, MessageType, ProtocolFactory};
+use transport::factory::SplitTransport;
+use {protocol, Error, Processor};
+
+struct Handler;
+
+impl SharedService for Handler {
+    fn get_struct(&self, key: i32) -> SharedServiceGetStructResult {
+        let mut nested = HashSet::new();
+        nested.insert(vec![vec![vec![vec![key]]]]);
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: Some(nested) }) }
+    }
+}
+
+fn request(key: i32) -> Vec<u8> {
+    let mut transport = SplitTransport::new(io::empty(), Vec::new());
+    let args = SharedServiceGetStructArgs { key: Some(key) };
+    helpers::send_message(&mut BinaryProtocol::new(), &mut transport, "get_struct",
+                          MessageType::Call, 1, &args).unwrap();
+    transport.into_inner().1
+}
+
+fn framed(message: Vec<u8>) -> Vec<u8> {
+    let len = message.len();
+    let mut frame = vec![(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    frame.extend(message);
+    frame
+}
+
+fn reply_key(reply: &[u8]) -> i32 {
+    let mut transport = SplitTransport::new(reply, io::sink());
+    let mut result = SharedServiceGetStructResult::default();
+    helpers::receive(&mut BinaryProtocol::new(), &mut transport, "get_struct", &mut result).unwrap();
+    result.success.unwrap().nested.unwrap().into_iter().next().unwrap()[0][0][0][0]
+}
+
+fn serve(input: Vec<u8>, messages: usize) -> (::Result<()>, bool, Vec<u8>) {
+    serve_with(DetectingProtocolFactory::new().binary(|| BinaryProtocol::new()), input, messages)
+}
+
+fn serve_with<L>(factory: DetectingProtocolFactory<L>, input: Vec<u8>, messages: usize)
+                 -> (::Result<()>, bool, Vec<u8>)
+where L: ProtocolFactory, L::Protocol: Detect {
+    let processor = SharedServiceProcessor::new(Handler);
+    let mut protocol = factory.new_protocol();
+    let mut transport = SplitTransport::new(Cursor::new(input), Vec::new());
+
+    let mut result = Ok(());
+    for _ in 0..messages {
+        result = processor.process(&mut protocol, &mut transport);
+        if result.is_err() { break; }
+    }
+
+    (result, protocol.is_framed(), transport.into_inner().1)
+}
+
+#[test]
+fn unframed_binary() {
+    let (result, framed, reply) = serve(request(3), 1);
+    assert!(result.is_ok());
+    assert!(!framed);
+    assert_eq!(reply_key(&reply), 3);
+}
+
+#[test]
+fn framed_binary() {
+    let mut input = framed(request(4));
+    input.extend(framed(request(5)));
+
+    let (result, framed, reply) = serve(input, 2);
+    assert!(result.is_ok());
+    assert!(framed);
+
+    let first = ((reply[0] as usize) << 24) | ((reply[1] as usize) << 16) |
+                ((reply[2] as usize) << 8) | reply[3] as usize;
+    assert_eq!(reply_key(&reply[4..4 + first]), 4);
+    assert_eq!(reply_key(&reply[8 + first..]), 5);
+}
+
+#[test]
+fn unknown_signature() {
+    let (result, _, reply) = serve(vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06], 1);
+    match result {
+        Err(Error::ProtocolError(protocol::Error::UnknownProtocol)) => {}
+        other => panic!("Expected UnknownProtocol, got {:?}", other)
+    }
+    assert!(reply.is_empty());
+}
+
+#[test]
+fn unregistered_protocol() {
+    // A compact signature, but only binary is accepted.
+    let (result, _, _) = serve(vec![0x82, 0x21, 0x00, 0x00, 0x00, 0x00], 1);
+    match result {
+        Err(Error::ProtocolError(protocol::Error::UnknownProtocol)) => {}
+        other => panic!("Expected UnknownProtocol, got {:?}", other)
+    }
+}
+
+#[test]
+fn checks_both_signature_bytes() {
+    // 0x80 followed by something other than the version.
+    let (result, _, _) = serve(vec![0x80, 0x02, 0x00, 0x00, 0x00, 0x00], 1);
+    match result {
+        Err(Error::ProtocolError(protocol::Error::UnknownProtocol)) => {}
+        other => panic!("Expected UnknownProtocol, got {:?}", other)
+    }
+}
+
+#[test]
+fn registered_protocol() {
+    fn any_binary_version(signature: &[u8; 2]) -> bool { signature[0] == 0x80 }
+
+    let factory = DetectingProtocolFactory::new().register(|| BinaryProtocol::new(), any_binary_version);
+    let (result, framed, reply) = serve_with(factory, framed(request(6)), 1);
+    assert!(result.is_ok());
+    assert!(framed);
+    assert_eq!(reply_key(&reply[4..]), 6);
+}
+
+#[test]
+fn frame_shorter_than_message() {
+    let message = request(7);
+    let mut input = framed(message[..message.len() - 1].to_vec());
+    input.extend(framed(request(8)));
+
+    let (result, _, _) = serve(input, 1);
+    assert!(result.is_err());
+}
+
+#[test]
+fn frame_longer_than_message() {
+    let mut message = request(7);
+    message.push(0);
+    let mut input = framed(message);
+    input.extend(framed(request(8)));
+
+    let (result, _, _) = serve(input, 1);
+    match result {
+        Err(Error::ProtocolError(protocol::Error::ProtocolViolation)) => {}
+        other => panic!("Expected ProtocolViolation, got {:?}", other)
+    }
+}
+
+#[test]
+fn frame_size_limit() {
+    let input = framed(request(9));
+    let limit = input.len() - 5;
+    let factory = DetectingProtocolFactory::new().binary(|| BinaryProtocol::new()).max_frame_size(limit);
+    let (result, _, _) = serve_with(factory, input, 1);
+    match result {
+        Err(Error::ProtocolError(protocol::Error::SizeLimit)) => {}
+        other => panic!("Expected SizeLimit, got {:?}", other)
+    }
+}
@@ -23,6 +23,8 @@ use transport::Transport;
 use Result;
 
 pub mod binary_protocol;
+pub mod detecting;
+pub mod split;
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
@@ -32,6 +34,14 @@ pub enum Error {
     ProtocolViolation,
     /// Received string cannot be converted to a UTF8 string
     InvalidUtf8(str::Utf8Error),
+    /// The first bytes of a connection match none of the accepted protocols
+    UnknownProtocol,
+    /// Received a negative string or container size
+    NegativeSize,
+    /// Received a string or container larger than the protocol accepts
+    SizeLimit,
+    /// Received structs or containers nested deeper than the protocol accepts
+    DepthLimit,
 }
 
 impl StdError for Error {
@@ -225,9 +235,86 @@ pub trait FromNum {
     fn from_num(num: i32) -> Option<Self>;
 }
 
+/// The error of parsing an enum from a name none of its values have.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseEnumError {
+    enum_name: &'static str,
+    name: String,
+}
+
+impl ParseEnumError {
+    pub fn new(enum_name: &'static str, name: &str) -> ParseEnumError {
+        ParseEnumError { enum_name: enum_name, name: String::from(name) }
+    }
+
+    /// The name that was parsed.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl StdError for ParseEnumError {
+    fn description(&self) -> &str {
+        "unknown enum value name"
+    }
+}
+
+impl fmt::Display for ParseEnumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} has no value named `{}`", self.enum_name, self.name)
+    }
+}
+
+/// The error of building a struct without one of its required fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildError {
+    struct_name: &'static str,
+    field: &'static str,
+}
+
+impl BuildError {
+    pub fn new(struct_name: &'static str, field: &'static str) -> BuildError {
+        BuildError { struct_name: struct_name, field: field }
+    }
+
+    /// The required field that wasn't set.
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+}
+
+impl StdError for BuildError {
+    fn description(&self) -> &str {
+        "required field not set"
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is missing the required field `{}`", self.struct_name, self.field)
+    }
+}
+
+/// Converts values of a type that can't implement `Encode` and `Decode`
+/// itself, usually one from another crate, for struct fields declared
+/// `name: T as Converter`. The generator declares those for fields annotated
+/// with `rs.type` and `rs.convert`.
+///
+/// `Thrift` must be the type of the field in the Thrift file.
+pub trait Convert<T> {
+    type Thrift: Encode + Decode;
+
+    fn to_thrift(value: &T) -> Self::Thrift;
+
+    /// Fails with `Error::ProtocolViolation` or similar when the value
+    /// received doesn't convert.
+    fn from_thrift(value: Self::Thrift) -> Result<T>;
+}
+
 pub mod helpers {
     use protocol::{Protocol, Type, MessageType, FromNum, Decode, Encode, Error};
     use transport::Transport;
+    use exception::ApplicationException;
     use Result;
 
     pub fn read_enum<F, T, P>(iprot: &mut P, transport: &mut T) -> Result<F>
@@ -243,8 +330,14 @@ pub mod helpers {
                          name: &str, _type: MessageType,
                          args: &W) -> Result<()>
     where W: Encode, T: Transport, P: Protocol {
-        let cseqid: i32 = 0;
-        try!(protocol.write_message_begin(transport, name, _type, cseqid));
+        send_message(protocol, transport, name, _type, 0, args)
+    }
+
+    pub fn send_message<W, T, P>(protocol: &mut P, transport: &mut T,
+                                 name: &str, _type: MessageType, sequence_id: i32,
+                                 args: &W) -> Result<()>
+    where W: Encode, T: Transport, P: Protocol {
+        try!(protocol.write_message_begin(transport, name, _type, sequence_id));
         try!(args.encode(protocol, transport));
         try!(protocol.write_message_end(transport));
         try!(transport.flush());
@@ -264,14 +357,10 @@ pub mod helpers {
     where R: Decode, T: Transport, P: Protocol {
         match (name, ty, id) {
             (_, MessageType::Exception, _) => {
-                println!("got exception");
-                // TODO
-                //let x = ApplicationException;
-                //x.read(&mut protocol)
-                //protocol.read_message_end();
-                //transport.read_end();
-                //throw x
-                Err(::Error::UserException)
+                let mut exception = ApplicationException::default();
+                try!(exception.decode(protocol, transport));
+                try!(protocol.read_message_end(transport));
+                Err(::Error::ApplicationError(exception))
             }
             // TODO: Make sure the client doesn't receive Call messages and that the server
             // doesn't receive Reply messages
@@ -0,0 +1,263 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use protocol::{MessageType, Protocol, ProtocolFactory, Type};
+use transport::Transport;
+use Result;
+
+/// A protocol reading with one protocol and writing with another, for
+/// servers whose requests and replies are encoded differently.
+pub struct SplitProtocol<I, O> {
+    input: I,
+    output: O,
+}
+
+impl<I: Protocol, O: Protocol> SplitProtocol<I, O> {
+    pub fn new(input: I, output: O) -> SplitProtocol<I, O> {
+        SplitProtocol {
+            input: input,
+            output: output,
+        }
+    }
+}
+
+impl<I: Protocol, O: Protocol> Protocol for SplitProtocol<I, O> {
+    fn write_message_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        name: &str,
+        message_type: MessageType,
+        sequence_id: i32
+    ) -> Result<()> {
+        self.output.write_message_begin(transport, name, message_type, sequence_id)
+    }
+
+    fn write_message_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.output.write_message_end(transport)
+    }
+
+    fn write_struct_begin<T: Transport>(&mut self, transport: &mut T, name: &str) -> Result<()> {
+        self.output.write_struct_begin(transport, name)
+    }
+
+    fn write_struct_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.output.write_struct_end(transport)
+    }
+
+    fn write_field_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        name: &str,
+        field_type: Type,
+        field_id: i16
+    ) -> Result<()> {
+        self.output.write_field_begin(transport, name, field_type, field_id)
+    }
+
+    fn write_field_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.output.write_field_end(transport)
+    }
+
+    fn write_field_stop<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.output.write_field_stop(transport)
+    }
+
+    fn write_map_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        key_type: Type,
+        value_type: Type,
+        size: usize
+    ) -> Result<()> {
+        self.output.write_map_begin(transport, key_type, value_type, size)
+    }
+
+    fn write_map_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.output.write_map_end(transport)
+    }
+
+    fn write_list_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        elem_type: Type,
+        size: usize
+    ) -> Result<()> {
+        self.output.write_list_begin(transport, elem_type, size)
+    }
+
+    fn write_list_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.output.write_list_end(transport)
+    }
+
+    fn write_set_begin<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        elem_type: Type,
+        size: usize
+    ) -> Result<()> {
+        self.output.write_set_begin(transport, elem_type, size)
+    }
+
+    fn write_set_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.output.write_set_end(transport)
+    }
+
+    fn write_bool<T: Transport>(&mut self, transport: &mut T, value: bool) -> Result<()> {
+        self.output.write_bool(transport, value)
+    }
+
+    fn write_byte<T: Transport>(&mut self, transport: &mut T, value: i8) -> Result<()> {
+        self.output.write_byte(transport, value)
+    }
+
+    fn write_i16<T: Transport>(&mut self, transport: &mut T, value: i16) -> Result<()> {
+        self.output.write_i16(transport, value)
+    }
+
+    fn write_i32<T: Transport>(&mut self, transport: &mut T, value: i32) -> Result<()> {
+        self.output.write_i32(transport, value)
+    }
+
+    fn write_i64<T: Transport>(&mut self, transport: &mut T, value: i64) -> Result<()> {
+        self.output.write_i64(transport, value)
+    }
+
+    fn write_double<T: Transport>(&mut self, transport: &mut T, value: f64) -> Result<()> {
+        self.output.write_double(transport, value)
+    }
+
+    fn write_str<T: Transport>(&mut self, transport: &mut T, value: &str) -> Result<()> {
+        self.output.write_str(transport, value)
+    }
+
+    fn write_string<T: Transport>(&mut self, transport: &mut T, value: &String) -> Result<()> {
+        self.output.write_string(transport, value)
+    }
+
+    fn write_binary<T: Transport>(&mut self, transport: &mut T, value: &[u8]) -> Result<()> {
+        self.output.write_binary(transport, value)
+    }
+
+    fn read_message_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(String, MessageType, i32)> {
+        self.input.read_message_begin(transport)
+    }
+
+    fn read_message_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.input.read_message_end(transport)
+    }
+
+    fn read_struct_begin<T: Transport>(&mut self, transport: &mut T) -> Result<String> {
+        self.input.read_struct_begin(transport)
+    }
+
+    fn read_struct_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.input.read_struct_end(transport)
+    }
+
+    fn read_field_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(String, Type, i16)> {
+        self.input.read_field_begin(transport)
+    }
+
+    fn read_field_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.input.read_field_end(transport)
+    }
+
+    fn read_map_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, Type, i32)> {
+        self.input.read_map_begin(transport)
+    }
+
+    fn read_map_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.input.read_map_end(transport)
+    }
+
+    fn read_list_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, i32)> {
+        self.input.read_list_begin(transport)
+    }
+
+    fn read_list_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.input.read_list_end(transport)
+    }
+
+    fn read_set_begin<T: Transport>(&mut self, transport: &mut T) -> Result<(Type, i32)> {
+        self.input.read_set_begin(transport)
+    }
+
+    fn read_set_end<T: Transport>(&mut self, transport: &mut T) -> Result<()> {
+        self.input.read_set_end(transport)
+    }
+
+    fn read_bool<T: Transport>(&mut self, transport: &mut T) -> Result<bool> {
+        self.input.read_bool(transport)
+    }
+
+    fn read_byte<T: Transport>(&mut self, transport: &mut T) -> Result<i8> {
+        self.input.read_byte(transport)
+    }
+
+    fn read_i16<T: Transport>(&mut self, transport: &mut T) -> Result<i16> {
+        self.input.read_i16(transport)
+    }
+
+    fn read_i32<T: Transport>(&mut self, transport: &mut T) -> Result<i32> {
+        self.input.read_i32(transport)
+    }
+
+    fn read_i64<T: Transport>(&mut self, transport: &mut T) -> Result<i64> {
+        self.input.read_i64(transport)
+    }
+
+    fn read_double<T: Transport>(&mut self, transport: &mut T) -> Result<f64> {
+        self.input.read_double(transport)
+    }
+
+    fn read_string<T: Transport>(&mut self, transport: &mut T) -> Result<String> {
+        self.input.read_string(transport)
+    }
+
+    fn read_binary<T: Transport>(&mut self, transport: &mut T) -> Result<Vec<u8>> {
+        self.input.read_binary(transport)
+    }
+
+    fn skip<T: Transport>(&mut self, transport: &mut T, type_: Type) -> Result<()> {
+        self.input.skip(transport, type_)
+    }
+}
+
+/// Creates `SplitProtocol`s from a factory for each direction.
+pub struct SplitProtocolFactory<I, O> {
+    input: I,
+    output: O,
+}
+
+impl<I: ProtocolFactory, O: ProtocolFactory> SplitProtocolFactory<I, O> {
+    pub fn new(input: I, output: O) -> SplitProtocolFactory<I, O> {
+        SplitProtocolFactory {
+            input: input,
+            output: output,
+        }
+    }
+}
+
+impl<I: ProtocolFactory, O: ProtocolFactory> ProtocolFactory for SplitProtocolFactory<I, O> {
+    type Protocol = SplitProtocol<I::Protocol, O::Protocol>;
+
+    fn new_protocol(&self) -> Self::Protocol {
+        SplitProtocol::new(self.input.new_protocol(), self.output.new_protocol())
+    }
+}
@@ -0,0 +1,153 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use transport::server::TransportServer;
+use transport::factory::{FactoryTransportServer, IdentityTransportFactory, SharedTransport};
+use transport::TransportFactory;
+use protocol::ProtocolFactory;
+use protocol::split::SplitProtocolFactory;
+use processor::Processor;
+use super::{SimpleServer, ThreadedServer};
+
+/// Builds a server with separate transport and protocol factories for the
+/// input and output of each connection, like the C++ `TServer`.
+///
+/// Transports default to the accepted connection itself. A protocol factory
+/// must be given, either for both directions or for each.
+pub struct ServerBuilder<P, TS, ITF, OTF, IPF, OPF> {
+    processor: P,
+    transport_server: TS,
+    input_transport_factory: ITF,
+    output_transport_factory: OTF,
+    input_protocol_factory: IPF,
+    output_protocol_factory: OPF,
+}
+
+impl<P, TS: TransportServer> ServerBuilder<P, TS, IdentityTransportFactory, IdentityTransportFactory, (), ()> {
+    pub fn new(processor: P, transport_server: TS) -> Self {
+        ServerBuilder {
+            processor: processor,
+            transport_server: transport_server,
+            input_transport_factory: IdentityTransportFactory,
+            output_transport_factory: IdentityTransportFactory,
+            input_protocol_factory: (),
+            output_protocol_factory: (),
+        }
+    }
+}
+
+impl<P, TS: TransportServer, ITF, OTF, IPF, OPF> ServerBuilder<P, TS, ITF, OTF, IPF, OPF> {
+    /// Uses `factory` for both the input and the output transports.
+    pub fn transport_factory<F>(self, factory: F) -> ServerBuilder<P, TS, F, F, IPF, OPF>
+    where F: TransportFactory<SharedTransport<TS::Transport>> + Clone {
+        self.input_transport_factory(factory.clone()).output_transport_factory(factory)
+    }
+
+    pub fn input_transport_factory<F>(self, factory: F) -> ServerBuilder<P, TS, F, OTF, IPF, OPF>
+    where F: TransportFactory<SharedTransport<TS::Transport>> {
+        ServerBuilder {
+            processor: self.processor,
+            transport_server: self.transport_server,
+            input_transport_factory: factory,
+            output_transport_factory: self.output_transport_factory,
+            input_protocol_factory: self.input_protocol_factory,
+            output_protocol_factory: self.output_protocol_factory,
+        }
+    }
+
+    pub fn output_transport_factory<F>(self, factory: F) -> ServerBuilder<P, TS, ITF, F, IPF, OPF>
+    where F: TransportFactory<SharedTransport<TS::Transport>> {
+        ServerBuilder {
+            processor: self.processor,
+            transport_server: self.transport_server,
+            input_transport_factory: self.input_transport_factory,
+            output_transport_factory: factory,
+            input_protocol_factory: self.input_protocol_factory,
+            output_protocol_factory: self.output_protocol_factory,
+        }
+    }
+
+    /// Uses `factory` for both the input and the output protocols.
+    pub fn protocol_factory<F>(self, factory: F) -> ServerBuilder<P, TS, ITF, OTF, F, F>
+    where F: ProtocolFactory + Clone {
+        self.input_protocol_factory(factory.clone()).output_protocol_factory(factory)
+    }
+
+    pub fn input_protocol_factory<F>(self, factory: F) -> ServerBuilder<P, TS, ITF, OTF, F, OPF>
+    where F: ProtocolFactory {
+        ServerBuilder {
+            processor: self.processor,
+            transport_server: self.transport_server,
+            input_transport_factory: self.input_transport_factory,
+            output_transport_factory: self.output_transport_factory,
+            input_protocol_factory: factory,
+            output_protocol_factory: self.output_protocol_factory,
+        }
+    }
+
+    pub fn output_protocol_factory<F>(self, factory: F) -> ServerBuilder<P, TS, ITF, OTF, IPF, F>
+    where F: ProtocolFactory {
+        ServerBuilder {
+            processor: self.processor,
+            transport_server: self.transport_server,
+            input_transport_factory: self.input_transport_factory,
+            output_transport_factory: self.output_transport_factory,
+            input_protocol_factory: self.input_protocol_factory,
+            output_protocol_factory: factory,
+        }
+    }
+}
+
+impl<P, TS, ITF, OTF, IPF, OPF> ServerBuilder<P, TS, ITF, OTF, IPF, OPF>
+where TS: TransportServer,
+      ITF: TransportFactory<SharedTransport<TS::Transport>>,
+      OTF: TransportFactory<SharedTransport<TS::Transport>>,
+      IPF: ProtocolFactory,
+      OPF: ProtocolFactory,
+      P: Processor<<SplitProtocolFactory<IPF, OPF> as ProtocolFactory>::Protocol,
+                   <FactoryTransportServer<TS, ITF, OTF> as TransportServer>::Transport> {
+    pub fn build(self) -> SimpleServer<P, SplitProtocolFactory<IPF, OPF>, FactoryTransportServer<TS, ITF, OTF>> {
+        let (processor, transport_server, protocol_factory) = self.into_parts();
+        SimpleServer::new(processor, transport_server, protocol_factory)
+    }
+
+    fn into_parts(self) -> (P, FactoryTransportServer<TS, ITF, OTF>, SplitProtocolFactory<IPF, OPF>) {
+        let transport_server = FactoryTransportServer::new(self.transport_server,
+                                                           self.input_transport_factory,
+                                                           self.output_transport_factory);
+        let protocol_factory = SplitProtocolFactory::new(self.input_protocol_factory,
+                                                         self.output_protocol_factory);
+        (self.processor, transport_server, protocol_factory)
+    }
+}
+
+impl<P, TS, ITF, OTF, IPF, OPF> ServerBuilder<P, TS, ITF, OTF, IPF, OPF>
+where TS: TransportServer + Send + Sync + 'static,
+      ITF: TransportFactory<SharedTransport<TS::Transport>> + Send + Sync + 'static,
+      OTF: TransportFactory<SharedTransport<TS::Transport>> + Send + Sync + 'static,
+      IPF: ProtocolFactory + Send + Sync + 'static,
+      OPF: ProtocolFactory + Send + Sync + 'static,
+      P: Processor<<SplitProtocolFactory<IPF, OPF> as ProtocolFactory>::Protocol,
+                   <FactoryTransportServer<TS, ITF, OTF> as TransportServer>::Transport>
+         + Send + Sync + 'static {
+    pub fn build_threaded(self) -> ThreadedServer<P, SplitProtocolFactory<IPF, OPF>, FactoryTransportServer<TS, ITF, OTF>> {
+        let (processor, transport_server, protocol_factory) = self.into_parts();
+        ThreadedServer::new(processor, protocol_factory, transport_server)
+    }
+}
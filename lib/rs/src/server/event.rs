@@ -0,0 +1,114 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::any::Any;
+use std::cell::RefCell;
+
+use protocol::Protocol;
+use transport::Transport;
+use processor::Processor;
+use ConnectionContext;
+
+/// Hooks into the lifecycle of a server and of each connection it accepts.
+///
+/// Every connection gets its own `Context`, created when it is accepted and
+/// deleted when it closes. While a message is being processed the context can
+/// be reached from the processor and its handler through `with_context`.
+pub trait ServerEventHandler: Send + Sync {
+    type Context: Any;
+
+    /// Called once before the server starts accepting connections.
+    fn pre_serve(&self) {}
+
+    /// Called for each accepted connection, with what the transport server
+    /// knows about the peer.
+    fn create_context(&self, connection: &ConnectionContext) -> Self::Context;
+
+    /// Called before each message on the connection is processed.
+    fn process_context(&self, _context: &mut Self::Context) {}
+
+    /// Called once the connection is closed.
+    fn delete_context(&self, _context: Self::Context) {}
+}
+
+/// The default handler, which does nothing.
+impl ServerEventHandler for () {
+    type Context = ();
+
+    fn create_context(&self, _connection: &ConnectionContext) {}
+}
+
+thread_local!(static CONTEXT: RefCell<Option<Box<Any>>> = RefCell::new(None));
+
+/// Runs `f` with the context of the connection whose message is being
+/// processed on this thread.
+///
+/// Returns `None` outside of a server, if the context is not a `C`, or when
+/// called from within `f`.
+pub fn with_context<C: Any, R, F: FnOnce(&mut C) -> R>(f: F) -> Option<R> {
+    CONTEXT.with(|slot| {
+        let mut slot = match slot.try_borrow_mut() {
+            Ok(slot) => slot,
+            Err(_) => return None
+        };
+        match *slot {
+            Some(ref mut context) => context.downcast_mut::<C>().map(f),
+            None => None
+        }
+    })
+}
+
+// Deletes the context of a connection once it is served, also when the
+// processor panics.
+struct ContextGuard<'a, H: ServerEventHandler + 'a> {
+    handler: &'a H,
+    // The context, unless it is in the slot while a message is processed.
+    context: Option<Box<Any>>,
+}
+
+impl<'a, H: ServerEventHandler> Drop for ContextGuard<'a, H> {
+    fn drop(&mut self) {
+        let context = match self.context.take() {
+            Some(context) => Some(context),
+            None => CONTEXT.with(|slot| slot.try_borrow_mut().ok().and_then(|mut slot| slot.take()))
+        };
+        if let Some(Ok(context)) = context.map(|context| context.downcast::<H::Context>()) {
+            self.handler.delete_context(*context);
+        }
+    }
+}
+
+/// Serves messages on a connection until it fails or is closed, calling the
+/// connection hooks of `handler` along the way.
+pub fn serve_connection<PR, P, T, H>(processor: &PR, protocol: &mut P, transport: &mut T,
+                                     handler: &H, connection: &ConnectionContext)
+where PR: Processor<P, T>, P: Protocol, T: Transport, H: ServerEventHandler {
+    let context: Box<Any> = Box::new(handler.create_context(connection));
+    let mut guard = ContextGuard { handler: handler, context: Some(context) };
+
+    loop {
+        handler.process_context(guard.context.as_mut().unwrap().downcast_mut().unwrap());
+
+        CONTEXT.with(|slot| *slot.borrow_mut() = guard.context.take());
+        let result = processor.process_in_context(protocol, transport, connection);
+        guard.context = CONTEXT.with(|slot| slot.borrow_mut().take());
+
+        if result.is_err() { break; }
+    }
+}
@@ -19,6 +19,10 @@
 
 pub mod simple_server;
 pub mod threaded;
+pub mod builder;
+mod event;
 
 pub use self::simple_server::SimpleServer;
 pub use self::threaded::ThreadedServer;
+pub use self::builder::ServerBuilder;
+pub use self::event::{ServerEventHandler, with_context};
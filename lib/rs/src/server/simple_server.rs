@@ -21,11 +21,13 @@ use transport::server::TransportServer;
 use transport::Transport;
 use protocol::ProtocolFactory;
 use processor::Processor;
+use super::event::{self, ServerEventHandler};
 
-pub struct SimpleServer<P, PF, TS> {
+pub struct SimpleServer<P, PF, TS, H = ()> {
     processor: P,
     protocol_factory: PF,
     transport_server: TS,
+    event_handler: H,
 }
 
 impl<P, PF: ProtocolFactory, TS: TransportServer> SimpleServer<P, PF, TS>
@@ -36,15 +38,39 @@ where P: Processor<PF::Protocol, TS::Transport>,
         SimpleServer {
             processor: processor,
             protocol_factory: pf,
-            transport_server: transport_server
+            transport_server: transport_server,
+            event_handler: ()
+        }
+    }
+}
+
+impl<P, PF: ProtocolFactory, TS: TransportServer, H: ServerEventHandler> SimpleServer<P, PF, TS, H>
+where P: Processor<PF::Protocol, TS::Transport>,
+      TS::Transport: Transport {
+
+    /// Replaces the server's event handler.
+    pub fn with_event_handler<E: ServerEventHandler>(self, handler: E) -> SimpleServer<P, PF, TS, E> {
+        SimpleServer {
+            processor: self.processor,
+            protocol_factory: self.protocol_factory,
+            transport_server: self.transport_server,
+            event_handler: handler
         }
     }
 
     pub fn serve(&mut self) {
+        self.event_handler.pre_serve();
+
         loop {
-            let mut transport = self.transport_server.accept().unwrap();
+            let (mut transport, mut connection) = self.transport_server.accept().unwrap();
+            if let Err(err) = self.transport_server.establish(&mut transport, &mut connection) {
+                warn!("Failed to establish connection: {}", err);
+                continue;
+            }
+
             let mut protocol = self.protocol_factory.new_protocol();
-            while let Ok(_) = self.processor.process(&mut protocol, &mut transport) { }
+            event::serve_connection(&self.processor, &mut protocol, &mut transport,
+                                    &self.event_handler, &connection);
         }
     }
 }
@@ -24,15 +24,17 @@ use transport::server::TransportServer;
 use transport::Transport;
 use protocol::{Protocol, ProtocolFactory};
 use processor::Processor;
+use super::event::{self, ServerEventHandler};
 
-pub struct ThreadedServer<P, PF, TS> {
-    inner: Arc<ThreadedServerInner<P, PF, TS>>
+pub struct ThreadedServer<P, PF, TS, H = ()> {
+    inner: Arc<ThreadedServerInner<P, PF, TS, H>>
 }
 
-struct ThreadedServerInner<P, PF, TS> {
+struct ThreadedServerInner<P, PF, TS, H> {
     processor: P,
     protocol_factory: PF,
-    transport_server: TS
+    transport_server: TS,
+    event_handler: H
 }
 
 impl<P, PF, TS> ThreadedServer<P, PF, TS>
@@ -48,7 +50,35 @@ where P: Processor<PF::Protocol, TS::Transport> + Send + Sync + 'static,
             inner: Arc::new(ThreadedServerInner {
                 processor: processor,
                 protocol_factory: factory,
-                transport_server: server
+                transport_server: server,
+                event_handler: ()
+            })
+        }
+    }
+}
+
+impl<P, PF, TS, H> ThreadedServer<P, PF, TS, H>
+where P: Processor<PF::Protocol, TS::Transport> + Send + Sync + 'static,
+      TS: TransportServer + Send + Sync + 'static,
+      PF: ProtocolFactory + Send + Sync + 'static,
+      H: ServerEventHandler + 'static,
+      TS::Transport: Transport {
+
+    /// Replaces the server's event handler.
+    ///
+    /// Panics if the server has already been cloned into worker threads.
+    pub fn with_event_handler<E: ServerEventHandler>(self, handler: E) -> ThreadedServer<P, PF, TS, E> {
+        let inner = match Arc::try_unwrap(self.inner) {
+            Ok(inner) => inner,
+            Err(_) => panic!("Can't set the event handler of a running server.")
+        };
+
+        ThreadedServer {
+            inner: Arc::new(ThreadedServerInner {
+                processor: inner.processor,
+                protocol_factory: inner.protocol_factory,
+                transport_server: inner.transport_server,
+                event_handler: handler
             })
         }
     }
@@ -56,6 +86,8 @@ where P: Processor<PF::Protocol, TS::Transport> + Send + Sync + 'static,
     pub fn serve(self, threads: usize) {
         assert!(threads != 0, "Can't accept on 0 threads.");
 
+        self.inner.event_handler.pre_serve();
+
         let (supervisor_tx, supervisor_rx) = mpsc::channel();
 
         for _ in (0..threads) {
@@ -83,11 +115,16 @@ where P: Processor<PF::Protocol, TS::Transport> + Send + Sync + 'static,
                 Sentinel::new(supervisor.clone(), PanicMessage(supervisor));
 
             loop {
-                let mut transport = shared.transport_server.accept().unwrap();
+                let (mut transport, mut connection) = shared.transport_server.accept().unwrap();
+                if let Err(err) = shared.transport_server.establish(&mut transport, &mut connection) {
+                    warn!("Failed to establish connection: {}", err);
+                    continue;
+                }
+
                 let mut protocol = shared.protocol_factory.new_protocol();
 
-                while let Ok(_) =
-                    shared.processor.process(&mut protocol, &mut transport) { }
+                event::serve_connection(&shared.processor, &mut protocol, &mut transport,
+                                        &shared.event_handler, &connection);
             }
         });
     }
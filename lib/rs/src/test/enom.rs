@@ -18,3 +18,69 @@ fn test_enum() {
     assert_eq!(Operation::default(), Operation::Sub);
 }
 
+#[test]
+fn test_enum_keeps_unknown_values() {
+    let mut protocol = encode(&Color::Green);
+    assert_eq!(protocol.log(), &[Prim(I32(2))]);
+    assert_eq!(decode::<Color>(&mut protocol), Color::Green);
+
+    // A value added to the enum after this side was built.
+    let mut protocol = encode(&3);
+    let color = decode::<Color>(&mut protocol);
+    assert_eq!(color, Color::Unknown(3));
+    assert_eq!(i32::from(color), 3);
+    assert_eq!(encode(&color).log(), &[Prim(I32(3))]);
+}
+
+#[test]
+fn test_enum_order() {
+    assert!(Operation::Add < Operation::Sub && Operation::Sub < Operation::Clear);
+
+    // Unknown values order by their number among the known ones.
+    let mut colors = vec![Color::Unknown(3), Color::Green, Color::Unknown(0), Color::Red];
+    colors.sort();
+    assert_eq!(colors, vec![Color::Unknown(0), Color::Red, Color::Green, Color::Unknown(3)]);
+    assert_eq!(Color::Unknown(2), Color::Green);
+}
+
+#[test]
+fn test_strict_enum_rejects_unknown_values() {
+    use protocol::Decode;
+    use {Error, protocol};
+
+    let mut protocol = encode(&7);
+    let mut op = Operation::default();
+    match op.decode(&mut protocol, &mut MockTransport::new(vec![])) {
+        Err(Error::ProtocolError(protocol::Error::ProtocolViolation)) => (),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_enum_names() {
+    assert_eq!(Operation::VALUES, &[Operation::Add, Operation::Sub, Operation::Clear]);
+    assert_eq!(Operation::Clear.name(), "Clear");
+    assert_eq!(Operation::Clear.to_i32(), 3);
+    assert_eq!(Operation::Sub.to_string(), "Sub");
+    assert_eq!("Add".parse::<Operation>(), Ok(Operation::Add));
+
+    let err = "add".parse::<Operation>().unwrap_err();
+    assert_eq!(err.name(), "add");
+    assert_eq!(err.to_string(), "Operation has no value named `add`");
+}
+
+#[test]
+fn test_enum_names_from_thrift_file() {
+    assert_eq!(Color::VALUES, &[Color::Red, Color::Green]);
+    assert_eq!(Color::Green.name(), "green");
+    assert_eq!(Color::Green.to_string(), "green");
+    assert_eq!("red".parse::<Color>(), Ok(Color::Red));
+    assert!("Red".parse::<Color>().is_err());
+
+    // Unknown values go by their number.
+    assert_eq!(Color::Unknown(5).name(), "Unknown");
+    assert_eq!(Color::Unknown(5).to_i32(), 5);
+    assert_eq!(Color::Unknown(5).to_string(), "5");
+    assert_eq!("5".parse::<Color>(), Ok(Color::Unknown(5)));
+    assert_eq!("2".parse::<Color>(), Ok(Color::Green));
+}
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 strukt! {
     name = Simple,
     fields = {
@@ -33,6 +35,29 @@ strukt! {
      }
 }
 
+strukt! {
+     name = OlderMany,
+     fields = {
+         one: i32 => 3,
+     },
+     unknown_fields = unknown
+}
+
+strukt! {
+     name = RetypedMany,
+     fields = {
+         one: i32 => 3,
+         two: i32 => 4,
+     },
+     unknown_fields = unknown
+}
+
+strukt! {
+    name = EmptyKeepingUnknown,
+    fields = {},
+    unknown_fields = unknown
+}
+
 enom! {
     name = Operation,
     values = [
@@ -43,3 +68,81 @@ enom! {
     default = Sub
 }
 
+
+newtype! {
+    name = UserId,
+    inner = i64,
+    derive = [Eq, Hash, PartialOrd, Ord,]
+}
+
+newtype! {
+    name = Timestamp,
+    inner = i64
+}
+
+strukt! {
+    name = Login,
+    fields = {
+        user: UserId => 1,
+        at: Timestamp => 2,
+        addr: ::std::net::Ipv4Addr as Ipv4String => 3,
+    }
+}
+
+// Sends addresses as strings, as `rs.convert = "Ipv4String"` asks.
+pub struct Ipv4String;
+
+impl ::protocol::Convert<::std::net::Ipv4Addr> for Ipv4String {
+    type Thrift = String;
+
+    fn to_thrift(value: &::std::net::Ipv4Addr) -> String {
+        value.to_string()
+    }
+
+    fn from_thrift(value: String) -> ::Result<::std::net::Ipv4Addr> {
+        value.parse().map_err(|_| ::Error::from(::protocol::Error::ProtocolViolation))
+    }
+}
+
+enom! {
+    name = Color,
+    values = [
+        Red = 1 => "red",
+        Green = 2 => "green",
+    ],
+    default = Red,
+    unknown = Unknown
+}
+
+strukt! {
+    name = Point,
+    fields = {
+        x: i32 => 1,
+        y: i32 => 2,
+    },
+    derive = [Eq, Hash, PartialOrd, Ord,]
+}
+
+strukt! {
+    name = Polygon,
+    fields = {
+        points: HashSet<Point> => 1,
+    }
+}
+
+strukt! {
+    name = Account,
+    fields = {
+        id: i64 => 1,
+        name: String => 2,
+        tags: Vec<String> => 3,
+    },
+    derive = [Eq, Hash, PartialOrd, Ord,],
+    builder = AccountBuilder,
+    accessors = [
+        set_id take_id has_id,
+        set_name take_name has_name,
+        set_tags take_tags has_tags,
+    ],
+    required = [id, name,]
+}
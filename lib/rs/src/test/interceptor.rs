@@ -0,0 +1,178 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use compiletest::*;
+use interceptor::{Call, Interceptor, Outcome};
+use protocol::binary_protocol::BinaryProtocol;
+use server::SimpleServer;
+use Error;
+
+struct Recorder {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+    reject: bool,
+    // Told when `after` has run, since a server runs it after the reply.
+    done: Option<Mutex<Sender<()>>>,
+}
+
+impl Recorder {
+    fn new(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> Recorder {
+        Recorder { name: name, log: log.clone(), reject: false, done: None }
+    }
+
+    fn rejecting(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> Recorder {
+        Recorder { name: name, log: log.clone(), reject: true, done: None }
+    }
+
+    fn signalling(mut self, done: Sender<()>) -> Recorder {
+        self.done = Some(Mutex::new(done));
+        self
+    }
+}
+
+impl Interceptor for Recorder {
+    fn before(&self, call: &Call) -> ::Result<()> {
+        self.log.lock().unwrap().push(format!("{} before {} {}", self.name, call.method, call.sequence_id));
+        if self.reject { Err(Error::UserException) } else { Ok(()) }
+    }
+
+    fn after(&self, call: &Call, outcome: Outcome, _elapsed: Duration) {
+        let outcome = if outcome.is_ok() { "ok" } else { "err" };
+        self.log.lock().unwrap().push(format!("{} after {} {} {}", self.name, call.method, call.sequence_id, outcome));
+        if let Some(ref done) = self.done {
+            done.lock().unwrap().send(()).unwrap();
+        }
+    }
+}
+
+struct Handler {
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl SharedService for Handler {
+    fn get_struct(&self, _key: i32) -> SharedServiceGetStructResult {
+        self.log.lock().unwrap().push(String::from("handler"));
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: None }) }
+    }
+}
+
+fn spawn_server<F>(log: &Arc<Mutex<Vec<String>>>, setup: F) -> SocketAddr
+where F: FnOnce(&mut SharedServiceProcessor<Handler>) + Send + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let log = log.clone();
+
+    thread::spawn(move || {
+        let mut processor = SharedServiceProcessor::new(Handler { log: log });
+        setup(&mut processor);
+        SimpleServer::new(processor, listener, || BinaryProtocol::new()).serve();
+    });
+
+    addr
+}
+
+fn entries(log: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+    log.lock().unwrap().clone()
+}
+
+#[test]
+fn test_server_interceptors() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (done, calls) = mpsc::channel();
+    let (first, second) = (Recorder::new("a", &log).signalling(done), Recorder::new("b", &log));
+    let addr = spawn_server(&log, move |processor| {
+        processor.add_interceptor(first);
+        processor.add_interceptor(second);
+    });
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    client.get_struct(1).unwrap();
+    client.get_struct(2).unwrap();
+    calls.recv().unwrap();
+    calls.recv().unwrap();
+
+    assert_eq!(entries(&log), vec![
+        "a before get_struct 1", "b before get_struct 1", "handler",
+        "b after get_struct 1 ok", "a after get_struct 1 ok",
+        "a before get_struct 2", "b before get_struct 2", "handler",
+        "b after get_struct 2 ok", "a after get_struct 2 ok",
+    ]);
+}
+
+#[test]
+fn test_server_interceptor_rejects_call() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (first, second) = (Recorder::new("a", &log), Recorder::rejecting("b", &log));
+    let addr = spawn_server(&log, move |processor| {
+        processor.add_interceptor(first);
+        processor.add_interceptor(second);
+    });
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    match client.get_struct(1) {
+        Err(Error::ApplicationError(ref exception)) => assert_eq!(exception.message, "UserException"),
+        other => panic!("Expected an application exception, got {:?}", other.map(|_| ()))
+    }
+
+    assert_eq!(entries(&log), vec![
+        "a before get_struct 1", "b before get_struct 1", "a after get_struct 1 err",
+    ]);
+
+    // The connection is still usable.
+    match client.get_struct(2) {
+        Err(Error::ApplicationError(_)) => {}
+        other => panic!("Expected an application exception, got {:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn test_client_interceptors() {
+    let server_log = Arc::new(Mutex::new(Vec::new()));
+    let addr = spawn_server(&server_log, |_| {});
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    client.add_interceptor(Recorder::new("a", &log));
+
+    client.get_struct(1).unwrap();
+
+    assert_eq!(entries(&log), vec!["a before get_struct 1", "a after get_struct 1 ok"]);
+}
+
+#[test]
+fn test_client_interceptor_rejects_call() {
+    let server_log = Arc::new(Mutex::new(Vec::new()));
+    let addr = spawn_server(&server_log, |_| {});
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    client.add_interceptor(Recorder::rejecting("a", &log));
+
+    match client.get_struct(1) {
+        Err(Error::UserException) => {}
+        other => panic!("Expected the interceptor's error, got {:?}", other.map(|_| ()))
+    }
+
+    // Nothing was sent, so the connection is still in a known state.
+    assert!(entries(&server_log).is_empty());
+}
+
+#[test]
+fn test_shared_interceptor() {
+    let server_log = Arc::new(Mutex::new(Vec::new()));
+    let addr = spawn_server(&server_log, |_| {});
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let shared = Arc::new(Recorder::new("a", &log));
+
+    for _ in 0..2 {
+        let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+        client.add_interceptor(shared.clone());
+        client.get_struct(1).unwrap();
+    }
+
+    assert_eq!(entries(&log).len(), 4);
+}
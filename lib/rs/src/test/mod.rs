@@ -1,10 +1,16 @@
 use protocol::{Type, Encode, Decode};
+use protocol::binary_protocol::BinaryProtocol;
 use mock::*;
 
 mod prim;
 mod strukt;
 mod enom;
+mod newtype;
 mod generated;
+mod interceptor;
+mod server;
+mod service;
+mod value;
 
 pub fn encode<T: Encode>(x: &T) -> MockProtocol {
     let mut protocol = MockProtocol::new();
@@ -19,6 +25,18 @@ pub fn decode<T: Decode>(protocol: &mut MockProtocol) -> T {
     instance
 }
 
+pub fn to_bytes<T: Encode>(x: &T) -> Vec<u8> {
+    let mut transport = MockTransport::new(vec![]);
+    x.encode(&mut BinaryProtocol::new(), &mut transport).unwrap();
+    transport.written().to_vec()
+}
+
+pub fn from_bytes<T: Decode>(bytes: Vec<u8>) -> T {
+    let mut instance = T::default();
+    instance.decode(&mut BinaryProtocol::new(), &mut MockTransport::new(bytes)).unwrap();
+    instance
+}
+
 pub fn field_end() -> ProtocolAction {
     Field(Begin((String::new(), Type::Stop, 0)))
 }
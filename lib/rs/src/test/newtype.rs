@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+
+use test::*;
+use mock::*;
+use test::generated::*;
+
+use protocol::{Decode, Type};
+use protocol::binary_protocol::BinaryProtocol;
+use {Error, protocol};
+
+#[test]
+fn test_newtype_encodes_as_inner() {
+    let mut protocol = encode(&UserId(7));
+    assert_eq!(protocol.log(), &[Prim(I64(7))]);
+    assert_eq!(decode::<UserId>(&mut protocol), UserId(7));
+
+    let ids: HashSet<UserId> = vec![UserId::from(1), UserId::from(1)].into_iter().collect();
+    assert_eq!(ids.len(), 1);
+}
+
+#[test]
+fn test_struct_with_newtype_and_converted_fields() {
+    let instance = Login {
+        user: Some(UserId(7)),
+        at: Some(Timestamp(1500)),
+        addr: Some(Ipv4Addr::new(127, 0, 0, 1)),
+    };
+    let mut protocol = encode(&instance);
+
+    assert_eq!(protocol.log(), &[
+        Struct(Begin(String::from("Login"))),
+        Field(Begin((String::from("user"), Type::I64, 1))),
+        Prim(I64(7)),
+        Field(End),
+        Field(Begin((String::from("at"), Type::I64, 2))),
+        Prim(I64(1500)),
+        Field(End),
+        Field(Begin((String::from("addr"), Type::String, 3))),
+        Prim(PString(String::from("127.0.0.1"))),
+        Field(End),
+        field_end(),
+        Struct(End)
+    ]);
+
+    let second = decode::<Login>(&mut protocol);
+    assert_eq!(second.user, instance.user);
+    assert_eq!(second.at.unwrap().0, 1500);
+    assert_eq!(second.addr, instance.addr);
+}
+
+strukt! {
+    name = RawLogin,
+    fields = {
+        addr: String => 3,
+    }
+}
+
+#[test]
+fn test_converted_field_rejects_bad_values() {
+    let bytes = to_bytes(&RawLogin { addr: Some(String::from("localhost")) });
+
+    let mut login = Login::default();
+    match login.decode(&mut BinaryProtocol::new(), &mut MockTransport::new(bytes)) {
+        Err(Error::ProtocolError(protocol::Error::ProtocolViolation)) => (),
+        other => panic!("unexpected {:?}", other),
+    }
+}
@@ -26,7 +26,8 @@ test_prim_encode! {
         String::from("goodbye"),
         String::from("garbage"),
         String::from("unicode \u{2600}\u{2601}")
-    ], PString, String }
+    ], PString, String },
+    { test_binary_encode, vec![vec![], vec![0u8, 1, 255]], Binary, Vec<u8> }
 }
 
 #[test]
@@ -0,0 +1,327 @@
+use std::net::{TcpListener, TcpStream};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use compiletest::*;
+use context::{ConnectionContext, PeerAddr};
+use protocol::binary_protocol::BinaryProtocol;
+use server::{self, ServerBuilder, ServerEventHandler, SimpleServer, ThreadedServer};
+use transport::factory::SharedTransport;
+
+use bufstream::BufStream;
+
+struct Connection {
+    peer: Option<PeerAddr>,
+    messages: usize,
+}
+
+struct Events {
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl ServerEventHandler for Events {
+    type Context = Connection;
+
+    fn pre_serve(&self) {
+        self.log.lock().unwrap().push(String::from("pre_serve"));
+    }
+
+    fn create_context(&self, connection: &ConnectionContext) -> Connection {
+        self.log.lock().unwrap().push(String::from("create"));
+        Connection { peer: connection.peer_addr.clone(), messages: 0 }
+    }
+
+    fn process_context(&self, context: &mut Connection) {
+        context.messages += 1;
+    }
+
+    fn delete_context(&self, context: Connection) {
+        // The last process_context precedes the read that finds the
+        // connection closed.
+        self.log.lock().unwrap().push(format!("delete {}", context.messages - 1));
+    }
+}
+
+struct Handler;
+
+impl SharedService for Handler {
+    // Replies with the number of messages seen on the connection and whether
+    // the peer address was known.
+    fn get_struct(&self, _key: i32) -> SharedServiceGetStructResult {
+        let seen = server::with_context(|context: &mut Connection| {
+            vec![vec![vec![vec![context.messages as i32, context.peer.is_some() as i32]]]]
+        });
+
+        let nested = seen.map(|seen| Some(seen).into_iter().collect());
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: nested }) }
+    }
+}
+
+fn call<T: ::Transport>(client: &mut SharedServiceClient<BinaryProtocol, T>) -> Vec<i32> {
+    let nested = client.get_struct(0).unwrap().success.unwrap().nested.unwrap();
+    nested.into_iter().next().unwrap()[0][0][0].clone()
+}
+
+fn wait_for(log: &Arc<Mutex<Vec<String>>>, len: usize) -> Vec<String> {
+    for _ in 0..100 {
+        if log.lock().unwrap().len() >= len { break; }
+        thread::sleep(Duration::from_millis(10));
+    }
+    log.lock().unwrap().clone()
+}
+
+#[test]
+fn simple_server_events() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let events = Events { log: log.clone() };
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(Handler);
+        SimpleServer::new(processor, listener, || BinaryProtocol::new())
+            .with_event_handler(events)
+            .serve();
+    });
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    assert_eq!(call(&mut client), vec![1, 1]);
+    assert_eq!(call(&mut client), vec![2, 1]);
+    drop(client);
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    assert_eq!(call(&mut client), vec![1, 1]);
+
+    assert_eq!(wait_for(&log, 4), vec!["pre_serve", "create", "delete 2", "create"]);
+}
+
+#[test]
+fn threaded_server_events() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let events = Events { log: log.clone() };
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(Handler);
+        ThreadedServer::new(processor, || BinaryProtocol::new(), listener)
+            .with_event_handler(events)
+            .serve(2);
+    });
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    assert_eq!(call(&mut client), vec![1, 1]);
+    drop(client);
+
+    assert_eq!(wait_for(&log, 3), vec!["pre_serve", "create", "delete 1"]);
+}
+
+#[test]
+fn no_context_outside_server() {
+    assert_eq!(server::with_context(|_: &mut Connection| ()), None);
+}
+
+// Replies with whether the context could be reached from within with_context.
+struct NestingHandler;
+
+impl SharedService for NestingHandler {
+    fn get_struct(&self, _key: i32) -> SharedServiceGetStructResult {
+        let nested = server::with_context(|_: &mut Connection| {
+            server::with_context(|_: &mut Connection| ()).is_some()
+        }).unwrap();
+        let nested = Some(vec![vec![vec![vec![nested as i32]]]]).into_iter().collect();
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: Some(nested) }) }
+    }
+}
+
+#[test]
+fn no_context_within_with_context() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let events = Events { log: Arc::new(Mutex::new(Vec::new())) };
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(NestingHandler);
+        SimpleServer::new(processor, listener, || BinaryProtocol::new())
+            .with_event_handler(events)
+            .serve();
+    });
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    assert_eq!(call(&mut client), vec![0]);
+}
+
+struct PanickingHandler;
+
+impl SharedService for PanickingHandler {
+    fn get_struct(&self, _key: i32) -> SharedServiceGetStructResult {
+        panic!("handler failed")
+    }
+}
+
+#[test]
+fn context_deleted_when_processor_panics() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let events = Events { log: log.clone() };
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(PanickingHandler);
+        SimpleServer::new(processor, listener, || BinaryProtocol::new())
+            .with_event_handler(events)
+            .serve();
+    });
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    assert!(client.get_struct(0).is_err());
+    assert_eq!(wait_for(&log, 3), vec!["pre_serve", "create", "delete 0"]);
+}
+
+// Replies with the uid of the peer process, or -1 if it is unknown.
+struct CredentialsHandler;
+
+impl SharedServiceWithContext for CredentialsHandler {
+    fn get_struct(&self, context: &ConnectionContext, _key: i32) -> SharedServiceGetStructResult {
+        let uid = context.credentials.map(|credentials| credentials.uid as i32).unwrap_or(-1);
+        let pid = context.credentials.and_then(|credentials| credentials.pid).unwrap_or(-1);
+        let nested = Some(vec![vec![vec![vec![uid, pid]]]]).into_iter().collect();
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: Some(nested) }) }
+    }
+}
+
+// Replies with the port of the peer, or -1 if it is unknown.
+struct PeerHandler;
+
+impl SharedServiceWithContext for PeerHandler {
+    fn get_struct(&self, context: &ConnectionContext, _key: i32) -> SharedServiceGetStructResult {
+        let port = match context.peer_addr {
+            Some(PeerAddr::Tcp(addr)) => addr.port() as i32,
+            _ => -1
+        };
+        let nested = Some(vec![vec![vec![vec![port]]]]).into_iter().collect();
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: Some(nested) }) }
+    }
+}
+
+#[test]
+fn tcp_peer_address() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(PeerHandler);
+        SimpleServer::new(processor, listener, || BinaryProtocol::new()).serve();
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    let port = stream.local_addr().unwrap().port() as i32;
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), stream);
+    assert_eq!(call(&mut client), vec![port]);
+}
+
+// Counts the bytes written through it.
+struct Counting<T> {
+    inner: T,
+    written: Arc<Mutex<usize>>,
+}
+
+impl<T: Read> Read for Counting<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<T: Write> Write for Counting<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = try!(self.inner.write(buf));
+        *self.written.lock().unwrap() += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn builder_with_transport_factory() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(PeerHandler);
+        ServerBuilder::new(processor, listener)
+            .transport_factory(|transport| BufStream::new(transport))
+            .protocol_factory(|| BinaryProtocol::new())
+            .build()
+            .serve();
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    let port = stream.local_addr().unwrap().port() as i32;
+
+    // The connection context makes it through the transport factories.
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), stream);
+    assert_eq!(call(&mut client), vec![port]);
+    assert_eq!(call(&mut client), vec![port]);
+}
+
+#[test]
+fn builder_with_separate_factories() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let written = Arc::new(Mutex::new(0));
+    let counter = written.clone();
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(PeerHandler);
+        ServerBuilder::new(processor, listener)
+            .input_transport_factory(|transport| BufStream::new(transport))
+            .output_transport_factory(move |transport: SharedTransport<TcpStream>| {
+                Counting { inner: transport, written: counter.clone() }
+            })
+            .input_protocol_factory(|| BinaryProtocol::new())
+            .output_protocol_factory(|| BinaryProtocol::new())
+            .build_threaded()
+            .serve(1);
+    });
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    call(&mut client);
+
+    // Replies go through the output transport, which may still be counting
+    // when the client gets them.
+    for _ in 0..100 {
+        if *written.lock().unwrap() > 0 { return; }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("Nothing was written through the output transport.");
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn unix_peer_credentials() {
+    use std::env;
+    use std::fs;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use libc;
+
+    let path = env::temp_dir().join(format!("thrift-test-{}.sock", unsafe { libc::getpid() }));
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(CredentialsHandler);
+        SimpleServer::new(processor, listener, || BinaryProtocol::new()).serve();
+    });
+
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), UnixStream::connect(&path).unwrap());
+    let expected = unsafe { vec![libc::getuid() as i32, libc::getpid()] };
+    assert_eq!(call(&mut client), expected);
+
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,115 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use context::ConnectionContext;
+use protocol::binary_protocol::BinaryProtocol;
+use server::SimpleServer;
+
+// Three services extending each other across modules, as they would be when
+// generated from three Thrift files.
+mod base {
+    service! {
+        trait_name = Base,
+        context_trait_name = BaseWithContext,
+        processor_name = BaseProcessor,
+        client_name = BaseClient,
+        parents = [],
+        service_methods = [
+            BaseNameArgs -> BaseNameResult = name() -> String => [],
+        ],
+        parent_methods = []
+    }
+}
+
+mod middle {
+    service! {
+        trait_name = Middle,
+        context_trait_name = MiddleWithContext,
+        processor_name = MiddleProcessor,
+        client_name = MiddleClient,
+        parents = [super::base::Base => super::base::BaseWithContext,],
+        service_methods = [
+            MiddleTwiceArgs -> MiddleTwiceResult = twice(x: i32 => 1,) -> i32 => [],
+        ],
+        parent_methods = [
+            (super::base::BaseNameArgs, super::base::BaseNameResult) = name() -> String => [],
+        ]
+    }
+}
+
+mod leaf {
+    service! {
+        trait_name = Leaf,
+        context_trait_name = LeafWithContext,
+        processor_name = LeafProcessor,
+        client_name = LeafClient,
+        parents = [super::middle::Middle => super::middle::MiddleWithContext,],
+        service_methods = [
+            LeafPeerArgs -> LeafPeerResult = peer() -> bool => [],
+        ],
+        parent_methods = [
+            (super::middle::MiddleTwiceArgs, super::middle::MiddleTwiceResult) = twice(x: i32 => 1,) -> i32 => [],
+            (super::base::BaseNameArgs, super::base::BaseNameResult) = name() -> String => [],
+        ],
+        idempotent = [name,]
+    }
+}
+
+use self::base::*;
+use self::leaf::*;
+use self::middle::*;
+
+// A single handler for the whole chain, which wants the connection context
+// for one of the methods only.
+struct Handler;
+
+impl Base for Handler {
+    fn name(&self) -> BaseNameResult {
+        BaseNameResult::success(String::from("handler"))
+    }
+}
+
+impl Middle for Handler {
+    fn twice(&self, x: i32) -> MiddleTwiceResult {
+        MiddleTwiceResult::success(2 * x)
+    }
+}
+
+impl LeafWithContext for Handler {
+    fn peer(&self, context: &ConnectionContext) -> LeafPeerResult {
+        LeafPeerResult::success(context.peer_addr.is_some())
+    }
+}
+
+fn spawn_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let processor = LeafProcessor::new(Handler);
+        SimpleServer::new(processor, listener, || BinaryProtocol::new()).serve();
+    });
+
+    addr
+}
+
+#[test]
+fn test_one_handler_serves_inherited_methods() {
+    let addr = spawn_server();
+
+    let mut client = LeafClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    assert_eq!(client.peer().unwrap().success, Some(true));
+    assert_eq!(client.twice(21).unwrap().success, Some(42));
+    assert_eq!(client.name().unwrap().success, Some(String::from("handler")));
+    drop(client);
+
+    // Clients of the parents talk to the same processor.
+    let mut client = BaseClient::new(BinaryProtocol::new(), TcpStream::connect(addr).unwrap());
+    assert_eq!(client.name().unwrap().success, Some(String::from("handler")));
+}
+
+#[test]
+fn test_inherited_idempotent_methods() {
+    assert!(LeafClient::<BinaryProtocol, TcpStream>::is_idempotent("name"));
+    assert!(!LeafClient::<BinaryProtocol, TcpStream>::is_idempotent("twice"));
+}
@@ -3,6 +3,7 @@ use mock::*;
 use test::generated::*;
 
 use protocol::Type;
+use value::Value;
 
 #[test]
 fn test_simple_struct() {
@@ -197,3 +198,71 @@ fn test_struct_with_many_fields() {
 //     assert_eq!(instance.this, second.this);
 // }
 
+
+#[test]
+fn test_struct_keeps_unknown_fields() {
+    let instance = Many {
+        one: Some(17),
+        two: Some(String::from("Some String")),
+        three: Some(vec![Simple { key: Some(String::from("A String")) }])
+    };
+    let bytes = to_bytes(&instance);
+
+    let older = from_bytes::<OlderMany>(bytes.clone());
+    assert_eq!(older.one, Some(17));
+    assert_eq!(older.unknown.keys().cloned().collect::<Vec<_>>(), vec![4, 9]);
+    assert_eq!(to_bytes(&older), bytes);
+
+    let empty = from_bytes::<EmptyKeepingUnknown>(bytes.clone());
+    assert_eq!(empty.unknown.len(), 3);
+    assert_eq!(to_bytes(&empty), bytes);
+}
+
+#[test]
+fn test_struct_drops_retyped_fields() {
+    let instance = Many { one: Some(17), two: Some(String::from("Some String")), three: None };
+    let retyped = from_bytes::<RetypedMany>(to_bytes(&instance));
+    assert_eq!(retyped.one, Some(17));
+    assert_eq!(retyped.two, None);
+    assert!(retyped.unknown.is_empty());
+
+    // Unknown fields never shadow declared ones.
+    let mut older = OlderMany { one: Some(17), unknown: Default::default() };
+    older.unknown.insert(3, Value::I32(18));
+    assert_eq!(from_bytes::<OlderMany>(to_bytes(&older)).one, Some(17));
+    assert_eq!(to_bytes(&older), to_bytes(&OlderMany { one: Some(17), unknown: Default::default() }));
+}
+
+#[test]
+fn test_struct_set_members() {
+    let a = Point { x: Some(1), y: Some(2) };
+    let b = Point { x: Some(1), y: None };
+    assert!(b < a);
+
+    let instance = Polygon { points: Some(vec![a.clone(), b.clone(), a.clone()].into_iter().collect()) };
+    assert_eq!(instance.points.as_ref().unwrap().len(), 2);
+    assert_eq!(from_bytes::<Polygon>(to_bytes(&instance)), instance);
+    assert!(from_bytes::<Polygon>(to_bytes(&instance)) != Polygon::default());
+}
+
+#[test]
+fn test_struct_builder() {
+    let account = Account::builder().id(7).name(String::from("seven")).build().unwrap();
+    assert_eq!(account, Account { id: Some(7), name: Some(String::from("seven")), tags: None });
+
+    let err = Account::builder().name(String::from("nobody")).build().unwrap_err();
+    assert_eq!(err.field(), "id");
+    assert_eq!(err.to_string(), "Account is missing the required field `id`");
+}
+
+#[test]
+fn test_struct_accessors() {
+    let mut account = Account::default();
+    assert!(!account.has_tags());
+
+    account.set_tags(vec![String::from("a")]);
+    assert!(account.has_tags());
+    assert_eq!(account.take_tags(), Some(vec![String::from("a")]));
+    assert!(!account.has_tags());
+    assert_eq!(account.take_tags(), None);
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use test::{to_bytes, from_bytes};
+use compiletest::*;
+use mock::MockTransport;
+use protocol::binary_protocol::BinaryProtocol;
+use protocol::{Decode, Type};
+use value::Value;
+
+fn references_other() -> ReferencesOther {
+    let mut map = HashMap::new();
+    map.insert(1, vec![String::from("one")]);
+    map.insert(2, vec![]);
+    ReferencesOther {
+        other: Some(DeeplyNested { nested: Some(vec![vec![vec![vec![vec![1, 2]], vec![]]]].into_iter().collect()) }),
+        another: Some(Simple { key: Some(String::from("key")) }),
+        map: Some(map),
+    }
+}
+
+#[test]
+fn test_value_roundtrip() {
+    let bytes = to_bytes(&references_other());
+    let value: Value = from_bytes(bytes.clone());
+    assert_eq!(to_bytes(&value), bytes);
+}
+
+#[test]
+fn test_value_fields() {
+    let value: Value = from_bytes(to_bytes(&Simple { key: Some(String::from("key")) }));
+    assert_eq!(value, Value::Struct(vec![(16, Value::Binary(b"key".to_vec()))]));
+}
+
+#[test]
+fn test_value_keeps_field_order() {
+    let value = Value::Struct(vec![(9, Value::I32(1)), (3, Value::Bool(true)), (9, Value::I32(2))]);
+    let bytes = to_bytes(&value);
+    let decoded: Value = from_bytes(bytes.clone());
+    assert_eq!(decoded, value);
+    assert_eq!(to_bytes(&decoded), bytes);
+}
+
+#[test]
+fn test_value_edit() {
+    let mut value: Value = from_bytes(to_bytes(&references_other()));
+    if let Value::Struct(ref mut fields) = value {
+        for &mut (id, ref mut field) in fields.iter_mut() {
+            if id == 3 {
+                *field = Value::Struct(vec![(16, Value::Binary(b"edited".to_vec()))]);
+            }
+        }
+        fields.retain(|&(id, _)| id != 4);
+    }
+
+    let edited: ReferencesOther = from_bytes(to_bytes(&value));
+    assert_eq!(edited.other.unwrap().nested, references_other().other.unwrap().nested);
+    assert_eq!(edited.another.unwrap().key, Some(String::from("edited")));
+    assert!(edited.map.is_none());
+}
+
+#[test]
+fn test_value_empty_containers() {
+    let mut value = Value::List(Type::Double, vec![]);
+    let bytes = to_bytes(&value);
+    value.decode(&mut BinaryProtocol::new(), &mut MockTransport::new(bytes)).unwrap();
+    assert_eq!(value, Value::List(Type::Double, vec![]));
+}
@@ -0,0 +1,165 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use context::ConnectionContext;
+use super::Transport;
+use super::server::TransportServer;
+
+/// Wraps the transports accepted by a server, for instance in buffering.
+pub trait TransportFactory<T: Transport> {
+    type Transport: Transport;
+
+    fn new_transport(&self, transport: T) -> Self::Transport;
+}
+
+impl<F, T: Transport, U: Transport> TransportFactory<T> for F where F: Fn(T) -> U {
+    type Transport = U;
+
+    fn new_transport(&self, transport: T) -> U {
+        (*self)(transport)
+    }
+}
+
+/// The factory that uses transports as they are.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct IdentityTransportFactory;
+
+impl<T: Transport> TransportFactory<T> for IdentityTransportFactory {
+    type Transport = T;
+
+    fn new_transport(&self, transport: T) -> T {
+        transport
+    }
+}
+
+/// A handle to a transport shared by the input and output sides of a
+/// connection.
+pub struct SharedTransport<T> {
+    inner: Rc<RefCell<T>>,
+}
+
+impl<T> SharedTransport<T> {
+    pub fn new(transport: T) -> SharedTransport<T> {
+        SharedTransport { inner: Rc::new(RefCell::new(transport)) }
+    }
+}
+
+impl<T> Clone for SharedTransport<T> {
+    fn clone(&self) -> SharedTransport<T> {
+        SharedTransport { inner: self.inner.clone() }
+    }
+}
+
+impl<T: Read> Read for SharedTransport<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.borrow_mut().read(buf)
+    }
+}
+
+impl<T: Write> Write for SharedTransport<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.borrow_mut().flush()
+    }
+}
+
+/// A transport reading from one transport and writing to another.
+pub struct SplitTransport<I, O> {
+    input: I,
+    output: O,
+}
+
+impl<I: Read, O: Write> SplitTransport<I, O> {
+    pub fn new(input: I, output: O) -> SplitTransport<I, O> {
+        SplitTransport {
+            input: input,
+            output: output,
+        }
+    }
+
+    pub fn into_inner(self) -> (I, O) {
+        (self.input, self.output)
+    }
+}
+
+impl<I: Read, O> Read for SplitTransport<I, O> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl<I, O: Write> Write for SplitTransport<I, O> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Wraps the connections accepted by a transport server with an input and an
+/// output transport factory.
+///
+/// Both factories are handed a `SharedTransport` over the same connection.
+/// The resulting transport reads through the input transport and writes
+/// through the output transport.
+pub struct FactoryTransportServer<TS, I, O> {
+    server: TS,
+    input: I,
+    output: O,
+}
+
+impl<TS, I, O> FactoryTransportServer<TS, I, O>
+where TS: TransportServer,
+      I: TransportFactory<SharedTransport<TS::Transport>>,
+      O: TransportFactory<SharedTransport<TS::Transport>> {
+    pub fn new(server: TS, input: I, output: O) -> FactoryTransportServer<TS, I, O> {
+        FactoryTransportServer {
+            server: server,
+            input: input,
+            output: output,
+        }
+    }
+}
+
+impl<TS, I, O> TransportServer for FactoryTransportServer<TS, I, O>
+where TS: TransportServer,
+      I: TransportFactory<SharedTransport<TS::Transport>>,
+      O: TransportFactory<SharedTransport<TS::Transport>> {
+    type Transport = SplitTransport<I::Transport, O::Transport>;
+
+    // The factories may start using the connection as soon as they wrap it,
+    // so it is established first and the wrapped transport needs nothing more.
+    fn accept(&self) -> io::Result<(Self::Transport, ConnectionContext)> {
+        let (mut transport, mut context) = try!(self.server.accept());
+        try!(self.server.establish(&mut transport, &mut context));
+        let shared = SharedTransport::new(transport);
+        let input = self.input.new_transport(shared.clone());
+        let output = self.output.new_transport(shared);
+        Ok((SplitTransport::new(input, output), context))
+    }
+}
@@ -0,0 +1,353 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io::{self, BufRead, Read, Write};
+use std::net::TcpStream;
+
+use bufstream::BufStream;
+
+use protocol::Protocol;
+use processor::Processor;
+use Result;
+
+static THRIFT_CONTENT_TYPE: &'static str = "application/x-thrift";
+
+/// A client transport that sends each message as the body of an HTTP POST.
+///
+/// Writes are buffered until `flush`, which posts the buffered message and
+/// makes the response body available to subsequent reads.
+pub struct HttpClientTransport {
+    addr: String,
+    host: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    keep_alive: bool,
+    retry_unanswered: bool,
+    stream: Option<BufStream<TcpStream>>,
+    write_buffer: Vec<u8>,
+    read_buffer: io::Cursor<Vec<u8>>,
+}
+
+impl HttpClientTransport {
+    /// Creates a transport posting to `url`, which must be of the form
+    /// `http://host[:port][/path]`, with IPv6 hosts in brackets. No connection
+    /// is made until the first flush.
+    pub fn new(url: &str) -> io::Result<HttpClientTransport> {
+        let rest = match url.find("://") {
+            Some(i) if &url[..i] == "http" => &url[i + 3..],
+            Some(_) => return Err(invalid_input("only http:// urls are supported")),
+            None => url,
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+
+        if authority.is_empty() {
+            return Err(invalid_input("missing host in url"));
+        }
+
+        let host_end = if authority.starts_with('[') {
+            match authority.find(']') {
+                Some(i) => i + 1,
+                None => return Err(invalid_input("unterminated IPv6 address in url")),
+            }
+        } else {
+            authority.find(':').unwrap_or(authority.len())
+        };
+
+        let addr = match &authority[host_end..] {
+            "" => format!("{}:80", authority),
+            port if port.starts_with(':') => String::from(authority),
+            _ => return Err(invalid_input("malformed host in url")),
+        };
+
+        Ok(HttpClientTransport {
+            addr: addr,
+            host: String::from(authority),
+            path: String::from(path),
+            headers: Vec::new(),
+            keep_alive: true,
+            retry_unanswered: false,
+            stream: None,
+            write_buffer: Vec::new(),
+            read_buffer: io::Cursor::new(Vec::new()),
+        })
+    }
+
+    /// Adds a header sent with every request.
+    pub fn add_header(&mut self, name: &str, value: &str) {
+        self.headers.push((String::from(name), String::from(value)));
+    }
+
+    /// Whether the connection is reused across requests. Defaults to `true`.
+    pub fn set_keep_alive(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+        if !keep_alive {
+            self.stream = None;
+        }
+    }
+
+    /// Whether a request is sent again on a fresh connection when a kept-alive
+    /// connection closes after the request was sent, without any of the
+    /// response. Defaults to `false`.
+    ///
+    /// This is usually a connection the server closed while idle, but the
+    /// server may also have processed the request and failed before
+    /// responding, so only enable this for idempotent calls.
+    pub fn set_retry_unanswered(&mut self, retry: bool) {
+        self.retry_unanswered = retry;
+    }
+
+    fn connect(&self) -> io::Result<BufStream<TcpStream>> {
+        Ok(BufStream::new(try!(TcpStream::connect(&self.addr[..]))))
+    }
+
+    fn post(&mut self) -> io::Result<Vec<u8>> {
+        // A kept-alive connection may have been closed by the server while
+        // idle. The request is sent again on a fresh one only if the server
+        // cannot have seen all of it, unless `retry_unanswered` is set.
+        if let Some(mut stream) = self.stream.take() {
+            match self.send(&mut stream) {
+                Ok(()) => match wait_for_response(&mut stream) {
+                    Ok(()) => return self.receive(stream),
+                    Err(ref err) if self.retry_unanswered && is_stale(err) => {}
+                    Err(err) => return Err(err),
+                },
+                Err(ref err) if is_stale(err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut stream = try!(self.connect());
+        try!(self.send(&mut stream));
+        self.receive(stream)
+    }
+
+    fn send(&self, stream: &mut BufStream<TcpStream>) -> io::Result<()> {
+        try!(write!(stream, "POST {} HTTP/1.1\r\n", self.path));
+        try!(write!(stream, "Host: {}\r\n", self.host));
+        try!(write!(stream, "Content-Type: {}\r\n", THRIFT_CONTENT_TYPE));
+        try!(write!(stream, "Accept: {}\r\n", THRIFT_CONTENT_TYPE));
+        try!(write!(stream, "Content-Length: {}\r\n", self.write_buffer.len()));
+        try!(write!(stream, "Connection: {}\r\n",
+                    if self.keep_alive { "keep-alive" } else { "close" }));
+        for &(ref name, ref value) in &self.headers {
+            try!(write!(stream, "{}: {}\r\n", name, value));
+        }
+        try!(stream.write_all(b"\r\n"));
+        try!(stream.write_all(&self.write_buffer));
+        stream.flush()
+    }
+
+    fn receive(&mut self, mut stream: BufStream<TcpStream>) -> io::Result<Vec<u8>> {
+        let (body, reusable) = try!(self.read_response(&mut stream));
+        if reusable { self.stream = Some(stream); }
+        Ok(body)
+    }
+
+    fn read_response(&self, stream: &mut BufStream<TcpStream>) -> io::Result<(Vec<u8>, bool)> {
+        // Interim responses such as `100 Continue` only have headers, and
+        // precede the final one.
+        let mut status = try!(read_line(stream));
+        while status.split(' ').nth(1).map(|code| code.starts_with('1')).unwrap_or(false) {
+            while !try!(read_line(stream)).is_empty() { }
+            status = try!(read_line(stream));
+        }
+
+        let code = status.split(' ').nth(1).unwrap_or("");
+        if code != "200" {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      format!("unexpected HTTP status: {}", status)));
+        }
+
+        let mut content_length = None;
+        let mut chunked = false;
+        let mut close = !self.keep_alive || status.starts_with("HTTP/1.0");
+
+        loop {
+            let line = try!(read_line(stream));
+            if line.is_empty() { break; }
+
+            let (name, value) = match line.find(':') {
+                Some(i) => (line[..i].trim().to_lowercase(), line[i + 1..].trim().to_lowercase()),
+                None => return Err(invalid_data("malformed HTTP header")),
+            };
+
+            match &name[..] {
+                "content-length" => match value.parse::<u64>() {
+                    Ok(len) => content_length = Some(len),
+                    Err(_) => return Err(invalid_data("malformed Content-Length")),
+                },
+                "transfer-encoding" => chunked = value.contains("chunked"),
+                "connection" if value == "close" => close = true,
+                "connection" if value == "keep-alive" => close = false,
+                _ => {}
+            }
+        }
+
+        let mut body = Vec::new();
+        if chunked {
+            try!(read_chunked(stream, &mut body));
+        } else if let Some(len) = content_length {
+            try!(Read::by_ref(stream).take(len).read_to_end(&mut body));
+            if (body.len() as u64) < len {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated HTTP body"));
+            }
+        } else {
+            // Without framing the body extends to the end of the connection.
+            try!(stream.read_to_end(&mut body));
+            close = true;
+        }
+
+        Ok((body, !close))
+    }
+}
+
+impl Read for HttpClientTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_buffer.read(buf)
+    }
+}
+
+impl Write for HttpClientTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_buffer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.write_buffer.is_empty() {
+            return Ok(());
+        }
+
+        let result = self.post();
+        self.write_buffer.clear();
+        self.read_buffer = io::Cursor::new(try!(result));
+        Ok(())
+    }
+}
+
+/// The server side of an HTTP exchange: reads come from the request body and
+/// writes go to the response body.
+///
+/// This lets any HTTP server drive a `Processor`, see `process`.
+pub struct HttpServerTransport<R, W> {
+    request: R,
+    response: W,
+}
+
+impl<R: Read, W: Write> HttpServerTransport<R, W> {
+    pub fn new(request: R, response: W) -> HttpServerTransport<R, W> {
+        HttpServerTransport {
+            request: request,
+            response: response,
+        }
+    }
+
+    pub fn into_inner(self) -> (R, W) {
+        (self.request, self.response)
+    }
+}
+
+impl<R: Read, W> Read for HttpServerTransport<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.request.read(buf)
+    }
+}
+
+impl<R, W: Write> Write for HttpServerTransport<R, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.response.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.response.flush()
+    }
+}
+
+/// Runs `processor` over a single HTTP request body, writing the reply into
+/// the response body.
+pub fn process<PR, P, R, W>(processor: &PR, protocol: &mut P, request: R, response: W) -> Result<()>
+where PR: Processor<P, HttpServerTransport<R, W>>, P: Protocol, R: Read, W: Write {
+    let mut transport = HttpServerTransport::new(request, response);
+    processor.process(protocol, &mut transport)
+}
+
+fn read_line<B: BufRead>(stream: &mut B) -> io::Result<String> {
+    let mut line = String::new();
+    if try!(stream.read_line(&mut line)) == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+    }
+
+    let len = line.trim_right_matches(|c| c == '\r' || c == '\n').len();
+    line.truncate(len);
+    Ok(line)
+}
+
+fn read_chunked<B: BufRead>(stream: &mut B, body: &mut Vec<u8>) -> io::Result<()> {
+    loop {
+        let line = try!(read_line(stream));
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = match u64::from_str_radix(size, 16) {
+            Ok(size) => size,
+            Err(_) => return Err(invalid_data("malformed chunk size")),
+        };
+
+        if size == 0 {
+            // Skip any trailers up to the terminating empty line.
+            while !try!(read_line(stream)).is_empty() { }
+            return Ok(());
+        }
+
+        let start = body.len();
+        try!(stream.by_ref().take(size).read_to_end(body));
+        if ((body.len() - start) as u64) < size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated HTTP chunk"));
+        }
+        try!(read_line(stream));
+    }
+}
+
+// Fails if the connection closes before the first byte of the response.
+fn wait_for_response<B: BufRead>(stream: &mut B) -> io::Result<()> {
+    if try!(stream.fill_buf()).is_empty() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+    }
+    Ok(())
+}
+
+fn is_stale(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset |
+        io::ErrorKind::ConnectionAborted | io::ErrorKind::UnexpectedEof => true,
+        _ => false
+    }
+}
+
+fn invalid_input(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod test;
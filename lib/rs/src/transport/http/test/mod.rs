@@ -0,0 +1,228 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+
+use super::{process, HttpClientTransport};
+
+use protocol::binary_protocol::BinaryProtocol;
+use compiletest::*;
+use Error;
+
+struct Handler;
+
+impl SharedService for Handler {
+    fn get_struct(&self, key: i32) -> SharedServiceGetStructResult {
+        let mut nested = HashSet::new();
+        nested.insert(vec![vec![vec![vec![key]]]]);
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: Some(nested) }) }
+    }
+}
+
+struct Request {
+    connection: usize,
+    headers: Vec<String>,
+}
+
+// A minimal HTTP/1.1 server: serves every request of every connection with
+// the processor and reports which connection each request arrived on.
+fn spawn_server(status: &'static str) -> (String, mpsc::Receiver<Request>) {
+    spawn_server_with(status, "", usize::max_value())
+}
+
+// Like `spawn_server`, sending `interim` before each response if it isn't
+// empty, and closing connections after `per_connection` requests.
+fn spawn_server_with(status: &'static str, interim: &'static str, per_connection: usize)
+                     -> (String, mpsc::Receiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/thrift", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(Handler);
+
+        for (connection, stream) in listener.incoming().enumerate() {
+            let stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+
+            for _ in 0..per_connection {
+                let (headers, body) = match read_request(&mut reader) {
+                    Some(request) => request,
+                    None => break,
+                };
+
+                let mut response = Vec::new();
+                if status == "200 OK" {
+                    process(&processor, &mut BinaryProtocol::new(), &body[..], &mut response).unwrap();
+                }
+
+                if !interim.is_empty() {
+                    write!(writer, "HTTP/1.1 {}\r\n\r\n", interim).unwrap();
+                }
+                write!(writer, "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n", status, response.len()).unwrap();
+                writer.write_all(&response).unwrap();
+                let _ = tx.send(Request { connection: connection, headers: headers });
+            }
+        }
+    });
+
+    (url, rx)
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<(Vec<String>, Vec<u8>)> {
+    let mut headers = Vec::new();
+    let mut length = 0;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 { return None; }
+
+        let line = line.trim().to_string();
+        if line.is_empty() { break; }
+        if line.to_lowercase().starts_with("content-length:") {
+            length = line[15..].trim().parse().unwrap();
+        }
+        headers.push(line);
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    Some((headers, body))
+}
+
+fn client(url: &str) -> SharedServiceClient<BinaryProtocol, HttpClientTransport> {
+    SharedServiceClient::new(BinaryProtocol::new(), HttpClientTransport::new(url).unwrap())
+}
+
+#[test]
+fn call_over_http() {
+    let (url, requests) = spawn_server("200 OK");
+    let mut client = client(&url);
+
+    let result = client.get_struct(42).unwrap();
+    let nested = result.success.unwrap().nested.unwrap();
+    assert!(nested.contains(&vec![vec![vec![vec![42]]]]));
+
+    let request = requests.recv().unwrap();
+    assert_eq!(request.headers[0], "POST /thrift HTTP/1.1");
+    assert!(request.headers.contains(&"Content-Type: application/x-thrift".to_string()));
+}
+
+#[test]
+fn custom_headers() {
+    let (url, requests) = spawn_server("200 OK");
+    let mut client = client(&url);
+    client.transport.add_header("Authorization", "Bearer secret");
+
+    client.get_struct(1).unwrap();
+
+    let request = requests.recv().unwrap();
+    assert!(request.headers.contains(&"Authorization: Bearer secret".to_string()));
+}
+
+#[test]
+fn keep_alive_reuses_connection() {
+    let (url, requests) = spawn_server("200 OK");
+    let mut client = client(&url);
+
+    client.get_struct(1).unwrap();
+    client.get_struct(2).unwrap();
+
+    assert_eq!(requests.recv().unwrap().connection, 0);
+    assert_eq!(requests.recv().unwrap().connection, 0);
+}
+
+#[test]
+fn no_keep_alive_reconnects() {
+    let (url, requests) = spawn_server("200 OK");
+    let mut client = client(&url);
+    client.transport.set_keep_alive(false);
+
+    client.get_struct(1).unwrap();
+    client.get_struct(2).unwrap();
+
+    let first = requests.recv().unwrap();
+    let second = requests.recv().unwrap();
+    assert!(first.headers.contains(&"Connection: close".to_string()));
+    assert!(first.connection != second.connection);
+}
+
+#[test]
+fn no_resend_after_request_was_sent() {
+    let (url, _requests) = spawn_server_with("200 OK", "", 1);
+    let mut client = client(&url);
+
+    // The server may have processed the second call before closing.
+    client.get_struct(1).unwrap();
+    assert!(client.get_struct(2).is_err());
+    client.get_struct(3).unwrap();
+}
+
+#[test]
+fn resend_unanswered_on_request() {
+    let (url, requests) = spawn_server_with("200 OK", "", 1);
+    let mut client = client(&url);
+    client.transport.set_retry_unanswered(true);
+
+    client.get_struct(1).unwrap();
+    client.get_struct(2).unwrap();
+    assert_eq!(requests.recv().unwrap().connection, 0);
+    assert_eq!(requests.recv().unwrap().connection, 1);
+}
+
+#[test]
+fn skips_interim_responses() {
+    let (url, _requests) = spawn_server_with("200 OK", "100 Continue", usize::max_value());
+    let mut client = client(&url);
+
+    assert!(client.get_struct(1).unwrap().success.is_some());
+    assert!(client.get_struct(2).unwrap().success.is_some());
+}
+
+#[test]
+fn error_status() {
+    let (url, _requests) = spawn_server("500 Internal Server Error");
+    let mut client = client(&url);
+
+    match client.get_struct(1) {
+        Err(Error::TransportError(_)) => {}
+        other => panic!("Expected a transport error, got {:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn invalid_url() {
+    assert!(HttpClientTransport::new("https://example.com/").is_err());
+    assert!(HttpClientTransport::new("http:///path").is_err());
+}
+
+#[test]
+fn ipv6_urls() {
+    assert_eq!(HttpClientTransport::new("http://[::1]/thrift").unwrap().addr, "[::1]:80");
+    assert_eq!(HttpClientTransport::new("http://[::1]:9090/thrift").unwrap().addr, "[::1]:9090");
+    assert_eq!(HttpClientTransport::new("http://[::1]:9090").unwrap().host, "[::1]:9090");
+    assert_eq!(HttpClientTransport::new("http://localhost").unwrap().addr, "localhost:80");
+    assert!(HttpClientTransport::new("http://[::1/thrift").is_err());
+    assert!(HttpClientTransport::new("http://[::1]x/thrift").is_err());
+}
@@ -20,6 +20,16 @@
 use std::io::{Read, Write};
 
 pub mod server;
+pub mod factory;
+pub mod http;
+pub mod reconnecting;
+pub mod timeout;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+pub use self::factory::TransportFactory;
+pub use self::reconnecting::ReconnectingTransport;
+pub use self::timeout::SetTimeout;
 
 pub trait Transport: Write + Read { }
 
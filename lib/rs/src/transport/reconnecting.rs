@@ -0,0 +1,134 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use super::{SetTimeout, Transport};
+
+/// A transport that dials a new connection through `connector` whenever the
+/// previous one has failed.
+///
+/// Any I/O error, or end of stream on a read, drops the current connection
+/// and is returned to the caller as usual, so the call in progress still
+/// fails. The next read or write then re-dials. `Interrupted` and
+/// `WouldBlock` say nothing about the connection, which is kept so that the
+/// caller can resume where it was. Combined with a
+/// `client::RetryPolicy` this lets a client survive server restarts.
+pub struct ReconnectingTransport<T, F> {
+    connector: F,
+    inner: Option<T>,
+}
+
+impl<T: Transport, F: Fn() -> io::Result<T>> ReconnectingTransport<T, F> {
+    /// Creates the transport without connecting; the first connection is made
+    /// on first use.
+    pub fn new(connector: F) -> ReconnectingTransport<T, F> {
+        ReconnectingTransport {
+            connector: connector,
+            inner: None,
+        }
+    }
+
+    /// Creates the transport and connects immediately.
+    pub fn connect(connector: F) -> io::Result<ReconnectingTransport<T, F>> {
+        let inner = try!(connector());
+        Ok(ReconnectingTransport {
+            connector: connector,
+            inner: Some(inner),
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Drops the current connection, if any, so that the next use re-dials.
+    pub fn disconnect(&mut self) {
+        self.inner = None;
+    }
+
+    pub fn get_ref(&self) -> Option<&T> {
+        self.inner.as_ref()
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.inner.as_mut()
+    }
+
+    fn inner(&mut self) -> io::Result<&mut T> {
+        if self.inner.is_none() {
+            self.inner = Some(try!((self.connector)()));
+        }
+        Ok(self.inner.as_mut().unwrap())
+    }
+}
+
+impl<T: Transport, F: Fn() -> io::Result<T>> Read for ReconnectingTransport<T, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = try!(self.inner()).read(buf);
+        match result {
+            Ok(0) if !buf.is_empty() => self.disconnect(),
+            Err(ref err) if is_connection_error(err) => self.disconnect(),
+            _ => {}
+        }
+        result
+    }
+}
+
+impl<T: Transport, F: Fn() -> io::Result<T>> Write for ReconnectingTransport<T, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = try!(self.inner()).write(buf);
+        if let Err(ref err) = result {
+            if is_connection_error(err) { self.disconnect(); }
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = try!(self.inner()).flush();
+        if let Err(ref err) = result {
+            if is_connection_error(err) { self.disconnect(); }
+        }
+        result
+    }
+}
+
+impl<T, F> SetTimeout for ReconnectingTransport<T, F>
+where T: SetTimeout + Transport, F: Fn() -> io::Result<T> {
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        // Connect first so that the timeout also bounds the first read or write.
+        if self.inner.is_none() && timeout.is_none() {
+            return Ok(());
+        }
+        try!(self.inner()).set_timeout(timeout)
+    }
+
+    fn abandon(&mut self) -> bool {
+        self.disconnect();
+        true
+    }
+}
+
+fn is_connection_error(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock => false,
+        _ => true
+    }
+}
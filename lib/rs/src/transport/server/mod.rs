@@ -20,17 +20,36 @@
 use std::io;
 use std::net::{TcpListener, TcpStream};
 use super::Transport;
+use context::{ConnectionContext, PeerAddr};
+
+#[cfg(unix)]
+mod unix;
 
 pub trait TransportServer {
     type Transport: Transport;
 
-    fn accept(&self) -> io::Result<Self::Transport>;
+    /// Accepts a connection, along with what is known about its peer.
+    ///
+    /// This runs on the thread accepting connections, so anything that
+    /// waits on the peer belongs in `establish` instead.
+    fn accept(&self) -> io::Result<(Self::Transport, ConnectionContext)>;
+
+    /// Completes the setup of an accepted connection, such as a TLS
+    /// handshake, on the thread about to serve it, adding what it learns
+    /// about the peer to `context`. Connections for which it fails are
+    /// dropped.
+    fn establish(&self, _transport: &mut Self::Transport, _context: &mut ConnectionContext)
+                 -> io::Result<()> {
+        Ok(())
+    }
 }
 
 impl TransportServer for TcpListener {
     type Transport = TcpStream;
 
-    fn accept(&self) -> io::Result<TcpStream> {
-        self.accept().map(|res| res.0)
+    fn accept(&self) -> io::Result<(TcpStream, ConnectionContext)> {
+        let (stream, addr) = try!(self.accept());
+        let context = ConnectionContext { peer_addr: Some(PeerAddr::Tcp(addr)), ..ConnectionContext::new() };
+        Ok((stream, context))
     }
 }
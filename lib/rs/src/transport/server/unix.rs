@@ -0,0 +1,79 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use context::{ConnectionContext, Credentials, PeerAddr};
+use super::TransportServer;
+
+impl TransportServer for UnixListener {
+    type Transport = UnixStream;
+
+    fn accept(&self) -> io::Result<(UnixStream, ConnectionContext)> {
+        let (stream, addr) = try!(self.accept());
+        let context = ConnectionContext {
+            peer_addr: Some(PeerAddr::Unix(addr.as_pathname().map(|path| path.to_path_buf()))),
+            credentials: peer_credentials(&stream).ok(),
+            ..ConnectionContext::new()
+        };
+        Ok((stream, context))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_credentials(stream: &UnixStream) -> io::Result<Credentials> {
+    use std::mem;
+    use libc::{self, c_void, socklen_t, ucred};
+
+    let mut cred: ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<ucred>() as socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED,
+                         &mut cred as *mut ucred as *mut c_void, &mut len)
+    };
+
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Credentials { uid: cred.uid, gid: cred.gid, pid: Some(cred.pid) })
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd",
+          target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly"))]
+fn peer_credentials(stream: &UnixStream) -> io::Result<Credentials> {
+    use libc;
+
+    let mut uid = 0;
+    let mut gid = 0;
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Credentials { uid: uid, gid: gid, pid: None })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos",
+              target_os = "ios", target_os = "freebsd", target_os = "openbsd",
+              target_os = "netbsd", target_os = "dragonfly")))]
+fn peer_credentials(_stream: &UnixStream) -> io::Result<Credentials> {
+    Err(io::Error::new(io::ErrorKind::Other, "peer credentials are not supported on this platform"))
+}
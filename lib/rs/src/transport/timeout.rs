@@ -0,0 +1,56 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io;
+use std::net::TcpStream;
+use std::time::Duration;
+
+use bufstream::BufStream;
+
+use super::Transport;
+
+/// Transports whose blocking operations can be bounded in time, which lets
+/// generated clients enforce per-call timeouts and deadlines.
+pub trait SetTimeout {
+    /// Bounds every subsequent read and write by `timeout`, or removes the
+    /// bound when `None`.
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Called after a call was abandoned halfway, when the position in the
+    /// stream is unknown. Returns whether the transport can still be used,
+    /// for instance because it will open a new connection.
+    fn abandon(&mut self) -> bool { false }
+}
+
+impl SetTimeout for TcpStream {
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        try!(self.set_read_timeout(timeout));
+        self.set_write_timeout(timeout)
+    }
+}
+
+impl<T: SetTimeout + Transport> SetTimeout for BufStream<T> {
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.get_mut().set_timeout(timeout)
+    }
+
+    fn abandon(&mut self) -> bool {
+        self.get_mut().abandon()
+    }
+}
@@ -0,0 +1,399 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustls::{self, ClientConfig, ClientConnection, ConnectionCommon, RootCertStore,
+             ServerConfig, ServerConnection, SideData, StreamOwned};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::pki_types::pem::PemObject;
+use rustls::server::WebPkiClientVerifier;
+
+use context::{ConnectionContext, PeerAddr};
+use super::server::TransportServer;
+use super::timeout::SetTimeout;
+
+/// A TLS session over a TCP connection, on either side.
+///
+/// Client transports complete the handshake when they are created, so that
+/// certificate problems are reported there rather than by the first call.
+/// Server transports complete it on first use, or on `complete_handshake`,
+/// so that accepting a connection never waits for the client.
+pub struct TlsTransport<C> {
+    stream: StreamOwned<C, TcpStream>,
+    // When the handshake must be over, counted from its start.
+    deadline: Option<Instant>,
+    // The timeout set through `SetTimeout`, in force once the handshake is over.
+    timeout: Option<Duration>,
+}
+
+pub type TlsClientTransport = TlsTransport<ClientConnection>;
+pub type TlsServerTransport = TlsTransport<ServerConnection>;
+
+impl<C, S> TlsTransport<C>
+where C: DerefMut + Deref<Target = ConnectionCommon<S>>, S: SideData {
+    fn new(conn: C, sock: TcpStream, timeout: Option<Duration>) -> TlsTransport<C> {
+        TlsTransport {
+            stream: StreamOwned::new(conn, sock),
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            timeout: None,
+        }
+    }
+
+    /// Completes the handshake if it isn't already, failing with `TimedOut`
+    /// once the handshake timeout has passed since the transport was created.
+    pub fn complete_handshake(&mut self) -> io::Result<()> {
+        if !self.stream.conn.is_handshaking() {
+            return Ok(());
+        }
+
+        // The last flight may still be queued when the handshake is over.
+        while self.stream.conn.is_handshaking() || self.stream.conn.wants_write() {
+            try!(self.handshake_step());
+        }
+        self.stream.sock.set_timeout(self.timeout)
+    }
+
+    // Sends or receives one flight of handshake data, bounding the socket by
+    // what is left until the deadline, so that a peer trickling its data
+    // can't stretch the handshake.
+    fn handshake_step(&mut self) -> io::Result<()> {
+        let timeout = match self.deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out"));
+                }
+                Some(deadline - now)
+            }
+            None => self.timeout,
+        };
+        try!(self.stream.sock.set_timeout(timeout));
+
+        let result = if self.stream.conn.wants_write() {
+            self.stream.conn.write_tls(&mut self.stream.sock).map(|_| ())
+        } else {
+            self.read_handshake()
+        };
+
+        match result {
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => Ok(()),
+            // The socket timeout ran out along with the deadline, which the
+            // next step reports.
+            Err(ref err) if self.deadline.is_some() &&
+                            (err.kind() == io::ErrorKind::WouldBlock ||
+                             err.kind() == io::ErrorKind::TimedOut) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn read_handshake(&mut self) -> io::Result<()> {
+        if try!(self.stream.conn.read_tls(&mut self.stream.sock)) == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "connection closed during the TLS handshake"));
+        }
+
+        match self.stream.conn.process_new_packets() {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                // Tell the peer why, if it is still listening.
+                let _ = self.stream.conn.write_tls(&mut self.stream.sock);
+                Err(io::Error::new(io::ErrorKind::InvalidData, err))
+            }
+        }
+    }
+
+    /// The underlying TCP connection.
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream.sock
+    }
+
+    /// The TLS session, for instance to inspect the peer's certificates.
+    pub fn connection(&self) -> &C {
+        &self.stream.conn
+    }
+}
+
+impl<C, S> Read for TlsTransport<C>
+where C: DerefMut + Deref<Target = ConnectionCommon<S>>, S: SideData {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        try!(self.complete_handshake());
+        self.stream.read(buf)
+    }
+}
+
+impl<C, S> Write for TlsTransport<C>
+where C: DerefMut + Deref<Target = ConnectionCommon<S>>, S: SideData {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        try!(self.complete_handshake());
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        try!(self.complete_handshake());
+        self.stream.flush()
+    }
+}
+
+impl<C, S> SetTimeout for TlsTransport<C>
+where C: DerefMut + Deref<Target = ConnectionCommon<S>>, S: SideData {
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeout = timeout;
+        if self.stream.conn.is_handshaking() {
+            // Applied when the handshake is over.
+            Ok(())
+        } else {
+            self.stream.sock.set_timeout(timeout)
+        }
+    }
+}
+
+const DEFAULT_HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+
+/// Opens TLS connections to servers whose certificates are signed by a given
+/// set of certificate authorities.
+#[derive(Clone)]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+    timeout: Option<Duration>,
+}
+
+impl TlsConnector {
+    /// Trusts the PEM-encoded certificates in `ca_pem`, and only those.
+    pub fn new(ca_pem: &[u8]) -> io::Result<TlsConnector> {
+        let builder = try!(ClientConfig::builder_with_provider(provider())
+                               .with_safe_default_protocol_versions()
+                               .map_err(config_error));
+        let config = builder.with_root_certificates(try!(root_store(ca_pem)))
+                            .with_no_client_auth();
+        Ok(TlsConnector::from_config(config))
+    }
+
+    /// Like `new`, also authenticating with the PEM-encoded certificate
+    /// chain and private key of the client, for servers requiring mutual TLS.
+    pub fn with_client_certificate(ca_pem: &[u8], cert_pem: &[u8], key_pem: &[u8])
+                                   -> io::Result<TlsConnector> {
+        let builder = try!(ClientConfig::builder_with_provider(provider())
+                               .with_safe_default_protocol_versions()
+                               .map_err(config_error));
+        let config = try!(builder.with_root_certificates(try!(root_store(ca_pem)))
+                                 .with_client_auth_cert(try!(certificates(cert_pem)),
+                                                        try!(private_key(key_pem)))
+                                 .map_err(config_error));
+        Ok(TlsConnector::from_config(config))
+    }
+
+    /// Uses a rustls configuration as is, for settings not covered here.
+    pub fn from_config(config: ClientConfig) -> TlsConnector {
+        TlsConnector {
+            config: Arc::new(config),
+            timeout: Some(Duration::from_secs(DEFAULT_HANDSHAKE_TIMEOUT_SECS)),
+        }
+    }
+
+    /// How long `connect` may take to reach each address of the server, and
+    /// the handshake to complete after that, `None` waiting forever. Defaults
+    /// to ten seconds.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> TlsConnector {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Connects to `addr` and completes the handshake. `server_name` is sent
+    /// with SNI and must match the server's certificate.
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A, server_name: &str) -> io::Result<TlsClientTransport> {
+        let stream = match self.timeout {
+            Some(timeout) => try!(connect_timeout(addr, timeout)),
+            None => try!(TcpStream::connect(addr)),
+        };
+        self.wrap(stream, server_name)
+    }
+
+    /// Starts a TLS session over an established connection.
+    pub fn wrap(&self, stream: TcpStream, server_name: &str) -> io::Result<TlsClientTransport> {
+        let name = try!(ServerName::try_from(String::from(server_name))
+                            .map_err(|_| invalid_input("invalid TLS server name")));
+        let conn = try!(ClientConnection::new(self.config.clone(), name).map_err(config_error));
+        let mut transport = TlsTransport::new(conn, stream, self.timeout);
+        try!(transport.complete_handshake());
+        Ok(transport)
+    }
+}
+
+/// Completes the server side of TLS handshakes.
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    config: Arc<ServerConfig>,
+    handshake_timeout: Option<Duration>,
+}
+
+impl TlsAcceptor {
+    /// Presents the PEM-encoded certificate chain and private key to clients.
+    pub fn new(cert_pem: &[u8], key_pem: &[u8]) -> io::Result<TlsAcceptor> {
+        let builder = try!(ServerConfig::builder_with_provider(provider())
+                               .with_safe_default_protocol_versions()
+                               .map_err(config_error));
+        let config = try!(builder.with_no_client_auth()
+                                 .with_single_cert(try!(certificates(cert_pem)),
+                                                   try!(private_key(key_pem)))
+                                 .map_err(config_error));
+        Ok(TlsAcceptor::from_config(config))
+    }
+
+    /// Like `new`, also requiring clients to present a certificate signed by
+    /// one of the PEM-encoded authorities in `client_ca_pem`.
+    pub fn with_client_auth(cert_pem: &[u8], key_pem: &[u8], client_ca_pem: &[u8])
+                            -> io::Result<TlsAcceptor> {
+        let roots = Arc::new(try!(root_store(client_ca_pem)));
+        let verifier = try!(WebPkiClientVerifier::builder_with_provider(roots, provider())
+                                .build()
+                                .map_err(|err| invalid_input(&err.to_string())));
+        let builder = try!(ServerConfig::builder_with_provider(provider())
+                               .with_safe_default_protocol_versions()
+                               .map_err(config_error));
+        let config = try!(builder.with_client_cert_verifier(verifier)
+                                 .with_single_cert(try!(certificates(cert_pem)),
+                                                   try!(private_key(key_pem)))
+                                 .map_err(config_error));
+        Ok(TlsAcceptor::from_config(config))
+    }
+
+    /// Uses a rustls configuration as is, for instance one choosing the
+    /// certificate by SNI with `rustls::server::ResolvesServerCertUsingSni`.
+    pub fn from_config(config: ServerConfig) -> TlsAcceptor {
+        TlsAcceptor {
+            config: Arc::new(config),
+            handshake_timeout: Some(Duration::from_secs(DEFAULT_HANDSHAKE_TIMEOUT_SECS)),
+        }
+    }
+
+    /// How long a client may take to complete the handshake, counted from
+    /// `accept`, before it is abandoned, `None` waiting forever. Defaults to
+    /// ten seconds.
+    pub fn handshake_timeout(mut self, timeout: Option<Duration>) -> TlsAcceptor {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Starts a TLS session with a client connected on `stream`. The
+    /// handshake is left to the first use of the transport, so that it runs
+    /// on the thread serving the client.
+    pub fn accept(&self, stream: TcpStream) -> io::Result<TlsServerTransport> {
+        let conn = try!(ServerConnection::new(self.config.clone()).map_err(config_error));
+        Ok(TlsTransport::new(conn, stream, self.handshake_timeout))
+    }
+}
+
+/// A `TransportServer` accepting TLS connections on a TCP listener.
+///
+/// The handshake completes in `establish`, on the thread serving the
+/// connection, which also adds the client's certificates and requested
+/// server name to its context. Connections whose handshake fails or times
+/// out are dropped by the server, so that a misbehaving client doesn't stop
+/// it.
+pub struct TlsServer {
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+}
+
+impl TlsServer {
+    pub fn new(listener: TcpListener, acceptor: TlsAcceptor) -> TlsServer {
+        TlsServer {
+            listener: listener,
+            acceptor: acceptor,
+        }
+    }
+}
+
+impl TransportServer for TlsServer {
+    type Transport = TlsServerTransport;
+
+    fn accept(&self) -> io::Result<(TlsServerTransport, ConnectionContext)> {
+        let (stream, addr) = try!(self.listener.accept());
+        let transport = try!(self.acceptor.accept(stream));
+        let context = ConnectionContext { peer_addr: Some(PeerAddr::Tcp(addr)), ..ConnectionContext::new() };
+        Ok((transport, context))
+    }
+
+    fn establish(&self, transport: &mut TlsServerTransport, context: &mut ConnectionContext)
+                 -> io::Result<()> {
+        try!(transport.complete_handshake());
+
+        let conn = transport.connection();
+        context.certificates = conn.peer_certificates()
+                                   .map(|chain| chain.iter().map(|cert| cert.to_vec()).collect());
+        context.server_name = conn.server_name().map(String::from);
+        Ok(())
+    }
+}
+
+// Like `TcpStream::connect`, giving each address `timeout` to accept.
+fn connect_timeout<A: ToSocketAddrs>(addr: A, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in try!(addr.to_socket_addrs()) {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| invalid_input("could not resolve to any addresses")))
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn certificates(pem: &[u8]) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certs = try!(CertificateDer::pem_slice_iter(pem)
+                         .collect::<Result<Vec<_>, _>>()
+                         .map_err(|err| invalid_input(&err.to_string())));
+    if certs.is_empty() {
+        return Err(invalid_input("no certificate found in PEM data"));
+    }
+    Ok(certs)
+}
+
+fn private_key(pem: &[u8]) -> io::Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_slice(pem).map_err(|err| invalid_input(&err.to_string()))
+}
+
+fn root_store(ca_pem: &[u8]) -> io::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in try!(certificates(ca_pem)) {
+        try!(roots.add(cert).map_err(config_error));
+    }
+    Ok(roots)
+}
+
+fn config_error(err: rustls::Error) -> io::Error {
+    invalid_input(&err.to_string())
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,219 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io::{ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, IsCa, KeyPair};
+
+use super::{TlsAcceptor, TlsClientTransport, TlsConnector, TlsServer};
+
+use compiletest::*;
+use protocol::binary_protocol::BinaryProtocol;
+use server::SimpleServer;
+use ConnectionContext;
+
+struct Ca {
+    issuer: CertifiedIssuer<'static, KeyPair>,
+}
+
+impl Ca {
+    fn new() -> Ca {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        Ca { issuer: CertifiedIssuer::self_signed(params, KeyPair::generate().unwrap()).unwrap() }
+    }
+
+    fn pem(&self) -> Vec<u8> {
+        self.issuer.pem().into_bytes()
+    }
+
+    // Issues a certificate for `name`, returning its PEM and that of its key.
+    fn issue(&self, name: &str) -> (Vec<u8>, Vec<u8>) {
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec![String::from(name)]).unwrap()
+                                     .signed_by(&key, &self.issuer).unwrap();
+        (cert.pem().into_bytes(), key.serialize_pem().into_bytes())
+    }
+}
+
+// Replies with the number of certificates the client presented, and whether
+// it sent the expected server name.
+struct Handler;
+
+impl SharedServiceWithContext for Handler {
+    fn get_struct(&self, context: &ConnectionContext, _key: i32) -> SharedServiceGetStructResult {
+        let certificates = context.certificates.as_ref().map(|chain| chain.len()).unwrap_or(0);
+        let sni = context.server_name.as_ref().map(|name| name == "localhost").unwrap_or(false);
+        let nested = Some(vec![vec![vec![vec![certificates as i32, sni as i32]]]]).into_iter().collect();
+        SharedServiceGetStructResult { success: Some(DeeplyNested { nested: Some(nested) }) }
+    }
+}
+
+fn spawn_server(acceptor: TlsAcceptor) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let processor = SharedServiceProcessor::new(Handler);
+        SimpleServer::new(processor, TlsServer::new(listener, acceptor), || BinaryProtocol::new()).serve();
+    });
+
+    addr
+}
+
+fn call(transport: TlsClientTransport) -> ::Result<Vec<i32>> {
+    let mut client = SharedServiceClient::new(BinaryProtocol::new(), transport);
+    let nested = try!(client.get_struct(0)).success.unwrap().nested.unwrap();
+    Ok(nested.into_iter().next().unwrap()[0][0][0].clone())
+}
+
+#[test]
+fn call_over_tls() {
+    let ca = Ca::new();
+    let (cert, key) = ca.issue("localhost");
+    let addr = spawn_server(TlsAcceptor::new(&cert, &key).unwrap());
+
+    let connector = TlsConnector::new(&ca.pem()).unwrap();
+    let transport = connector.connect(addr, "localhost").unwrap();
+    assert_eq!(call(transport).unwrap(), vec![0, 1]);
+}
+
+#[test]
+fn untrusted_server() {
+    let ca = Ca::new();
+    let (cert, key) = ca.issue("localhost");
+    let addr = spawn_server(TlsAcceptor::new(&cert, &key).unwrap());
+
+    let untrusting = TlsConnector::new(&Ca::new().pem()).unwrap();
+    assert!(untrusting.connect(addr, "localhost").is_err());
+
+    // The server survives the failed handshake.
+    let connector = TlsConnector::new(&ca.pem()).unwrap();
+    let transport = connector.connect(addr, "localhost").unwrap();
+    assert_eq!(call(transport).unwrap(), vec![0, 1]);
+}
+
+#[test]
+fn server_name_mismatch() {
+    let ca = Ca::new();
+    let (cert, key) = ca.issue("localhost");
+    let addr = spawn_server(TlsAcceptor::new(&cert, &key).unwrap());
+
+    let connector = TlsConnector::new(&ca.pem()).unwrap();
+    assert!(connector.connect(addr, "example.com").is_err());
+    assert!(connector.connect(addr, "localhost").is_ok());
+}
+
+#[test]
+fn mutual_tls() {
+    let ca = Ca::new();
+    let (cert, key) = ca.issue("localhost");
+    let (client_cert, client_key) = ca.issue("client");
+    let addr = spawn_server(TlsAcceptor::with_client_auth(&cert, &key, &ca.pem()).unwrap());
+
+    let connector = TlsConnector::with_client_certificate(&ca.pem(), &client_cert, &client_key).unwrap();
+    let transport = connector.connect(addr, "localhost").unwrap();
+    assert_eq!(call(transport).unwrap(), vec![1, 1]);
+
+    // With TLS 1.3 the server checks the client's certificate after the
+    // client considers the handshake done, so the rejection shows on the call.
+    let anonymous = TlsConnector::new(&ca.pem()).unwrap();
+    match anonymous.connect(addr, "localhost") {
+        Ok(transport) => assert!(call(transport).is_err()),
+        Err(_) => {}
+    }
+}
+
+#[test]
+fn invalid_pem() {
+    assert!(TlsConnector::new(b"not a certificate").is_err());
+    assert!(TlsAcceptor::new(b"", b"").is_err());
+}
+
+#[test]
+fn silent_client_times_out() {
+    let ca = Ca::new();
+    let (cert, key) = ca.issue("localhost");
+    let acceptor = TlsAcceptor::new(&cert, &key).unwrap()
+                               .handshake_timeout(Some(Duration::from_millis(100)));
+    let addr = spawn_server(acceptor);
+
+    // Never sends a ClientHello, and stays connected.
+    let _silent = TcpStream::connect(addr).unwrap();
+
+    let connector = TlsConnector::new(&ca.pem()).unwrap();
+    let transport = connector.connect(addr, "localhost").unwrap();
+    assert_eq!(call(transport).unwrap(), vec![0, 1]);
+}
+
+#[test]
+fn accept_leaves_handshake_to_first_use() {
+    let ca = Ca::new();
+    let (cert, key) = ca.issue("localhost");
+    let acceptor = TlsAcceptor::new(&cert, &key).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    // Returns at once although the client never sends a ClientHello.
+    let silent = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut transport = acceptor.accept(listener.accept().unwrap().0).unwrap();
+    assert!(transport.connection().is_handshaking());
+
+    drop(silent);
+    assert!(transport.complete_handshake().is_err());
+}
+
+#[test]
+fn trickling_client_times_out() {
+    let ca = Ca::new();
+    let (cert, key) = ca.issue("localhost");
+    let acceptor = TlsAcceptor::new(&cert, &key).unwrap()
+                               .handshake_timeout(Some(Duration::from_millis(200)));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Sends a byte of a 512 byte handshake record every 20ms, well within
+    // any single read's timeout.
+    thread::spawn(move || {
+        let mut client = TcpStream::connect(addr).unwrap();
+        let _ = client.write_all(&[0x16, 0x03, 0x01, 0x02, 0x00]);
+        while client.write_all(&[0]).is_ok() {
+            thread::sleep(Duration::from_millis(20));
+        }
+    });
+
+    let start = Instant::now();
+    let mut transport = acceptor.accept(listener.accept().unwrap().0).unwrap();
+    let err = transport.complete_handshake().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn connector_times_out() {
+    // Connections complete in the backlog, but no handshake ever comes back.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let connector = TlsConnector::new(&Ca::new().pem()).unwrap()
+                                 .timeout(Some(Duration::from_millis(100)));
+    let err = connector.connect(listener.local_addr().unwrap(), "localhost").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+}
@@ -0,0 +1,203 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+//! Thrift data decoded without generated types.
+
+use protocol::{self, Decode, Encode, ThriftTyped, Type};
+use {Error, Protocol, Result, Transport};
+
+/// Any Thrift value, with the types it was sent with.
+///
+/// Containers keep their element types and their order, so that encoding a
+/// decoded value writes it back as it was received, even when empty. Field
+/// names are not on the wire, so struct fields are only known by id, and are
+/// kept in the order they were received in, duplicates included.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Byte(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Double(f64),
+    /// A string or binary field; strings are not checked to be UTF-8.
+    Binary(Vec<u8>),
+    Struct(Vec<(i16, Value)>),
+    /// The element type and the elements.
+    List(Type, Vec<Value>),
+    /// The element type and the elements, in the order they were received.
+    Set(Type, Vec<Value>),
+    /// The key and value types, and the entries in the order they were
+    /// received.
+    Map(Type, Type, Vec<(Value, Value)>),
+}
+
+impl Value {
+    /// Reads a value of type `typ`, walking the data the way `Protocol::skip`
+    /// does.
+    pub fn read<P, T>(protocol: &mut P, transport: &mut T, typ: Type) -> Result<Value>
+    where P: Protocol, T: Transport {
+        let value = match typ {
+            Type::Bool => Value::Bool(try!(protocol.read_bool(transport))),
+            Type::Byte => Value::Byte(try!(protocol.read_byte(transport))),
+            Type::I16 => Value::I16(try!(protocol.read_i16(transport))),
+            Type::I32 => Value::I32(try!(protocol.read_i32(transport))),
+            Type::I64 => Value::I64(try!(protocol.read_i64(transport))),
+            Type::Double => Value::Double(try!(protocol.read_double(transport))),
+            Type::String => Value::Binary(try!(protocol.read_binary(transport))),
+            Type::Struct => {
+                let mut fields = Vec::new();
+                try!(protocol.read_struct_begin(transport));
+                loop {
+                    let (_, field_type, id) = try!(protocol.read_field_begin(transport));
+                    if field_type == Type::Stop {
+                        break;
+                    }
+                    fields.push((id, try!(Value::read(protocol, transport, field_type))));
+                    try!(protocol.read_field_end(transport));
+                }
+                try!(protocol.read_struct_end(transport));
+                Value::Struct(fields)
+            }
+            Type::Map => {
+                let (key_type, value_type, size) = try!(protocol.read_map_begin(transport));
+                let mut entries = Vec::new();
+                for _ in 0..size {
+                    let key = try!(Value::read(protocol, transport, key_type));
+                    let value = try!(Value::read(protocol, transport, value_type));
+                    entries.push((key, value));
+                }
+                try!(protocol.read_map_end(transport));
+                Value::Map(key_type, value_type, entries)
+            }
+            Type::Set => {
+                let (elem_type, size) = try!(protocol.read_set_begin(transport));
+                let mut elems = Vec::new();
+                for _ in 0..size {
+                    elems.push(try!(Value::read(protocol, transport, elem_type)));
+                }
+                try!(protocol.read_set_end(transport));
+                Value::Set(elem_type, elems)
+            }
+            Type::List => {
+                let (elem_type, size) = try!(protocol.read_list_begin(transport));
+                let mut elems = Vec::new();
+                for _ in 0..size {
+                    elems.push(try!(Value::read(protocol, transport, elem_type)));
+                }
+                try!(protocol.read_list_end(transport));
+                Value::List(elem_type, elems)
+            }
+            Type::Void | Type::Stop => return Err(Error::from(protocol::Error::ProtocolViolation)),
+        };
+
+        Ok(value)
+    }
+
+    /// The type this value is encoded as.
+    pub fn value_type(&self) -> Type {
+        match *self {
+            Value::Bool(_) => Type::Bool,
+            Value::Byte(_) => Type::Byte,
+            Value::I16(_) => Type::I16,
+            Value::I32(_) => Type::I32,
+            Value::I64(_) => Type::I64,
+            Value::Double(_) => Type::Double,
+            Value::Binary(_) => Type::String,
+            Value::Struct(_) => Type::Struct,
+            Value::List(..) => Type::List,
+            Value::Set(..) => Type::Set,
+            Value::Map(..) => Type::Map,
+        }
+    }
+}
+
+/// An empty struct, so that decoding a default `Value` reads a struct, as
+/// message bodies are.
+impl Default for Value {
+    fn default() -> Value {
+        Value::Struct(Vec::new())
+    }
+}
+
+/// Values are structs as far as generated code knows, since that is what
+/// `Decode` reads into a default value. Use `value_type` for the actual type.
+///
+/// This only holds for structs: containers of values, such as `Vec<Value>`,
+/// decode struct elements only. Read other data with `Value::read`.
+impl ThriftTyped for Value {
+    fn typ() -> Type { Type::Struct }
+}
+
+impl Encode for Value {
+    fn encode<P, T>(&self, protocol: &mut P, transport: &mut T) -> Result<()>
+    where P: Protocol, T: Transport {
+        match *self {
+            Value::Bool(x) => protocol.write_bool(transport, x),
+            Value::Byte(x) => protocol.write_byte(transport, x),
+            Value::I16(x) => protocol.write_i16(transport, x),
+            Value::I32(x) => protocol.write_i32(transport, x),
+            Value::I64(x) => protocol.write_i64(transport, x),
+            Value::Double(x) => protocol.write_double(transport, x),
+            Value::Binary(ref x) => protocol.write_binary(transport, x),
+            Value::Struct(ref fields) => {
+                try!(protocol.write_struct_begin(transport, ""));
+                for &(id, ref value) in fields {
+                    try!(protocol.write_field_begin(transport, "", value.value_type(), id));
+                    try!(value.encode(protocol, transport));
+                    try!(protocol.write_field_end(transport));
+                }
+                try!(protocol.write_field_stop(transport));
+                protocol.write_struct_end(transport)
+            }
+            Value::Map(key_type, value_type, ref entries) => {
+                try!(protocol.write_map_begin(transport, key_type, value_type, entries.len()));
+                for &(ref key, ref value) in entries {
+                    try!(key.encode(protocol, transport));
+                    try!(value.encode(protocol, transport));
+                }
+                protocol.write_map_end(transport)
+            }
+            Value::Set(elem_type, ref elems) => {
+                try!(protocol.write_set_begin(transport, elem_type, elems.len()));
+                for elem in elems {
+                    try!(elem.encode(protocol, transport));
+                }
+                protocol.write_set_end(transport)
+            }
+            Value::List(elem_type, ref elems) => {
+                try!(protocol.write_list_begin(transport, elem_type, elems.len()));
+                for elem in elems {
+                    try!(elem.encode(protocol, transport));
+                }
+                protocol.write_list_end(transport)
+            }
+        }
+    }
+}
+
+/// Reads a value of the type of the current one: a struct for a default
+/// value.
+impl Decode for Value {
+    fn decode<P, T>(&mut self, protocol: &mut P, transport: &mut T) -> Result<()>
+    where P: Protocol, T: Transport {
+        *self = try!(Value::read(protocol, transport, self.value_type()));
+        Ok(())
+    }
+}
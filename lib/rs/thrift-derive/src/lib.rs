@@ -0,0 +1,378 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements. See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership. The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License. You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+//! Derives the traits `strukt!` and `enom!` implement, for types written
+//! by hand.
+//!
+//! ```ignore
+//! #[macro_use]
+//! extern crate thrift_derive;
+//! extern crate thrift;
+//!
+//! #[derive(Debug, Default, ThriftStruct)]
+//! pub struct Work {
+//!     #[thrift(id = 1)]
+//!     pub num1: i32,
+//!     #[thrift(id = 3)]
+//!     pub op: Operation,
+//!     #[thrift(id = 4)]
+//!     pub comment: Option<String>,
+//! }
+//!
+//! #[derive(Copy, Clone, Debug, PartialEq, ThriftEnum)]
+//! pub enum Operation {
+//!     Add = 1,
+//!     Subtract = 2,
+//! }
+//! ```
+//!
+//! Fields of type `Option<T>` are only written when set, other fields are
+//! always written. Structs must implement `Default`.
+//!
+//! Enums default to their first variant, or to the one marked
+//! `#[thrift(default)]`; `#[thrift(no_default)]` on the enum leaves `Default`
+//! to be implemented by hand. Like `enom!` without `unknown`, they fail to
+//! decode values they don't list, unless a variant holding an `i32` is marked
+//! `#[thrift(unknown)]` to keep them.
+
+// quote! recurses once per token.
+#![recursion_limit = "256"]
+
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use syn::{Body, ConstExpr, DeriveInput, Field, Lit, MetaItem, NestedMetaItem, Ty, UnOp, VariantData};
+
+#[proc_macro_derive(ThriftStruct, attributes(thrift))]
+pub fn derive_thrift_struct(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+    impl_struct(&ast).parse().unwrap()
+}
+
+#[proc_macro_derive(ThriftEnum, attributes(thrift))]
+pub fn derive_thrift_enum(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+    impl_enum(&ast).parse().unwrap()
+}
+
+fn impl_struct(ast: &DeriveInput) -> quote::Tokens {
+    let name = &ast.ident;
+    let name_str = name.as_ref();
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let fields = match ast.body {
+        Body::Struct(VariantData::Struct(ref fields)) => &fields[..],
+        Body::Struct(VariantData::Unit) => &[],
+        _ => panic!("#[derive(ThriftStruct)] only supports structs with named fields, not `{}`", name),
+    };
+
+    let mut ids: Vec<i16> = Vec::new();
+    let mut encoders = Vec::new();
+    let mut decoders = Vec::new();
+    for field in fields {
+        let fname = field.ident.as_ref().unwrap();
+        let fname_str = fname.as_ref();
+        let fty = &field.ty;
+        let id = field_id(name, field);
+        if ids.contains(&id) {
+            panic!("#[derive(ThriftStruct)] on `{}`: id {} is used twice", name, id);
+        }
+        ids.push(id);
+
+        encoders.push(if is_option(fty) {
+            quote! {
+                if let Some(ref x) = self.#fname {
+                    try!(protocol.write_field_begin(transport, #fname_str, <#fty as ThriftTyped>::typ(), #id));
+                    try!(x.encode(protocol, transport));
+                    try!(protocol.write_field_end(transport));
+                }
+            }
+        } else {
+            quote! {
+                try!(protocol.write_field_begin(transport, #fname_str, <#fty as ThriftTyped>::typ(), #id));
+                try!(self.#fname.encode(protocol, transport));
+                try!(protocol.write_field_end(transport));
+            }
+        });
+        decoders.push(quote! {
+            else if (typ, id) == (<#fty as ThriftTyped>::typ(), #id) {
+                try!(self.#fname.decode(protocol, transport));
+            }
+        });
+    }
+
+    quote! {
+        impl #impl_generics ::thrift::protocol::ThriftTyped for #name #ty_generics #where_clause {
+            fn typ() -> ::thrift::protocol::Type { ::thrift::protocol::Type::Struct }
+        }
+
+        impl #impl_generics ::thrift::protocol::Encode for #name #ty_generics #where_clause {
+            fn encode<P, T>(&self, protocol: &mut P, transport: &mut T) -> ::thrift::Result<()>
+            where P: ::thrift::Protocol, T: ::thrift::Transport {
+                #[allow(unused_imports)]
+                use ::thrift::protocol::{Encode, ThriftTyped};
+                #[allow(unused_imports)]
+                use ::thrift::Protocol;
+
+                try!(protocol.write_struct_begin(transport, #name_str));
+                #(#encoders)*
+                try!(protocol.write_field_stop(transport));
+                try!(protocol.write_struct_end(transport));
+
+                Ok(())
+            }
+        }
+
+        impl #impl_generics ::thrift::protocol::Decode for #name #ty_generics #where_clause {
+            fn decode<P, T>(&mut self, protocol: &mut P, transport: &mut T) -> ::thrift::Result<()>
+            where P: ::thrift::Protocol, T: ::thrift::Transport {
+                #[allow(unused_imports)]
+                use ::thrift::protocol::{Decode, ThriftTyped};
+                #[allow(unused_imports)]
+                use ::thrift::Protocol;
+
+                try!(protocol.read_struct_begin(transport));
+
+                loop {
+                    let (_, typ, id) = try!(protocol.read_field_begin(transport));
+
+                    if typ == ::thrift::protocol::Type::Stop {
+                        break;
+                    } #(#decoders)* else {
+                        let _ = id;
+                        try!(protocol.skip(transport, typ));
+                    }
+
+                    try!(protocol.read_field_end(transport));
+                }
+
+                try!(protocol.read_struct_end(transport));
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn impl_enum(ast: &DeriveInput) -> quote::Tokens {
+    let name = &ast.ident;
+    let variants = match ast.body {
+        Body::Enum(ref variants) => variants,
+        _ => panic!("#[derive(ThriftEnum)] only supports enums, not `{}`", name),
+    };
+    if variants.is_empty() {
+        panic!("#[derive(ThriftEnum)] on `{}`: the enum needs at least one variant", name);
+    }
+
+    let mut no_default = false;
+    for word in thrift_words(&ast.attrs) {
+        match &word[..] {
+            "no_default" => no_default = true,
+            _ => panic!("#[derive(ThriftEnum)] on `{}`: unknown attribute `{}`, expected #[thrift(no_default)]",
+                        name, word),
+        }
+    }
+
+    // Like in Rust and Thrift, variants without a discriminant follow the
+    // previous one.
+    let mut value = -1i64;
+    let mut to_num = Vec::new();
+    let mut from_num = Vec::new();
+    let mut default = None;
+    let mut unknown = None;
+    for variant in variants {
+        let vname = &variant.ident;
+        let mut is_default = false;
+        let mut is_unknown = false;
+        for word in thrift_words(&variant.attrs) {
+            match &word[..] {
+                "default" if default.is_none() => is_default = true,
+                "unknown" if unknown.is_none() => is_unknown = true,
+                "default" | "unknown" => {
+                    panic!("#[derive(ThriftEnum)] on `{}`: more than one variant is marked {}", name, word)
+                }
+                _ => panic!("#[derive(ThriftEnum)] on `{}`: unknown attribute `{}` on `{}`, expected \
+                             #[thrift(default)] or #[thrift(unknown)]", name, word, vname),
+            }
+        }
+
+        if is_unknown {
+            match variant.data {
+                VariantData::Tuple(ref fields) if fields.len() == 1 && !is_default => {}
+                _ => panic!("#[derive(ThriftEnum)] on `{}`: the unknown variant `{}` must hold the value alone, \
+                             as in `{}(i32)`", name, vname, vname),
+            }
+            to_num.push(quote! { #name::#vname(num) => num, });
+            unknown = Some(vname);
+            continue;
+        }
+        if variant.data != VariantData::Unit {
+            panic!("#[derive(ThriftEnum)] on `{}`: variant `{}` has fields", name, vname);
+        }
+        if is_default {
+            default = Some(vname);
+        }
+        value = match variant.discriminant {
+            Some(ref discriminant) => discriminant_value(name, discriminant),
+            None => value + 1,
+        };
+        if value < i32::min_value() as i64 || value > i32::max_value() as i64 {
+            panic!("#[derive(ThriftEnum)] on `{}`: value of `{}` does not fit in an i32", name, vname);
+        }
+        let num = value as i32;
+        to_num.push(quote! { #name::#vname => #num, });
+        from_num.push(quote! { #num => Some(#name::#vname), });
+    }
+    if from_num.is_empty() {
+        panic!("#[derive(ThriftEnum)] on `{}`: the enum needs a variant besides the unknown one", name);
+    }
+
+    let default_impl = if no_default {
+        if default.is_some() {
+            panic!("#[derive(ThriftEnum)] on `{}`: #[thrift(default)] with #[thrift(no_default)]", name);
+        }
+        quote! {}
+    } else {
+        let first = variants.iter().map(|variant| &variant.ident).find(|&vname| Some(vname) != unknown).unwrap();
+        let default = default.unwrap_or(first);
+        quote! {
+            impl Default for #name {
+                fn default() -> Self { #name::#default }
+            }
+        }
+    };
+
+    let decode = match unknown {
+        Some(uname) => quote! {
+            #[allow(unused_imports)]
+            use ::thrift::Protocol;
+
+            let num = try!(protocol.read_i32(transport));
+            *self = <#name as ::thrift::protocol::FromNum>::from_num(num).unwrap_or(#name::#uname(num));
+        },
+        None => quote! {
+            *self = try!(::thrift::protocol::helpers::read_enum(protocol, transport));
+        },
+    };
+
+    quote! {
+        #default_impl
+
+        impl ::thrift::protocol::FromNum for #name {
+            fn from_num(num: i32) -> Option<Self> {
+                match num {
+                    #(#from_num)*
+                    _ => None
+                }
+            }
+        }
+
+        impl ::thrift::protocol::ThriftTyped for #name {
+            fn typ() -> ::thrift::protocol::Type { ::thrift::protocol::Type::I32 }
+        }
+
+        impl ::thrift::protocol::Encode for #name {
+            fn encode<P, T>(&self, protocol: &mut P, transport: &mut T) -> ::thrift::Result<()>
+            where P: ::thrift::Protocol, T: ::thrift::Transport {
+                #[allow(unused_imports)]
+                use ::thrift::Protocol;
+
+                let num = match *self {
+                    #(#to_num)*
+                };
+                protocol.write_i32(transport, num)
+            }
+        }
+
+        impl ::thrift::protocol::Decode for #name {
+            fn decode<P, T>(&mut self, protocol: &mut P, transport: &mut T) -> ::thrift::Result<()>
+            where P: ::thrift::Protocol, T: ::thrift::Transport {
+                #decode
+                Ok(())
+            }
+        }
+    }
+}
+
+// The words in the `#[thrift(...)]` attributes among `attrs`.
+fn thrift_words(attrs: &[syn::Attribute]) -> Vec<String> {
+    let mut words = Vec::new();
+    for attr in attrs {
+        let items = match attr.value {
+            MetaItem::List(ref attr_name, ref items) if attr_name == "thrift" => items,
+            _ => continue,
+        };
+        for item in items {
+            match *item {
+                NestedMetaItem::MetaItem(MetaItem::Word(ref word)) => words.push(word.to_string()),
+                _ => words.push(quote!(#item).to_string()),
+            }
+        }
+    }
+    words
+}
+
+// The id in `#[thrift(id = N)]`.
+fn field_id(name: &syn::Ident, field: &Field) -> i16 {
+    let fname = field.ident.as_ref().unwrap();
+    let mut id = None;
+    for attr in &field.attrs {
+        let items = match attr.value {
+            MetaItem::List(ref attr_name, ref items) if attr_name == "thrift" => items,
+            _ => continue,
+        };
+        for item in items {
+            match *item {
+                NestedMetaItem::MetaItem(MetaItem::NameValue(ref key, Lit::Int(value, _))) if key == "id" => {
+                    if value == 0 || value > i16::max_value() as u64 {
+                        panic!("#[derive(ThriftStruct)] on `{}`: the id of `{}` must be between 1 and {}",
+                               name, fname, i16::max_value());
+                    }
+                    id = Some(value as i16);
+                }
+                _ => panic!("#[derive(ThriftStruct)] on `{}`: unknown attribute on `{}`, expected #[thrift(id = N)]",
+                            name, fname),
+            }
+        }
+    }
+    match id {
+        Some(id) => id,
+        None => panic!("#[derive(ThriftStruct)] on `{}`: `{}` needs a #[thrift(id = N)] attribute", name, fname),
+    }
+}
+
+fn discriminant_value(name: &syn::Ident, discriminant: &ConstExpr) -> i64 {
+    match *discriminant {
+        ConstExpr::Lit(Lit::Int(value, _)) => value as i64,
+        ConstExpr::Unary(UnOp::Neg, ref operand) => -discriminant_value(name, operand),
+        _ => panic!("#[derive(ThriftEnum)] on `{}`: discriminants must be integer literals", name),
+    }
+}
+
+// Whether the field is optional, which we can only tell from the name of its
+// type.
+fn is_option(ty: &Ty) -> bool {
+    match *ty {
+        Ty::Path(None, ref path) => path.segments.last().map_or(false, |segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
@@ -0,0 +1,154 @@
+#[macro_use]
+extern crate thrift;
+#[macro_use]
+extern crate thrift_derive;
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use thrift::protocol::binary_protocol::BinaryProtocol;
+use thrift::protocol::{Decode, Encode};
+
+/// A hand-written struct, with docs and derives of its own.
+#[derive(Debug, Default, Clone, PartialEq, ThriftStruct)]
+pub struct Work {
+    #[thrift(id = 1)]
+    pub num1: i32,
+    #[thrift(id = 2)]
+    pub num2: i32,
+    #[thrift(id = 3)]
+    pub op: Operation,
+    #[thrift(id = 4)]
+    pub comment: Option<String>,
+    #[thrift(id = 7)]
+    pub tags: HashMap<String, Vec<i64>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, ThriftEnum)]
+pub enum Operation {
+    Add = 1,
+    Subtract,
+    Multiply = 10,
+    Negate = -1,
+}
+
+#[derive(Debug, Default, PartialEq, ThriftStruct)]
+pub struct Nothing;
+
+#[derive(Copy, Clone, Debug, PartialEq, ThriftEnum)]
+pub enum Color {
+    Red,
+    #[thrift(default)]
+    Green,
+    #[thrift(unknown)]
+    Other(i32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, ThriftEnum)]
+#[thrift(no_default)]
+pub enum Level {
+    Low,
+    High,
+}
+
+impl Default for Level {
+    fn default() -> Level { Level::High }
+}
+
+strukt! {
+    name = MacroWork,
+    fields = {
+        num1: i32 => 1,
+        num2: i32 => 2,
+        op: MacroOperation => 3,
+        comment: String => 4,
+        tags: HashMap<String, Vec<i64>> => 7,
+    }
+}
+
+enom! {
+    name = MacroOperation,
+    values = [Add = 1, Subtract = 2, Multiply = 10, Negate = -1,],
+    default = Add
+}
+
+fn to_bytes<T: Encode>(x: &T) -> Vec<u8> {
+    let mut transport = Cursor::new(Vec::new());
+    x.encode(&mut BinaryProtocol::new(), &mut transport).unwrap();
+    transport.into_inner()
+}
+
+fn from_bytes<T: Decode>(bytes: Vec<u8>) -> T {
+    let mut instance = T::default();
+    instance.decode(&mut BinaryProtocol::new(), &mut Cursor::new(bytes)).unwrap();
+    instance
+}
+
+fn work() -> Work {
+    let mut tags = HashMap::new();
+    tags.insert(String::from("primes"), vec![2, 3, 5]);
+    Work { num1: 6, num2: 7, op: Operation::Multiply, comment: Some(String::from("answer")), tags: tags }
+}
+
+#[test]
+fn test_derived_struct_roundtrip() {
+    let work = work();
+    assert_eq!(from_bytes::<Work>(to_bytes(&work)), work);
+}
+
+#[test]
+fn test_derived_struct_matches_strukt() {
+    let work = work();
+    let macro_work = MacroWork {
+        num1: Some(6),
+        num2: Some(7),
+        op: Some(MacroOperation::Multiply),
+        comment: Some(String::from("answer")),
+        tags: Some(work.tags.clone()),
+    };
+    assert_eq!(to_bytes(&work), to_bytes(&macro_work));
+
+    let decoded: MacroWork = from_bytes(to_bytes(&work));
+    assert_eq!(decoded.op, Some(MacroOperation::Multiply));
+    assert_eq!(decoded.comment, Some(String::from("answer")));
+}
+
+#[test]
+fn test_derived_struct_skips_unset_options_and_unknown_fields() {
+    let macro_work = MacroWork { num1: Some(1), comment: None, ..MacroWork::default() };
+    let work: Work = from_bytes(to_bytes(&macro_work));
+    assert_eq!(work, Work { num1: 1, ..Work::default() });
+
+    let work = Work { comment: None, ..self::work() };
+    let decoded: MacroWork = from_bytes(to_bytes(&work));
+    assert_eq!(decoded.comment, None);
+
+    let nothing: Nothing = from_bytes(to_bytes(&work));
+    assert_eq!(nothing, Nothing);
+}
+
+#[test]
+fn test_derived_enum() {
+    use thrift::protocol::FromNum;
+
+    assert_eq!(Operation::default(), Operation::Add);
+    assert_eq!(Operation::from_num(2), Some(Operation::Subtract));
+    assert_eq!(Operation::from_num(-1), Some(Operation::Negate));
+    assert_eq!(Operation::from_num(3), None);
+    assert_eq!(to_bytes(&Operation::Negate), to_bytes(&MacroOperation::Negate));
+    assert_eq!(from_bytes::<Operation>(to_bytes(&Operation::Subtract)), Operation::Subtract);
+}
+
+#[test]
+fn test_derived_enum_attributes() {
+    use thrift::protocol::FromNum;
+
+    assert_eq!(Color::default(), Color::Green);
+    assert_eq!(Color::from_num(5), None);
+    assert_eq!(from_bytes::<Color>(to_bytes(&5i32)), Color::Other(5));
+    assert_eq!(to_bytes(&Color::Other(5)), to_bytes(&5i32));
+    assert_eq!(from_bytes::<Color>(to_bytes(&1i32)), Color::Green);
+
+    assert_eq!(Level::default(), Level::High);
+    assert_eq!(from_bytes::<Level>(to_bytes(&Level::Low)), Level::Low);
+}
@@ -1,8 +1,9 @@
+#[macro_use]
 extern crate thrift;
 
+mod small_test;
 mod thrift_test;
 
-
 #[test]
 fn it_works() {
 }
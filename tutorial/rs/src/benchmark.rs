@@ -43,7 +43,7 @@ pub fn main() {
     };
 
     let mut client = tutorial::CalculatorClient::new(
-        BinaryProtocol, BufStream::new(TcpStream::connect("127.0.0.1:9090").unwrap()));
+        BinaryProtocol::new(), BufStream::new(TcpStream::connect("127.0.0.1:9090").unwrap()));
 
     println!("Rust Thrift benchmark");
     println!("Running {} iterations", iterations);
@@ -30,7 +30,7 @@ mod shared;
 
 pub fn main() {
     let stream = BufStream::new(TcpStream::connect("127.0.0.1:9090").unwrap());
-    let mut client = tutorial::CalculatorClient::new(BinaryProtocol, stream);
+    let mut client = tutorial::CalculatorClient::new(BinaryProtocol::new(), stream);
 
     // Ping
     client.ping().unwrap();
@@ -32,6 +32,7 @@ use std::collections::HashMap;
 use thrift::protocol::binary_protocol::BinaryProtocol;
 use thrift::server::SimpleServer;
 use thrift::transport::server::TransportServer;
+use thrift::ConnectionContext;
 
 use tutorial::*;
 use shared::*;
@@ -65,23 +66,26 @@ impl<'a> Calculator for &'a CalculatorHandler {
             Operation::MULTIPLY => num1 * num2,
             Operation::DIVIDE => {
                 if num2 == 0 {
-                    return CalculatorCalculateResult {
-                        success: None,
-                        ouch: Some(InvalidOperation {
-                            what_op: work.op.map(|x| x as i32),
-                            why: Some("Cannot divide by 0".into())
-                        })
-                    };
+                    return CalculatorCalculateResult::ouch(InvalidOperation {
+                        what_op: work.op.map(i32::from),
+                        why: Some("Cannot divide by 0".into())
+                    });
                 }
 
                 num1 / num2
             }
+            Operation::Unknown(op) => {
+                return CalculatorCalculateResult::ouch(InvalidOperation {
+                    what_op: Some(op),
+                    why: Some("Unknown operation".into())
+                });
+            }
         };
 
         let ss = SharedStruct { key: Some(log_id), value: Some(val.to_string()) };
         self.log.borrow_mut().insert(log_id, ss);
 
-        CalculatorCalculateResult { success: Some(val), ouch: None }
+        CalculatorCalculateResult::success(val)
     }
 
     fn zip(&self) -> CalculatorZipResult {
@@ -102,17 +106,17 @@ struct BufferServer(TcpListener);
 impl TransportServer for BufferServer {
      type Transport = BufStream<TcpStream>;
 
-     fn accept(&self) -> io::Result<BufStream<TcpStream>> {
-        self.0.accept().map(|res| BufStream::new(res.0))
+     fn accept(&self) -> io::Result<(BufStream<TcpStream>, ConnectionContext)> {
+        TransportServer::accept(&self.0).map(|(stream, context)| (BufStream::new(stream), context))
      }
 }
 
 pub fn main() {
     let handler = CalculatorHandler { log: RefCell::new(HashMap::new()) };
-    let processor = CalculatorProcessor::new(&handler, &handler);
+    let processor = CalculatorProcessor::new(&handler);
 
     let server_transport = BufferServer(TcpListener::bind("127.0.0.1:9090").unwrap());
-    let mut server = SimpleServer::new(processor, server_transport, || BinaryProtocol);
+    let mut server = SimpleServer::new(processor, server_transport, || BinaryProtocol::new());
 
     println!("Starting the server...");
     server.serve();
@@ -8,28 +8,34 @@
 #[allow(unused_imports)]
 use std::collections::{HashMap, HashSet};
 
-
 strukt! {
   name = SharedStruct,
   fields = {
     key: i32 => 1,
     value: String => 2,
-  }
+  },
+  derive = [Eq, Hash, PartialOrd, Ord, ],
+  builder = SharedStructBuilder,
+  accessors = [
+    set_key take_key has_key,
+    set_value take_value has_value,
+  ],
+  required = []
 }
 
 service! {
   trait_name = SharedService,
+  context_trait_name = SharedServiceWithContext,
   processor_name = SharedServiceProcessor,
   client_name = SharedServiceClient,
+  parents = [],
   service_methods = [
-    SharedServiceGetStructArgs -> SharedServiceGetStructResult = a.getStruct(
+    SharedServiceGetStructArgs -> SharedServiceGetStructResult = getStruct(
       key: i32 => 1,
     ) -> SharedStruct => [
     ],
   ],
   parent_methods = [
-  ],
-  bounds = [A: SharedService, ],
-  fields = [a: A, ]
+  ]
 }
 
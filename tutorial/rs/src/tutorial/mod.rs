@@ -8,8 +8,6 @@
 #[allow(unused_imports)]
 use std::collections::{HashMap, HashSet};
 
-use shared::*;
-
 enom! {
   name = Operation,
   values = [
@@ -18,7 +16,8 @@ enom! {
     MULTIPLY = 3,
     DIVIDE = 4,
   ],
-  default = ADD
+  default = ADD,
+  unknown = Unknown
 }
 
 pub type MyInteger = i32;
@@ -30,7 +29,16 @@ strukt! {
     num2: i32 => 2,
     op: Operation => 3,
     comment: Option<String> => 4,
-  }
+  },
+  derive = [Eq, Hash, PartialOrd, Ord, ],
+  builder = WorkBuilder,
+  accessors = [
+    set_num1 take_num1 has_num1,
+    set_num2 take_num2 has_num2,
+    set_op take_op has_op,
+    set_comment take_comment has_comment,
+  ],
+  required = []
 }
 
 strukt! {
@@ -38,39 +46,46 @@ strukt! {
   fields = {
     what_op: i32 => 1,
     why: String => 2,
-  }
+  },
+  derive = [Eq, Hash, PartialOrd, Ord, ],
+  builder = InvalidOperationBuilder,
+  accessors = [
+    set_what_op take_what_op has_what_op,
+    set_why take_why has_why,
+  ],
+  required = []
 }
 
 service! {
   trait_name = Calculator,
+  context_trait_name = CalculatorWithContext,
   processor_name = CalculatorProcessor,
   client_name = CalculatorClient,
+  parents = [crate::shared::SharedService => crate::shared::SharedServiceWithContext, ],
   service_methods = [
-    CalculatorPingArgs -> CalculatorPingResult = a.ping(
+    CalculatorPingArgs -> CalculatorPingResult = ping(
     ) -> () => [
     ],
-    CalculatorAddArgs -> CalculatorAddResult = a.add(
+    CalculatorAddArgs -> CalculatorAddResult = add(
       num1: i32 => 1,
       num2: i32 => 2,
     ) -> i32 => [
     ],
-    CalculatorCalculateArgs -> CalculatorCalculateResult = a.calculate(
+    CalculatorCalculateArgs -> CalculatorCalculateResult = calculate(
       logid: i32 => 1,
       w: Work => 2,
     ) -> i32 => [
       ouch: InvalidOperation => 1,
     ],
-    CalculatorZipArgs -> CalculatorZipResult = a.zip(
+    CalculatorZipArgs -> CalculatorZipResult = zip(
     ) -> () => [
     ],
   ],
   parent_methods = [
-    SharedServiceGetStructArgs -> SharedServiceGetStructResult = b.getStruct(
+    (crate::shared::SharedServiceGetStructArgs, crate::shared::SharedServiceGetStructResult) = getStruct(
       key: i32 => 1,
-    ) -> SharedStruct => [
+    ) -> crate::shared::SharedStruct => [
     ],
-  ],
-  bounds = [A: Calculator, B: SharedService, ],
-  fields = [a: A, b: B, ]
+  ]
 }
 